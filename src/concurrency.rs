@@ -0,0 +1,266 @@
+use crate::{
+  config::Config,
+  run::{CancelToken, RunContext},
+  status::sytter_status_update,
+  sytter::ThreadedSytter,
+  trigger::TriggerEvent,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::VecDeque,
+  panic::{catch_unwind, AssertUnwindSafe},
+  sync::{
+    mpsc::{Receiver, RecvTimeoutError, Sender},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+use tracing::*;
+
+/// What a Sytter does with an event that arrives while it is already running.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConcurrencyPolicy {
+  /// Run it afterwards, holding up to `queue_size` events.  Events that arrive
+  /// when the queue is full are dropped.
+  #[default]
+  Queue,
+  /// Ignore it.
+  Skip,
+  /// Cancel the current run and start a new one with this event.
+  Replace,
+  /// Start another run alongside the current one.
+  Parallel,
+}
+
+pub fn concurrency_default_queue_size() -> usize {
+  10
+}
+
+/// Everything the dispatcher reacts to.  Runs report back on the same channel
+/// as the triggers so the dispatcher only ever has to wait on one thing.
+#[derive(Debug)]
+pub enum DispatchMessage {
+  Event(TriggerEvent),
+  RunFinished(String),
+  /// Stop taking events and wait up to the grace period for runs in progress
+  /// to finish, cancelling any that don't.  The sender is told once every run
+  /// is over.
+  Shutdown(Duration, Sender<()>),
+}
+
+// How long cancelled runs get to wind down before we stop waiting on them.
+// Scripts are killed well before this.
+const SHUTDOWN_CANCEL_WAIT: Duration = Duration::from_secs(5);
+
+struct DispatcherShutdown {
+  deadline: Instant,
+  cancelled: bool,
+  done: Sender<()>,
+}
+
+struct ActiveRun {
+  run_id: String,
+  cancel: CancelToken,
+}
+
+/// Decides when each event becomes a run.  All of the bookkeeping lives on the
+/// dispatcher's thread, so none of it needs locking.
+pub struct Dispatcher {
+  sytter: Arc<ThreadedSytter>,
+  config: Config,
+  policy: ConcurrencyPolicy,
+  queue_size: usize,
+  send_to_dispatcher: Sender<DispatchMessage>,
+  active: Vec<ActiveRun>,
+  queue: VecDeque<TriggerEvent>,
+  shutdown: Option<DispatcherShutdown>,
+}
+
+impl Dispatcher {
+  pub fn new(
+    sytter: Arc<ThreadedSytter>,
+    config: Config,
+    policy: ConcurrencyPolicy,
+    queue_size: usize,
+    send_to_dispatcher: Sender<DispatchMessage>,
+  ) -> Self {
+    Dispatcher {
+      sytter,
+      config,
+      policy,
+      queue_size,
+      send_to_dispatcher,
+      active: vec![],
+      queue: VecDeque::new(),
+      shutdown: None,
+    }
+  }
+
+  pub fn dispatch(&mut self, receive: Receiver<DispatchMessage>) {
+    loop {
+      let message = match &self.shutdown {
+        None => receive.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        Some(shutdown) => receive.recv_timeout(
+          shutdown.deadline.saturating_duration_since(Instant::now()),
+        ),
+      };
+      match message {
+        Ok(DispatchMessage::Event(event)) if self.shutdown.is_some() => {
+          warn!(
+            "{}: Shutting down, dropping {} event.",
+            self.sytter.name, event.kind,
+          );
+          sytter_status_update(&self.sytter.name, |s| s.events_dropped += 1);
+        }
+        Ok(DispatchMessage::Event(event)) => self.event_handle(event),
+        Ok(DispatchMessage::RunFinished(run_id)) => {
+          self.active.retain(|r| r.run_id != run_id);
+          if self.active.is_empty() {
+            if let Some(event) = self.queue.pop_front() {
+              self.run_start(event);
+            }
+          }
+        }
+        Ok(DispatchMessage::Shutdown(grace, done)) => {
+          self.shutdown_begin(grace, done)
+        }
+        Err(RecvTimeoutError::Timeout) => {
+          if !self.shutdown_grace_expire() {
+            break;
+          }
+        }
+        Err(RecvTimeoutError::Disconnected) => break,
+      }
+      if let Some(shutdown) = &self.shutdown {
+        if self.active.is_empty() {
+          info!("{}: All runs finished.", self.sytter.name);
+          let _ = shutdown.done.send(());
+          break;
+        }
+      }
+    }
+  }
+
+  fn shutdown_begin(&mut self, grace: Duration, done: Sender<()>) {
+    let name = &self.sytter.name;
+    if !self.queue.is_empty() {
+      warn!(
+        "{}: Shutting down, dropping {} queued events.",
+        name,
+        self.queue.len(),
+      );
+      let dropped = self.queue.len() as u64;
+      sytter_status_update(name, |s| s.events_dropped += dropped);
+      self.queue.clear();
+    }
+    info!(
+      "{}: Shutting down, waiting up to {:?} for {} runs.",
+      name,
+      grace,
+      self.active.len(),
+    );
+    self.shutdown = Some(DispatcherShutdown {
+      deadline: Instant::now() + grace,
+      cancelled: false,
+      done,
+    });
+  }
+
+  /// Cancel whatever is still running once the grace period is up.  Returns
+  /// false when even the cancelled runs have taken too long, and it's time to
+  /// stop waiting.
+  fn shutdown_grace_expire(&mut self) -> bool {
+    match &mut self.shutdown {
+      Some(shutdown) if !shutdown.cancelled => {
+        warn!(
+          "{}: Grace period is over, cancelling {} runs.",
+          self.sytter.name,
+          self.active.len(),
+        );
+        self.active.iter().for_each(|r| r.cancel.cancel());
+        shutdown.cancelled = true;
+        shutdown.deadline = Instant::now() + SHUTDOWN_CANCEL_WAIT;
+        true
+      }
+      Some(shutdown) => {
+        error!(
+          "{}: {} cancelled runs did not finish, giving up on them.",
+          self.sytter.name,
+          self.active.len(),
+        );
+        let _ = shutdown.done.send(());
+        false
+      }
+      None => true,
+    }
+  }
+
+  fn event_handle(&mut self, event: TriggerEvent) {
+    let name = self.sytter.name.clone();
+    sytter_status_update(&name, |s| s.events_received += 1);
+    match self.policy {
+      _ if self.active.is_empty() => self.run_start(event),
+      ConcurrencyPolicy::Parallel => self.run_start(event),
+      ConcurrencyPolicy::Queue => {
+        if self.queue.len() < self.queue_size {
+          debug!("{}: Busy, queueing {} event.", name, event.kind);
+          sytter_status_update(&name, |s| s.events_queued += 1);
+          self.queue.push_back(event);
+        } else {
+          warn!(
+            "{}: Queue is full ({} events), dropping {} event.",
+            name, self.queue_size, event.kind,
+          );
+          sytter_status_update(&name, |s| s.events_dropped += 1);
+        }
+      }
+      ConcurrencyPolicy::Skip => {
+        warn!("{}: Busy, skipping {} event.", name, event.kind);
+        sytter_status_update(&name, |s| s.events_skipped += 1);
+      }
+      ConcurrencyPolicy::Replace => {
+        self.active.iter().for_each(|r| {
+          warn!("{}: Replacing run {}.", name, r.run_id);
+          r.cancel.cancel();
+        });
+        sytter_status_update(&name, |s| s.runs_replaced += 1);
+        // The new run starts once the cancelled one has wound down.  Only the
+        // newest event is worth keeping until then.
+        if let Some(older) = self.queue.pop_front() {
+          warn!("{}: Dropping {} event, replaced.", name, older.kind);
+          sytter_status_update(&name, |s| s.events_dropped += 1);
+        }
+        self.queue.push_back(event);
+      }
+    }
+  }
+
+  fn run_start(&mut self, event: TriggerEvent) {
+    let context = RunContext::new(&self.sytter.name, event);
+    self.active.push(ActiveRun {
+      run_id: context.run_id.clone(),
+      cancel: context.cancel.clone(),
+    });
+    sytter_status_update(&self.sytter.name, |s| s.runs_started += 1);
+    let sytter = self.sytter.clone();
+    let config = self.config.clone();
+    let send = self.send_to_dispatcher.clone();
+    std::thread::spawn(move || {
+      let run_id = context.run_id.clone();
+      // The dispatcher must hear that the run is over no matter how it ended,
+      // or it would wait on the run forever.
+      if catch_unwind(AssertUnwindSafe(|| {
+        sytter.trigger_execute_on_message(&config, context)
+      }))
+      .is_err()
+      {
+        error!("{}: Run {} panicked.", sytter.name, run_id);
+        sytter_status_update(&sytter.name, |s| s.runs_panicked += 1);
+      }
+      let _ = send
+        .send(DispatchMessage::RunFinished(run_id))
+        .inspect_err(|e| error!("Could not report finished run: {:?}", e));
+    });
+  }
+}
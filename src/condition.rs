@@ -1,11 +1,53 @@
 use dyn_clone::DynClone;
+use serde::{Deserialize, Serialize};
 
-use crate::{config::Config, error::AppError};
+use crate::{config::Config, error::AppError, run::RunContext};
 use core::fmt::Debug;
 
 #[typetag::serde(tag = "type")]
 pub trait Condition: Debug + Sync + Send + DynClone {
-  fn check_condition(&self, config: &Config) -> Result<bool, AppError>;
+  fn check_condition(
+    &self,
+    config: &Config,
+    context: &RunContext,
+  ) -> Result<bool, AppError>;
 }
 
 dyn_clone::clone_trait_object!(Condition);
+
+/// How a list of conditions is combined into a single answer.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConditionMode {
+  #[default]
+  All,
+  Any,
+}
+
+/// Evaluate conditions in declaration order, stopping as soon as the answer is
+/// known.  This matters because conditions are usually scripts with side
+/// effects and a cost, so we don't want to run more of them than we need to.
+/// An empty list is vacuously true for `All` and false for `Any`.
+pub fn conditions_check(
+  mode: &ConditionMode,
+  conditions: &[Box<dyn Condition>],
+  config: &Config,
+  context: &RunContext,
+) -> Result<bool, AppError> {
+  match mode {
+    ConditionMode::All => conditions.iter().try_fold(true, |acc, c| {
+      if acc {
+        c.check_condition(config, context)
+      } else {
+        Ok(false)
+      }
+    }),
+    ConditionMode::Any => conditions.iter().try_fold(false, |acc, c| {
+      if acc {
+        Ok(true)
+      } else {
+        c.check_condition(config, context)
+      }
+    }),
+  }
+}
@@ -1,6 +1,6 @@
-use crate::error::AppError;
+use crate::{deserialize::duration_des, error::AppError};
 use clap::Parser;
-use std::env::var;
+use std::{env::var, time::Duration};
 use tracing::Level;
 
 // Without a structopt declaration, the argument is positional.
@@ -11,6 +11,11 @@ pub struct CliConfig {
   pub sytters_path: Option<String>,
   #[arg(short, long, help = "Log level: trace, debug, info, warn, error")]
   pub log_level: Option<String>,
+  #[arg(
+    long,
+    help = "How long runs get to finish when shutting down, such as 10s"
+  )]
+  pub shutdown_grace_period: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -18,12 +23,14 @@ pub struct Config {
   pub sytters_path: String,
   pub log_level: Level,
   pub http_port: usize,
+  pub shutdown_grace_period: Duration,
 }
 
 pub struct EnvConfig {
   pub sytters_path: Option<String>,
   pub log_level: Option<String>,
   pub http_port: Option<usize>,
+  pub shutdown_grace_period: Option<String>,
 }
 
 // TODO: Remove this, since clap handles this now.
@@ -36,6 +43,7 @@ pub fn env_config_load() -> Result<EnvConfig, AppError> {
       .map_err(AppError::ConfigEnvVarError)
       .ok(),
     http_port: var("sytter_http_port").ok().and_then(|s| s.parse().ok()),
+    shutdown_grace_period: var("sytter_shutdown_grace_period").ok(),
   };
   Ok(config)
 }
@@ -51,6 +59,11 @@ fn parse_log_level(level_str: &str) -> Result<Level, AppError> {
   }
 }
 
+fn parse_shutdown_grace_period(s: &str) -> Result<Duration, AppError> {
+  duration_des(&toml::Value::String(s.to_string()))
+    .map_err(AppError::ConfigInvalidShutdownGracePeriod)
+}
+
 pub fn config_cli_merge(
   env_config: EnvConfig,
   cli_config: CliConfig,
@@ -67,6 +80,12 @@ pub fn config_cli_merge(
       .or(env_config.sytters_path)
       .unwrap_or("~/.config/sytter/sytters".to_string()),
     log_level: parse_log_level(&log_level_str)?,
+    shutdown_grace_period: parse_shutdown_grace_period(
+      &cli_config
+        .shutdown_grace_period
+        .or(env_config.shutdown_grace_period)
+        .unwrap_or("10s".to_string()),
+    )?,
   })
 }
 
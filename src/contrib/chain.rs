@@ -0,0 +1,218 @@
+use crate::{
+  error::AppError,
+  run::{run_reports_subscribe, RunOutcome, RunReport},
+  shell::run_context_envs,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  str::FromStr,
+  sync::{
+    mpsc::{Receiver, RecvTimeoutError, SyncSender, TryRecvError},
+    Mutex,
+  },
+  time::Duration,
+};
+use toml::Table;
+use tracing::*;
+
+/// Follows another Sytter, firing when one of its runs ends.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChainTrigger {
+  // The Sytter to follow.
+  pub sytter: String,
+  // Which outcome to fire on.  Any of them if not given.
+  pub outcome: Option<RunOutcome>,
+}
+
+lazy_static! {
+  // Each followed Sytter, and the Sytters that follow it, from every sytter
+  // trigger loaded so far.
+  static ref CHAIN_FOLLOWERS: Mutex<BTreeMap<String, BTreeSet<String>>> =
+    Mutex::new(BTreeMap::new());
+}
+
+/// Note that `follower` follows `followed`, unless that would close a circle of
+/// Sytters setting each other off forever.  Then it gives the circle, starting
+/// and ending with `followed`.
+fn chain_follow_register(
+  follower: &str,
+  followed: &str,
+) -> Result<(), Vec<String>> {
+  let mut followers = CHAIN_FOLLOWERS.lock().unwrap_or_else(|e| e.into_inner());
+  // Look for a way from the follower back around to the one it follows.
+  let mut came_from: BTreeMap<&str, &str> = BTreeMap::new();
+  let mut next = vec![follower];
+  while let Some(sytter) = next.pop() {
+    if sytter == followed {
+      let mut circle = vec![followed.to_string()];
+      let mut at = followed;
+      while at != follower {
+        at = came_from[at];
+        circle.push(at.to_string());
+      }
+      circle.reverse();
+      circle.insert(0, followed.to_string());
+      return Err(circle);
+    }
+    for downstream in followers.get(sytter).into_iter().flatten() {
+      if downstream != follower && !came_from.contains_key(downstream.as_str())
+      {
+        came_from.insert(downstream, sytter);
+        next.push(downstream);
+      }
+    }
+  }
+  followers
+    .entry(followed.to_string())
+    .or_default()
+    .insert(follower.to_string());
+  Ok(())
+}
+
+fn chain_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in sytter Trigger {}",
+    field, message,
+  ))
+}
+
+pub fn chain_trigger_toml_deserialize(
+  sytter_name: &str,
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let string_field = |field: &str| -> Result<Option<String>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| {
+        x.as_str()
+          .map(|s| s.to_string())
+          .ok_or(chain_field_error(field, "must be a string."))
+      })
+      .transpose()
+  };
+  let sytter =
+    string_field("sytter")?.ok_or(AppError::SytterDeserializeRawError(
+      "Field 'sytter' missing from sytter Trigger.".to_string(),
+    ))?;
+  // Every run would start another, forever.
+  if sytter == sytter_name {
+    return Err(chain_field_error(
+      "sytter",
+      "can't be the Sytter the trigger is in.",
+    ));
+  }
+  let outcome = match string_field("outcome")?.as_deref() {
+    None | Some("any") => None,
+    Some(outcome) => Some(RunOutcome::from_str(outcome).map_err(|_| {
+      chain_field_error(
+        "outcome",
+        &format!(
+          "must be 'succeeded', 'failed', 'condition-false', or 'any', not \
+           '{}'.",
+          outcome,
+        ),
+      )
+    })?),
+  };
+  // Whichever Sytter would close the circle is the one that fails to load.
+  chain_follow_register(sytter_name, &sytter).map_err(|circle| {
+    chain_field_error(
+      "sytter",
+      &format!(
+        "would make Sytters set each other off forever: {}.",
+        circle.join(" -> "),
+      ),
+    )
+  })?;
+  Ok(Box::new(ChainTrigger { sytter, outcome }))
+}
+
+// Runs are waited on in slices this long, so a shutdown doesn't have to wait
+// for one.
+const CHAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The upstream run as the downstream one sees it.  Everything a script in the
+/// upstream run could see as sytter_<name> is here as upstream_<name>, such as
+/// upstream_run_id or upstream_step_0_exit_code.
+fn chain_event(report: &RunReport) -> TriggerEvent {
+  let mut event = TriggerEvent::new("sytter")
+    .fields(
+      run_context_envs(&report.context)
+        .into_iter()
+        .map(|(k, v)| {
+          (
+            format!("upstream_{}", k.strip_prefix("sytter_").unwrap_or(&k)),
+            v,
+          )
+        })
+        .collect(),
+    )
+    .field("upstream_outcome", &report.outcome);
+  // The last executor to fail is the one that stopped the run.
+  if let Some(exit_code) = report
+    .context
+    .step_results
+    .iter()
+    .rev()
+    .find_map(|r| r.exit_code)
+  {
+    event = event.field("upstream_exit_code", exit_code);
+  }
+  if let Some(error) = &report.error {
+    event = event.field("upstream_error", error);
+  }
+  event
+}
+
+#[typetag::serde]
+impl Trigger for ChainTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let reports = run_reports_subscribe();
+    info!(
+      "Following {} for {} runs.",
+      self.sytter,
+      self
+        .outcome
+        .as_ref()
+        .map(|o| o.to_string())
+        .unwrap_or("any".to_string()),
+    );
+    loop {
+      match reports.recv_timeout(CHAIN_POLL_INTERVAL) {
+        Ok(report)
+          if report.context.sytter_name == self.sytter
+            && self.outcome.as_ref().is_none_or(|o| *o == report.outcome) =>
+        {
+          debug!(
+            "{} run {} was {}.",
+            self.sytter, report.context.run_id, report.outcome,
+          );
+          if let Err(e) = send_to_sytter.send(chain_event(&report)) {
+            debug!("Error triggering Sytter from sytter trigger: {:?}", e);
+          }
+        }
+        Ok(_) | Err(RecvTimeoutError::Timeout) => (),
+        // Runs keep every subscription they hand out.
+        Err(RecvTimeoutError::Disconnected) => {
+          return Err(AppError::TriggerRuntimeError(
+            "Stopped hearing about runs.".to_string(),
+          ))
+        }
+      }
+      match receive_from_sytter.try_recv() {
+        Err(TryRecvError::Empty) => (),
+        Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+          debug!("Sytter is closing. No longer following {}.", self.sytter);
+          return Ok(());
+        }
+      }
+    }
+  }
+}
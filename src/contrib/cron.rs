@@ -1,8 +1,11 @@
-use crate::{error::AppError, trigger::Trigger};
+use crate::{
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
 use cron::Schedule;
 use job_scheduler_ng::{Job, JobScheduler};
 use serde::{Deserialize, Serialize};
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender};
 use tap::Tap;
 use toml::Table;
 use tracing::{debug, info, trace};
@@ -30,11 +33,11 @@ pub fn cron_trigger_toml_deserialize(
 impl Trigger for CronTrigger {
   fn trigger_await(
     &mut self,
-    send_to_sytter: SyncSender<String>,
-    _receive_from_sytter: Receiver<String>,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
   ) -> Result<(), AppError> {
     let mut sched = JobScheduler::new();
-    // let send_to_sytter_threaded = send_to_sytter.clone();
+    let cron = self.cron.clone();
     sched.add(
       Job::new(
         self.cron.parse::<Schedule>().map_err(|e| {
@@ -45,9 +48,9 @@ impl Trigger for CronTrigger {
         })?,
         move || {
           info!("Cron trigger fired!");
-          // We don't really have a meaningful message to send, I
-          // think. Not yet. For now we just need to send _something_.
-          match send_to_sytter.send("foo".to_string()) {
+          match send_to_sytter
+            .send(TriggerEvent::new("cron").field("cron", &cron))
+          {
             Ok(_) => {
               debug!("Successfully sent message to Sytter.")
             }
@@ -77,12 +80,19 @@ impl Trigger for CronTrigger {
     // })?;
     loop {
       sched.tick();
-      std::thread::sleep(
+      // Waiting on the Sytter instead of sleeping lets a shutdown interrupt
+      // a long wait between jobs.
+      match receive_from_sytter.recv_timeout(
         sched
           .time_till_next_job()
           .tap(|t| trace!("Time until next cron tick: {:?}ms", t)),
-      );
+      ) {
+        Err(RecvTimeoutError::Timeout) => (),
+        Ok(TriggerControl::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+          debug!("Cron trigger '{}' shutting down.", self.cron);
+          return Ok(());
+        }
+      }
     }
-    // Ok(())
   }
 }
@@ -1,21 +1,39 @@
-use crate::{error::AppError, trigger::Trigger};
+#[cfg(target_os = "macos")]
+use crate::trigger::trigger_shutdown_await;
+use crate::{
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
 
+#[cfg(target_os = "linux")]
+use crate::linux::device::{device_connection_listen, LinuxSubsystem};
 #[cfg(target_os = "macos")]
 use crate::macos::device::device_connection_listen_start;
 use serde::{Deserialize, Serialize};
 use std::any::type_name;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use std::sync::mpsc::{Receiver, SyncSender};
-use strum_macros::EnumString;
+use strum_macros::{Display, EnumString};
 use toml::Table;
 use tracing::*;
 
-#[derive(Clone, Debug, Deserialize, EnumString, PartialEq, Serialize)]
+#[derive(
+  Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
+)]
 pub enum DeviceConnectionEvent {
   Add,
   Remove,
 }
 
+/// A device event along with what the OS could tell us about the device.
+/// Property keys become trigger event fields, such as "device_vendor_id".
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceConnection {
+  pub event: DeviceConnectionEvent,
+  pub properties: BTreeMap<String, String>,
+}
+
 /// Platform-agnostic device types
 #[derive(Clone, Debug, Deserialize, EnumString, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase")]
@@ -38,6 +56,10 @@ pub struct DeviceConnectionTrigger {
   pub macos_device_class: Option<String>,
   #[serde(default)]
   pub macos_notification_type: Option<String>,
+
+  // Linux-specific override (if specified, device_types is ignored)
+  #[serde(default)]
+  pub linux_subsystem: Option<String>,
 }
 
 impl DeviceConnectionTrigger {
@@ -86,6 +108,40 @@ impl DeviceConnectionTrigger {
       DeviceType::Network => "IONetworkInterface".to_string(),
     }
   }
+
+  /// Get the subsystems to monitor, preferring the Linux override, then
+  /// mapping from device_types
+  #[cfg(target_os = "linux")]
+  fn get_linux_subsystems(&self) -> Vec<LinuxSubsystem> {
+    if let Some(ref subsystem) = self.linux_subsystem {
+      return vec![LinuxSubsystem {
+        subsystem: subsystem.clone(),
+        device_type: None,
+      }];
+    }
+    self
+      .expand_device_types()
+      .iter()
+      .map(Self::map_device_type_to_linux)
+      .collect()
+  }
+
+  /// Map platform-agnostic device types to Linux (udev) subsystems
+  #[cfg(target_os = "linux")]
+  fn map_device_type_to_linux(device_type: &DeviceType) -> LinuxSubsystem {
+    let (subsystem, device_type) = match device_type {
+      DeviceType::Any => panic!("Any should be expanded before mapping"),
+      // Each of a USB device's interfaces gets its own uevents too, but only
+      // the device itself is wanted.
+      DeviceType::Usb => ("usb", Some("usb_device")),
+      DeviceType::Storage => ("block", None),
+      DeviceType::Network => ("net", None),
+    };
+    LinuxSubsystem {
+      subsystem: subsystem.to_string(),
+      device_type: device_type.map(|t| t.to_string()),
+    }
+  }
 }
 
 pub fn device_connection_event_deserialize(
@@ -110,10 +166,14 @@ pub fn device_connection_toml_deserialize(
       ))
     })?;
 
-  // Validate that we have either device_types or macos_device_class
-  if trigger.device_types.is_empty() && trigger.macos_device_class.is_none() {
+  // Validate that we have device_types or an OS-specific override
+  if trigger.device_types.is_empty()
+    && trigger.macos_device_class.is_none()
+    && trigger.linux_subsystem.is_none()
+  {
     return Err(AppError::SytterDeserializeRawError(
-      "Either 'device_types' or 'macos_device_class' must be specified"
+      "Either 'device_types', 'macos_device_class', or 'linux_subsystem' must \
+       be specified"
         .to_string(),
     ));
   }
@@ -125,10 +185,11 @@ pub fn device_connection_toml_deserialize(
 impl Trigger for DeviceConnectionTrigger {
   fn trigger_await(
     &mut self,
-    send_to_sytter: SyncSender<String>,
-    _receive_from_sytter: Receiver<String>,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
   ) -> Result<(), AppError> {
-    if cfg!(target_os = "macos") {
+    #[cfg(target_os = "macos")]
+    {
       let device_classes = self.get_device_classes();
       let notification_type = self.get_notification_type();
 
@@ -144,15 +205,13 @@ impl Trigger for DeviceConnectionTrigger {
       }
 
       // Register a listener for each device class
-      let mut _cleanup_fns = Vec::new();
+      let mut cleanup_fns = Vec::new();
 
       for device_class in device_classes {
         let send_to_sytter_for_this_listener = send_to_sytter.clone();
         let events_for_this_listener = events.clone();
         let device_class_name = device_class.clone();
 
-        // TODO: Connect plumbing such that any trigger can be shut down via
-        // a cleanup function that is returned when listening.
         debug!(
           "Calling device_connection_listen_start for device class: {}...",
           device_class
@@ -160,20 +219,25 @@ impl Trigger for DeviceConnectionTrigger {
         let cleanup_fn = device_connection_listen_start(
           &device_class,
           &notification_type,
-          Box::new(move |p: DeviceConnectionEvent| {
+          Box::new(move |p: DeviceConnection| {
             debug!(
               "Device event received for {}: {:?} (trigger watching: {:?})",
               device_class_name,
               p,
               events_for_this_listener,
             );
-            if events_for_this_listener.contains(&p) {
+            if events_for_this_listener.contains(&p.event) {
               info!(
                 "Device event {:?} for {} matches trigger, signaling sytter",
                 p,
                 device_class_name
               );
-              match send_to_sytter_for_this_listener.send("foo".to_string()) {
+              match send_to_sytter_for_this_listener.send(
+                TriggerEvent::new("device-connection")
+                  .field("device_event", &p.event)
+                  .field("device_class", &device_class_name)
+                  .fields(p.properties.clone()),
+              ) {
                 Ok(_) => info!(
                   "Successfully signaled sytter for device event {:?} ({})",
                   p,
@@ -199,22 +263,64 @@ impl Trigger for DeviceConnectionTrigger {
         .inspect(|_| info!("Device connection listener successfully registered for {}", device_class))
         .inspect_err(|e| error!("Failed to register device connection listener for {}: {:?}", device_class, e))?;
 
-        _cleanup_fns.push(cleanup_fn);
+        cleanup_fns.push(cleanup_fn);
       }
 
-      info!("Device connection trigger is now active with {} listeners waiting for events", _cleanup_fns.len());
-      // None of this is called at the right time. trigger_await is async so we
-      // can make the setup happen on its own thread. In the case of
-      // macos/device.rs we already have a thread. I'm not sure if that overhead
-      // is necessary but whatever.
-      // This is the event to clean up. Just block on it.
-      // let _ = receive_from_sytter.recv();
-      // debug!("Sytter is closing. Cleaning up power hooks...");
-      // for cleanup_fn in cleanup_fns {
-      //   cleanup_fn()?;
-      // }
-      // debug!("Device connection listener cleanup done!");
-    } else {
+      info!("Device connection trigger is now active with {} listeners waiting for events", cleanup_fns.len());
+      // Each listener has its own thread, so all that's left for us is to wait
+      // until it's time to clean up.
+      trigger_shutdown_await(&receive_from_sytter);
+      debug!("Sytter is closing. Cleaning up device connection listeners...");
+      // Every listener gets cleaned up, even if an earlier one fails.
+      cleanup_fns
+        .into_iter()
+        .map(|cleanup_fn| cleanup_fn())
+        .collect::<Vec<Result<(), AppError>>>()
+        .into_iter()
+        .collect::<Result<(), AppError>>()?;
+      debug!("Device connection listener cleanup done!");
+    }
+    #[cfg(target_os = "linux")]
+    {
+      let subsystems = self.get_linux_subsystems();
+      info!(
+        "Device connection trigger starting. Events: {:?}, Device types: {:?}, Linux subsystems: {:?}",
+        self.events, self.device_types, subsystems,
+      );
+      let events = self.events.clone();
+      device_connection_listen(
+        &subsystems,
+        |subsystem, p| {
+          if events.contains(&p.event) {
+            info!(
+              "Device event {:?} for {} matches trigger, signaling sytter",
+              p, subsystem.subsystem,
+            );
+            if let Err(e) = send_to_sytter.send(
+              TriggerEvent::new("device-connection")
+                .field("device_event", &p.event)
+                .field("device_class", &subsystem.subsystem)
+                .fields(p.properties),
+            ) {
+              error!(
+                "Failed to signal sytter for device event ({}): {:?}",
+                subsystem.subsystem, e,
+              );
+            }
+          } else {
+            debug!(
+              "Device event {:?} for {} does not match trigger (expecting {:?}), skipping",
+              p, subsystem.subsystem, events,
+            );
+          }
+        },
+        &receive_from_sytter,
+      )?;
+      debug!("Device connection listener cleanup done!");
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+      let _ = (send_to_sytter, receive_from_sytter);
       error!("OS not supported for device connection events!");
     }
     Ok(())
@@ -0,0 +1,135 @@
+#[cfg(target_os = "linux")]
+use crate::linux::file_watch::file_watch_listen;
+use crate::{
+  deserialize::duration_des,
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::{
+  str::FromStr,
+  sync::mpsc::{Receiver, SyncSender},
+  time::Duration,
+};
+use strum_macros::{Display, EnumString};
+use toml::Table;
+
+#[derive(
+  Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
+)]
+pub enum FileWatchEvent {
+  Create,
+  Modify,
+  Delete,
+  Move,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FileWatchTrigger {
+  pub paths: Vec<String>,
+  pub events: Vec<FileWatchEvent>,
+  pub recursive: bool,
+  // How long a path has to go without changing before its event is sent.
+  // Keeps a file that is still being written from firing over and over.
+  pub settle: Option<Duration>,
+}
+
+fn file_watch_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in file-watch Trigger {}",
+    field, message,
+  ))
+}
+
+fn file_watch_strings_deserialize(
+  section_data: &Table,
+  field: &str,
+) -> Result<Option<Vec<String>>, AppError> {
+  section_data
+    .get(field)
+    .map(|x| {
+      x.as_array()
+        .and_then(|xs| {
+          xs.iter()
+            .map(|s| s.as_str().map(|s| s.to_string()))
+            .collect::<Option<Vec<String>>>()
+        })
+        .ok_or(file_watch_field_error(field, "must be a list of strings."))
+    })
+    .transpose()
+}
+
+pub fn file_watch_trigger_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let paths = file_watch_strings_deserialize(section_data, "paths")?
+    .filter(|paths| !paths.is_empty())
+    .ok_or(file_watch_field_error("paths", "is missing or empty."))?;
+  // Catch bad globs when the Sytter loads, rather than on every restart.
+  paths.iter().try_for_each(|p| {
+    Pattern::new(p).map(|_| ()).map_err(|e| {
+      file_watch_field_error("paths", &format!("has a bad glob '{}': {}", p, e))
+    })
+  })?;
+  Ok(Box::new(FileWatchTrigger {
+    paths,
+    events: match file_watch_strings_deserialize(section_data, "events")? {
+      None => vec![
+        FileWatchEvent::Create,
+        FileWatchEvent::Modify,
+        FileWatchEvent::Delete,
+        FileWatchEvent::Move,
+      ],
+      Some(events) => events
+        .iter()
+        .map(|e| {
+          FileWatchEvent::from_str(e).map_err(|_| {
+            file_watch_field_error(
+              "events",
+              &format!(
+                "has '{}', but only Create, Modify, Delete, and Move are \
+                 supported.",
+                e,
+              ),
+            )
+          })
+        })
+        .collect::<Result<Vec<FileWatchEvent>, AppError>>()?,
+    },
+    recursive: match section_data.get("recursive") {
+      None => false,
+      Some(x) => x.as_bool().ok_or(file_watch_field_error(
+        "recursive",
+        "must be true or false.",
+      ))?,
+    },
+    settle: section_data
+      .get("settle")
+      .map(|x| {
+        duration_des(x).map_err(|e| file_watch_field_error("settle", &e))
+      })
+      .transpose()?,
+  }))
+}
+
+#[typetag::serde]
+impl Trigger for FileWatchTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    #[cfg(target_os = "linux")]
+    {
+      file_watch_listen(self, send_to_sytter, receive_from_sytter)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+      let _ = (send_to_sytter, receive_from_sytter);
+      Err(AppError::TriggerInitializeError(
+        "File watch triggers are only supported on Linux.".to_string(),
+      ))
+    }
+  }
+}
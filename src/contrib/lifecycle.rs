@@ -0,0 +1,288 @@
+use crate::{
+  deserialize::duration_des,
+  error::AppError,
+  run::CancelToken,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::BTreeMap,
+  str::FromStr,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc::{
+      channel, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError,
+    },
+    Arc, Mutex,
+  },
+  time::{Duration, Instant},
+};
+use strum_macros::{Display, EnumString};
+use toml::Table;
+use tracing::*;
+
+#[derive(
+  Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
+)]
+pub enum LifecycleEvent {
+  Startup,
+  Shutdown,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LifecycleTrigger {
+  // The Sytter this belongs to, which is how shutdowns find it.
+  pub sytter: String,
+  pub events: Vec<LifecycleEvent>,
+  // How long after the trigger starts to fire Startup.
+  pub delay: Duration,
+  // How long the Shutdown run gets before it is cancelled, up to the shutdown
+  // grace period.
+  pub deadline: Duration,
+  // Startup only fires once, even if the trigger is restarted.
+  #[serde(skip)]
+  pub startup_fired: bool,
+}
+
+fn lifecycle_default_deadline() -> Duration {
+  Duration::from_secs(10)
+}
+
+fn lifecycle_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in lifecycle Trigger {}",
+    field, message,
+  ))
+}
+
+pub fn lifecycle_trigger_toml_deserialize(
+  sytter_name: &str,
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let duration_field = |field: &str| -> Result<Option<Duration>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| duration_des(x).map_err(|e| lifecycle_field_error(field, &e)))
+      .transpose()
+  };
+  let events = section_data
+    .get("events")
+    .ok_or(AppError::SytterDeserializeRawError(
+      "Field 'events' missing from lifecycle Trigger.".to_string(),
+    ))?
+    .as_array()
+    .ok_or(lifecycle_field_error(
+      "events",
+      "must be a list of strings.",
+    ))?
+    .iter()
+    .map(|e| {
+      e.as_str()
+        .and_then(|e| LifecycleEvent::from_str(e).ok())
+        .ok_or(lifecycle_field_error(
+          "events",
+          &format!("has an unknown event '{}'.", e),
+        ))
+    })
+    .collect::<Result<Vec<LifecycleEvent>, AppError>>()?;
+  Ok(Box::new(LifecycleTrigger {
+    sytter: sytter_name.to_string(),
+    events,
+    delay: duration_field("delay")?.unwrap_or(Duration::ZERO),
+    deadline: duration_field("deadline")?
+      .unwrap_or(lifecycle_default_deadline()),
+    startup_fired: false,
+  }))
+}
+
+/// What a trigger is sent when its Sytter shuts down.
+struct LifecycleShutdown {
+  // Dropped by the trigger once its run is over, which is how the shutdown
+  // hears about it.
+  done: Sender<()>,
+  // The shutdown grace period, which caps the trigger's deadline.
+  grace: Duration,
+}
+
+// Keyed by Sytter name and a number for each trigger, since a Sytter can have
+// more than one.
+type LifecycleListeners = BTreeMap<(String, u64), Sender<LifecycleShutdown>>;
+
+lazy_static! {
+  // Triggers that fire on Shutdown add themselves while they are running.
+  static ref LIFECYCLE_SHUTDOWN_LISTENERS: Arc<Mutex<LifecycleListeners>> =
+    Arc::new(Mutex::new(BTreeMap::new()));
+}
+
+static LIFECYCLE_LISTENER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Stops the trigger from hearing about shutdowns, however it stops.
+struct LifecycleRegistration((String, u64));
+
+impl Drop for LifecycleRegistration {
+  fn drop(&mut self) {
+    if let Ok(mut listeners) = LIFECYCLE_SHUTDOWN_LISTENERS.lock() {
+      listeners.remove(&self.0);
+    }
+  }
+}
+
+/// Fire the Sytter's Shutdown triggers, and wait until each of their runs is
+/// over or has been cancelled for going past its deadline.  No deadline is
+/// longer than `grace`, the same as any other run gets.
+pub fn lifecycle_shutdown_run(sytter_name: &str, grace: Duration) {
+  let listeners = LIFECYCLE_SHUTDOWN_LISTENERS
+    .lock()
+    .map(|listeners| {
+      listeners
+        .iter()
+        .filter(|((sytter, _), _)| sytter == sytter_name)
+        .map(|(_, send)| send.clone())
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  if listeners.is_empty() {
+    return;
+  }
+  info!(
+    "{}: Running {} Shutdown triggers...",
+    sytter_name,
+    listeners.len(),
+  );
+  listeners
+    .into_iter()
+    .filter_map(|send_to_trigger| {
+      let (send_done, receive_done) = channel::<()>();
+      send_to_trigger
+        .send(LifecycleShutdown {
+          done: send_done,
+          grace,
+        })
+        .ok()
+        .map(|_| receive_done)
+    })
+    .collect::<Vec<_>>()
+    .into_iter()
+    .for_each(|receive_done| {
+      // Nothing is sent.  The trigger drops its end when it's done.
+      let _ = receive_done.recv();
+    });
+}
+
+// Shutdowns are waited on in slices this long, so the Sytter's own shutdown
+// doesn't have to wait for one.
+const LIFECYCLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl LifecycleTrigger {
+  fn event_send(
+    &self,
+    event: TriggerEvent,
+    send_to_sytter: &SyncSender<TriggerEvent>,
+  ) {
+    if let Err(e) = send_to_sytter.send(event) {
+      debug!("Error triggering Sytter from lifecycle trigger: {:?}", e);
+    }
+  }
+
+  /// Fire Shutdown, and give the run until the deadline to finish.  The event
+  /// holds the other end of the channel, so it's closed once the run is over,
+  /// including when it's over because it was cancelled.
+  fn shutdown_run(
+    &self,
+    send_to_sytter: &SyncSender<TriggerEvent>,
+    grace: Duration,
+  ) {
+    let deadline = self.deadline.min(grace);
+    let (send_finished, run_finished) = channel::<()>();
+    let cancel = CancelToken::default();
+    self.event_send(
+      TriggerEvent::new("lifecycle")
+        .field("lifecycle_event", LifecycleEvent::Shutdown)
+        .hold(send_finished)
+        .cancel(cancel.clone()),
+      send_to_sytter,
+    );
+    match run_finished.recv_timeout(deadline) {
+      Err(RecvTimeoutError::Timeout) => {
+        warn!(
+          "{}: Shutdown run did not finish within {:?}, cancelling it.",
+          self.sytter, deadline,
+        );
+        cancel.cancel();
+        let _ = run_finished.recv();
+        debug!("{}: Shutdown run cancelled.", self.sytter);
+      }
+      Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+        debug!("{}: Shutdown run finished.", self.sytter);
+      }
+    }
+  }
+}
+
+#[typetag::serde]
+impl Trigger for LifecycleTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    // This end is kept even when Shutdown isn't wanted, so waiting on the
+    // channel always waits.
+    let (send_to_trigger, receive_shutdowns) = channel::<LifecycleShutdown>();
+    let _registration = if self.events.contains(&LifecycleEvent::Shutdown) {
+      let key = (
+        self.sytter.clone(),
+        LIFECYCLE_LISTENER_ID.fetch_add(1, Ordering::SeqCst),
+      );
+      LIFECYCLE_SHUTDOWN_LISTENERS
+        .lock()
+        .map_err(|e| {
+          AppError::TriggerInitializeError(format!(
+            "Lifecycle listeners lock poisoned: {:?}",
+            e,
+          ))
+        })?
+        .insert(key.clone(), send_to_trigger.clone());
+      Some(LifecycleRegistration(key))
+    } else {
+      None
+    };
+    let mut startup_at = (self.events.contains(&LifecycleEvent::Startup)
+      && !self.startup_fired)
+      .then(|| Instant::now() + self.delay);
+    info!(
+      "Listening for lifecycle events {:?} in {}.",
+      self.events, self.sytter,
+    );
+    loop {
+      if startup_at.is_some_and(|at| Instant::now() >= at) {
+        debug!("{}: Starting up.", self.sytter);
+        startup_at = None;
+        self.startup_fired = true;
+        self.event_send(
+          TriggerEvent::new("lifecycle")
+            .field("lifecycle_event", LifecycleEvent::Startup),
+          &send_to_sytter,
+        );
+      }
+      match receive_shutdowns.recv_timeout(LIFECYCLE_POLL_INTERVAL) {
+        Ok(shutdown) => {
+          self.shutdown_run(&send_to_sytter, shutdown.grace);
+          drop(shutdown.done);
+        }
+        Err(RecvTimeoutError::Timeout)
+        | Err(RecvTimeoutError::Disconnected) => (),
+      }
+      match receive_from_sytter.try_recv() {
+        Err(TryRecvError::Empty) => (),
+        Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+          debug!(
+            "Sytter is closing. No longer listening for lifecycle events."
+          );
+          return Ok(());
+        }
+      }
+    }
+  }
+}
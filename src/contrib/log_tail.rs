@@ -0,0 +1,348 @@
+use crate::{
+  contrib::stdout::line_captures,
+  deserialize::duration_des,
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+  fs::File,
+  io::{ErrorKind, Read, Seek, SeekFrom},
+  os::unix::fs::MetadataExt,
+  path::{Path, PathBuf},
+  sync::mpsc::{Receiver, RecvTimeoutError, SyncSender},
+  time::Duration,
+};
+use toml::Table;
+use tracing::*;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LogTailTrigger {
+  pub path: PathBuf,
+  pub line_regex: String,
+  // How often to look for new lines.
+  pub interval: Duration,
+  // Where to remember how far into the file we got.
+  pub offset_path: Option<PathBuf>,
+}
+
+fn log_tail_default_interval() -> Duration {
+  Duration::from_secs(1)
+}
+
+fn log_tail_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in log-tail Trigger {}",
+    field, message,
+  ))
+}
+
+fn log_tail_line_regex_compile(pattern: &str) -> Result<Regex, AppError> {
+  Regex::new(pattern).map_err(|e| {
+    log_tail_field_error(
+      "line_regex",
+      &format!("is not a valid regular expression: {}", e),
+    )
+  })
+}
+
+/// Make a name safe to use as a single file name.
+fn log_tail_file_name(name: &str) -> String {
+  name.replace('%', "%25").replace('/', "%2F")
+}
+
+/// Under $XDG_STATE_HOME (~/.local/state by default), named for the Sytter and
+/// the file, so two Sytters tailing the same file don't share an offset.
+fn log_tail_default_offset_path(
+  sytter_name: &str,
+  path: &Path,
+) -> Option<PathBuf> {
+  std::env::var("XDG_STATE_HOME")
+    .ok()
+    .filter(|s| !s.is_empty())
+    .map(PathBuf::from)
+    .or(
+      std::env::var("HOME")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(|h| PathBuf::from(h).join(".local/state")),
+    )
+    .map(|state| {
+      state
+        .join("sytter/log-tail")
+        .join(log_tail_file_name(sytter_name))
+        .join(log_tail_file_name(&path.to_string_lossy()))
+    })
+}
+
+pub fn log_tail_trigger_toml_deserialize(
+  sytter_name: &str,
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let string_field = |field: &str| -> Result<Option<String>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| {
+        x.as_str()
+          .map(|s| s.to_string())
+          .ok_or(log_tail_field_error(field, "must be a string."))
+      })
+      .transpose()
+  };
+  let path = PathBuf::from(string_field("path")?.ok_or(
+    AppError::SytterDeserializeRawError(
+      "Field 'path' missing from log-tail Trigger.".to_string(),
+    ),
+  )?);
+  let trigger = LogTailTrigger {
+    offset_path: string_field("offset_path")?
+      .map(PathBuf::from)
+      .or(log_tail_default_offset_path(sytter_name, &path)),
+    path,
+    line_regex: string_field("line_regex")?.ok_or(
+      AppError::SytterDeserializeRawError(
+        "Field 'line_regex' missing from log-tail Trigger.".to_string(),
+      ),
+    )?,
+    interval: section_data
+      .get("interval")
+      .map(|x| {
+        duration_des(x).map_err(|e| log_tail_field_error("interval", &e))
+      })
+      .transpose()?
+      .unwrap_or(log_tail_default_interval()),
+  };
+  if trigger.interval.is_zero() {
+    return Err(log_tail_field_error("interval", "must be more than zero."));
+  }
+  log_tail_line_regex_compile(&trigger.line_regex)?;
+  Ok(Box::new(trigger))
+}
+
+/// How far into which file we got.  The inode tells whether the file is still
+/// the same one, or was rotated away.
+#[derive(Clone, Debug, PartialEq)]
+struct LogTailOffset {
+  inode: u64,
+  offset: u64,
+}
+
+fn log_tail_offset_read(offset_path: &Path) -> Option<LogTailOffset> {
+  let contents = std::fs::read_to_string(offset_path).ok()?;
+  let (inode, offset) = contents.trim().split_once(' ')?;
+  Some(LogTailOffset {
+    inode: inode.parse().ok()?,
+    offset: offset.parse().ok()?,
+  })
+}
+
+/// Written to the side and moved into place, so a crash can't leave half of
+/// an offset behind.
+fn log_tail_offset_write(
+  offset_path: &Path,
+  offset: &LogTailOffset,
+) -> std::io::Result<()> {
+  if let Some(parent) = offset_path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let mut temporary = offset_path.as_os_str().to_owned();
+  temporary.push(".tmp");
+  std::fs::write(&temporary, format!("{} {}\n", offset.inode, offset.offset))?;
+  std::fs::rename(&temporary, offset_path)
+}
+
+/// The file being followed.  It stays open across rotations until everything
+/// written to it has been read.
+struct LogTailFile {
+  file: File,
+  inode: u64,
+  // How far into the file has been read.
+  position: u64,
+  // The end of the file, which doesn't have its newline yet.
+  partial: Vec<u8>,
+}
+
+impl LogTailFile {
+  /// Open the file if it's there, starting at `start` (the end of the file if
+  /// not given, or the start if the file is shorter than that).
+  fn open(path: &Path, start: Option<u64>) -> Result<Option<Self>, AppError> {
+    let open_error = |e: std::io::Error| {
+      AppError::TriggerRuntimeError(format!(
+        "Could not read '{}': {:?}",
+        path.display(),
+        e,
+      ))
+    };
+    let mut file = match File::open(path) {
+      Ok(file) => file,
+      Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+      Err(e) => return Err(open_error(e)),
+    };
+    let metadata = file.metadata().map_err(open_error)?;
+    let position = match start {
+      Some(start) if start <= metadata.len() => start,
+      Some(_) => 0,
+      None => metadata.len(),
+    };
+    file.seek(SeekFrom::Start(position)).map_err(open_error)?;
+    Ok(Some(LogTailFile {
+      file,
+      inode: metadata.ino(),
+      position,
+      partial: vec![],
+    }))
+  }
+
+  /// Where the next unread line starts.
+  fn offset(&self) -> LogTailOffset {
+    LogTailOffset {
+      inode: self.inode,
+      offset: self.position - self.partial.len() as u64,
+    }
+  }
+
+  /// Every whole line written since the last read.  Lines don't have to be
+  /// UTF-8.
+  fn lines(&mut self) -> std::io::Result<Vec<String>> {
+    // Truncated in place, such as by logrotate's copytruncate.
+    if self.file.metadata()?.len() < self.position {
+      info!("Log file was truncated, reading it from the start.");
+      self.file.seek(SeekFrom::Start(0))?;
+      self.position = 0;
+      self.partial.clear();
+    }
+    let read = self.file.read_to_end(&mut self.partial)?;
+    self.position += read as u64;
+    let Some(end) = self.partial.iter().rposition(|b| *b == b'\n') else {
+      return Ok(vec![]);
+    };
+    let rest = self.partial.split_off(end + 1);
+    let lines = String::from_utf8_lossy(&self.partial)
+      .lines()
+      .map(|l| l.to_string())
+      .collect();
+    self.partial = rest;
+    Ok(lines)
+  }
+
+  /// The end of a file that won't be written to anymore, as a line of its own.
+  fn rest(self) -> Option<String> {
+    Some(String::from_utf8_lossy(&self.partial).to_string())
+      .filter(|l| !l.is_empty())
+  }
+}
+
+impl LogTailTrigger {
+  fn line_send(
+    &self,
+    regex: &Regex,
+    line: String,
+    send_to_sytter: &SyncSender<TriggerEvent>,
+  ) {
+    let Some(captures) = line_captures("log_tail", regex, &line) else {
+      trace!("Line from '{}' skipped: {}", self.path.display(), line);
+      return;
+    };
+    let event = TriggerEvent::new("log-tail")
+      .fields(captures)
+      .field("log_tail_path", self.path.display())
+      .field("log_tail_line", line);
+    if let Err(e) = send_to_sytter.send(event) {
+      debug!("Error triggering Sytter from log-tail trigger: {:?}", e);
+    }
+  }
+
+  fn offset_save(&self, offset: &LogTailOffset) {
+    if let Some(offset_path) = &self.offset_path {
+      if let Err(e) = log_tail_offset_write(offset_path, offset) {
+        warn!(
+          "Could not save the offset for '{}' to '{}': {:?}",
+          self.path.display(),
+          offset_path.display(),
+          e,
+        );
+      }
+    }
+  }
+}
+
+#[typetag::serde]
+impl Trigger for LogTailTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let regex = log_tail_line_regex_compile(&self.line_regex)?;
+    let read_error = |e: std::io::Error| {
+      AppError::TriggerRuntimeError(format!(
+        "Could not read '{}': {:?}",
+        self.path.display(),
+        e,
+      ))
+    };
+    // Pick up where we left off if it's the same file.  If it was rotated
+    // while we weren't looking, all of the new file is unread.  Without an
+    // offset, only lines from now on count.
+    let saved = self.offset_path.as_deref().and_then(log_tail_offset_read);
+    let mut current = LogTailFile::open(&self.path, None)?;
+    if let (Some(file), Some(saved)) = (&current, &saved) {
+      let start = match file.inode == saved.inode {
+        true => saved.offset,
+        false => 0,
+      };
+      current = LogTailFile::open(&self.path, Some(start))?;
+    }
+    let mut saved = current.as_ref().map(|f| f.offset()).or(saved);
+    info!(
+      "Following '{}' every {:?}, from {:?}.",
+      self.path.display(),
+      self.interval,
+      saved,
+    );
+    loop {
+      // Anything that shows up later is new, so all of it is read.
+      if current.is_none() {
+        current = LogTailFile::open(&self.path, Some(0))?;
+      }
+      if let Some(file) = &mut current {
+        for line in file.lines().map_err(read_error)? {
+          self.line_send(&regex, line, &send_to_sytter);
+        }
+        // Once something else is at the path, the old file has been rotated
+        // away.  What was written to it since the last read is read first.
+        // If nothing is there yet, the old file could still be written to.
+        let rotated =
+          std::fs::metadata(&self.path).is_ok_and(|m| m.ino() != file.inode);
+        if rotated {
+          info!("'{}' was rotated.", self.path.display());
+          let lines = file.lines().map_err(read_error)?;
+          let rest = current.take().and_then(LogTailFile::rest);
+          for line in lines.into_iter().chain(rest) {
+            self.line_send(&regex, line, &send_to_sytter);
+          }
+          current = LogTailFile::open(&self.path, Some(0))?;
+        }
+      }
+      if let Some(offset) = current
+        .as_ref()
+        .map(|f| f.offset())
+        .filter(|o| saved.as_ref() != Some(o))
+      {
+        self.offset_save(&offset);
+        saved = Some(offset);
+      }
+      match receive_from_sytter.recv_timeout(self.interval) {
+        Err(RecvTimeoutError::Timeout) => (),
+        Ok(TriggerControl::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+          debug!(
+            "Sytter is closing. No longer following '{}'.",
+            self.path.display()
+          );
+          return Ok(());
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use toml::Table;
+
+use crate::{
+  condition::{conditions_check, Condition, ConditionMode},
+  config::Config,
+  error::AppError,
+  run::RunContext,
+  sytter::sytter_condition_table_deserialize,
+};
+
+/// True when every nested condition is true.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AllCondition {
+  pub conditions: Vec<Box<dyn Condition>>,
+}
+
+/// True when at least one nested condition is true.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AnyCondition {
+  pub conditions: Vec<Box<dyn Condition>>,
+}
+
+/// True when the nested conditions are not all true.  With a single nested
+/// condition this is a plain negation.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NotCondition {
+  pub conditions: Vec<Box<dyn Condition>>,
+}
+
+fn nested_conditions_deserialize(
+  section_data: &Table,
+  kind: &str,
+) -> Result<Vec<Box<dyn Condition>>, AppError> {
+  section_data
+    .get("conditions")
+    .and_then(|x| x.as_array())
+    .ok_or(AppError::SytterDeserializeRawError(format!(
+      "Field 'conditions' missing from '{}' Condition.",
+      kind,
+    )))?
+    .iter()
+    .map(|x| {
+      x.as_table()
+        .ok_or(AppError::SytterDeserializeRawError(format!(
+          "Field 'conditions' in '{}' Condition must be a list of tables.",
+          kind,
+        )))
+        .and_then(sytter_condition_table_deserialize)
+    })
+    .collect()
+}
+
+pub fn all_condition_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Condition>, AppError> {
+  Ok(Box::new(AllCondition {
+    conditions: nested_conditions_deserialize(section_data, "all")?,
+  }))
+}
+
+pub fn any_condition_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Condition>, AppError> {
+  Ok(Box::new(AnyCondition {
+    conditions: nested_conditions_deserialize(section_data, "any")?,
+  }))
+}
+
+pub fn not_condition_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Condition>, AppError> {
+  Ok(Box::new(NotCondition {
+    conditions: nested_conditions_deserialize(section_data, "not")?,
+  }))
+}
+
+#[typetag::serde]
+impl Condition for AllCondition {
+  fn check_condition(
+    &self,
+    config: &Config,
+    context: &RunContext,
+  ) -> Result<bool, AppError> {
+    conditions_check(&ConditionMode::All, &self.conditions, config, context)
+  }
+}
+
+#[typetag::serde]
+impl Condition for AnyCondition {
+  fn check_condition(
+    &self,
+    config: &Config,
+    context: &RunContext,
+  ) -> Result<bool, AppError> {
+    conditions_check(&ConditionMode::Any, &self.conditions, config, context)
+  }
+}
+
+#[typetag::serde]
+impl Condition for NotCondition {
+  fn check_condition(
+    &self,
+    config: &Config,
+    context: &RunContext,
+  ) -> Result<bool, AppError> {
+    conditions_check(&ConditionMode::All, &self.conditions, config, context)
+      .map(|x| !x)
+  }
+}
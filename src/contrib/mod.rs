@@ -1,4 +1,17 @@
+pub mod chain;
 pub mod cron;
 pub mod device;
+pub mod file_watch;
+pub mod lifecycle;
+pub mod log_tail;
+pub mod logic;
+pub mod network;
 pub mod power;
+pub mod probe;
+pub mod process;
+pub mod resource;
 pub mod shell;
+pub mod signal;
+pub mod state;
+pub mod stdout;
+pub mod webhook;
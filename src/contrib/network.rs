@@ -0,0 +1,163 @@
+#[cfg(target_os = "linux")]
+use crate::linux::network::{network_change_listen, NetworkChange};
+use crate::{
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::{
+  str::FromStr,
+  sync::mpsc::{Receiver, SyncSender},
+};
+use strum_macros::{Display, EnumString};
+use toml::Table;
+use tracing::*;
+
+#[derive(
+  Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
+)]
+pub enum NetworkEvent {
+  LinkUp,
+  LinkDown,
+  AddressAdd,
+  AddressRemove,
+  DefaultRouteAdd,
+  DefaultRouteRemove,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NetworkTrigger {
+  pub events: Vec<NetworkEvent>,
+  // Globs for interface names, such as "tun*".  Every interface if empty.
+  pub interfaces: Vec<String>,
+}
+
+fn network_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in network Trigger {}",
+    field, message,
+  ))
+}
+
+fn network_strings_deserialize(
+  section_data: &Table,
+  field: &str,
+) -> Result<Option<Vec<String>>, AppError> {
+  section_data
+    .get(field)
+    .map(|x| {
+      x.as_array()
+        .and_then(|xs| {
+          xs.iter()
+            .map(|s| s.as_str().map(|s| s.to_string()))
+            .collect::<Option<Vec<String>>>()
+        })
+        .ok_or(network_field_error(field, "must be a list of strings."))
+    })
+    .transpose()
+}
+
+fn network_interface_patterns(
+  interfaces: &[String],
+) -> Result<Vec<Pattern>, AppError> {
+  interfaces
+    .iter()
+    .map(|i| {
+      Pattern::new(i).map_err(|e| {
+        network_field_error(
+          "interfaces",
+          &format!("has an invalid glob '{}': {}", i, e),
+        )
+      })
+    })
+    .collect()
+}
+
+pub fn network_trigger_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let trigger = NetworkTrigger {
+    events: match network_strings_deserialize(section_data, "events")? {
+      None => vec![
+        NetworkEvent::LinkUp,
+        NetworkEvent::LinkDown,
+        NetworkEvent::AddressAdd,
+        NetworkEvent::AddressRemove,
+        NetworkEvent::DefaultRouteAdd,
+        NetworkEvent::DefaultRouteRemove,
+      ],
+      Some(events) => events
+        .iter()
+        .map(|e| {
+          NetworkEvent::from_str(e).map_err(|_| {
+            network_field_error(
+              "events",
+              &format!("has an unknown event '{}'.", e),
+            )
+          })
+        })
+        .collect::<Result<Vec<NetworkEvent>, AppError>>()?,
+    },
+    interfaces: network_strings_deserialize(section_data, "interfaces")?
+      .unwrap_or_default(),
+  };
+  network_interface_patterns(&trigger.interfaces)?;
+  Ok(Box::new(trigger))
+}
+
+#[cfg(target_os = "linux")]
+impl NetworkTrigger {
+  /// Whether the trigger wants a change.  With interfaces to match, changes
+  /// that aren't tied to an interface (such as a default route across
+  /// several) are left out.
+  fn wants(&self, patterns: &[Pattern], change: &NetworkChange) -> bool {
+    self.events.contains(&change.event)
+      && (patterns.is_empty()
+        || change
+          .interface
+          .as_ref()
+          .is_some_and(|i| patterns.iter().any(|p| p.matches(i))))
+  }
+}
+
+#[typetag::serde]
+impl Trigger for NetworkTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let patterns = network_interface_patterns(&self.interfaces)?;
+    info!(
+      "Listening for network events {:?} on interfaces {:?}.",
+      self.events, self.interfaces,
+    );
+    #[cfg(target_os = "linux")]
+    {
+      network_change_listen(
+        |change| {
+          if !self.wants(&patterns, &change) {
+            trace!("Network change {:?} skipped.", change);
+            return;
+          }
+          debug!("Network change {:?}.", change);
+          let event = TriggerEvent::new("network")
+            .field("network_event", &change.event)
+            .fields(change.properties);
+          if let Err(e) = send_to_sytter.send(event) {
+            debug!("Error triggering Sytter from network trigger: {:?}", e);
+          }
+        },
+        &receive_from_sytter,
+      )
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+      let _ = (patterns, send_to_sytter, receive_from_sytter);
+      Err(AppError::TriggerInitializeError(
+        "Network triggers are only supported on Linux.".to_string(),
+      ))
+    }
+  }
+}
@@ -1,29 +1,92 @@
-use crate::state::{State, SytterVariable};
-use crate::{error::AppError, trigger::Trigger};
+#[cfg(target_os = "macos")]
+use crate::trigger::trigger_shutdown_await;
+use crate::{
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
 
+use crate::deserialize::duration_des;
+#[cfg(target_os = "linux")]
+use crate::linux::{logind::sleep_listen, power_supply::power_supply_listen};
 #[cfg(target_os = "macos")]
 use crate::macos::power::sleep_listen_start;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+#[cfg(target_os = "linux")]
+use std::sync::mpsc::{sync_channel, RecvTimeoutError};
 use std::sync::mpsc::{Receiver, SyncSender};
+use std::time::Duration;
 use strum_macros::{Display, EnumString};
 use toml::Table;
 use tracing::*;
 
+// When listening for both sleep and the power supply, the Sytter and the two
+// listeners are checked on in slices this long.
+#[cfg(target_os = "linux")]
+const POWER_LISTENER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(
   Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
 )]
 pub enum PowerEvent {
-  Boot,     // Not supported.
-  Shutdown, // Not supported.
+  Boot,     // Not supported, see the lifecycle trigger's Startup.
+  Shutdown, // Not supported, see the lifecycle trigger's Shutdown.
   Sleep,
   Wake,
   Unknown,
+  AcPlugged,
+  AcUnplugged,
+  BatteryThreshold,
+  BatteryStatus,
+}
+
+impl PowerEvent {
+  /// Whether the event comes from watching the AC adapter and battery, rather
+  /// than from the OS announcing sleep.
+  pub fn is_power_supply(&self) -> bool {
+    matches!(
+      self,
+      PowerEvent::AcPlugged
+        | PowerEvent::AcUnplugged
+        | PowerEvent::BatteryThreshold
+        | PowerEvent::BatteryStatus,
+    )
+  }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PowerTrigger {
   pub events: Vec<PowerEvent>,
+  // Battery percentages that fire BatteryThreshold when crossed.
+  #[serde(default)]
+  pub battery_thresholds: Vec<u8>,
+  // How often the battery is read, since not every battery announces its
+  // percentage changing.
+  #[serde(default = "power_default_interval")]
+  pub interval: Duration,
+}
+
+/// What the AC adapter and batteries were doing when last read.  Anything the
+/// machine doesn't have, or doesn't report, is None.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PowerSupply {
+  pub ac_online: Option<bool>,
+  // Across every battery, for machines with more than one.
+  pub battery_percent: Option<u8>,
+  // As the OS reports it, such as "Charging" or "Discharging".
+  pub battery_status: Option<String>,
+  pub battery_time_to_empty: Option<Duration>,
+}
+
+fn power_default_interval() -> Duration {
+  Duration::from_secs(60)
+}
+
+fn power_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in power Trigger {}",
+    field, message,
+  ))
 }
 
 pub fn power_event_deserialize(s: String) -> Result<PowerEvent, AppError> {
@@ -37,48 +100,199 @@ pub fn power_event_deserialize(s: String) -> Result<PowerEvent, AppError> {
 pub fn power_trigger_toml_deserialize(
   section_data: &Table,
 ) -> Result<Box<dyn Trigger>, AppError> {
+  let events = section_data
+    .get("events")
+    .ok_or(AppError::PowerEventsMissingError)
+    .and_then(|candidate| {
+      candidate.as_array().ok_or(AppError::PowerEventParseError)
+    })
+    .and_then(|vec| {
+      vec
+        .into_iter()
+        .map(|s| {
+          s.as_str()
+            .ok_or(AppError::PowerEventParseError)
+            .map(|s| s.to_owned())
+            .and_then(power_event_deserialize)
+        })
+        .collect::<Result<Vec<PowerEvent>, AppError>>()
+    })?;
+  let battery_thresholds = section_data
+    .get("battery_thresholds")
+    .map(|x| {
+      x.as_array()
+        .ok_or(power_field_error(
+          "battery_thresholds",
+          "must be a list of percentages.",
+        ))?
+        .iter()
+        .map(|t| {
+          t.as_integer()
+            .filter(|t| (0..=100).contains(t))
+            .map(|t| t as u8)
+            .ok_or(power_field_error(
+              "battery_thresholds",
+              &format!("has '{}', which is not a percentage.", t),
+            ))
+        })
+        .collect::<Result<Vec<u8>, AppError>>()
+    })
+    .transpose()?
+    .unwrap_or_default();
+  if events.contains(&PowerEvent::BatteryThreshold)
+    && battery_thresholds.is_empty()
+  {
+    return Err(power_field_error(
+      "battery_thresholds",
+      "is required for BatteryThreshold events.",
+    ));
+  }
+  let interval = section_data
+    .get("interval")
+    .map(|x| duration_des(x).map_err(|e| power_field_error("interval", &e)))
+    .transpose()?
+    .unwrap_or(power_default_interval());
+  if interval.is_zero() {
+    return Err(power_field_error("interval", "must be more than zero."));
+  }
   Ok(Box::new(PowerTrigger {
-    events: section_data
-      .get("events")
-      .ok_or(AppError::PowerEventsMissingError)
-      .and_then(|candidate| {
-        candidate.as_array().ok_or(AppError::PowerEventParseError)
-      })
-      .and_then(|vec| {
-        vec
-          .into_iter()
-          .map(|s| {
-            s.as_str()
-              .ok_or(AppError::PowerEventParseError)
-              .map(|s| s.to_owned())
-              .and_then(power_event_deserialize)
-          })
-          .collect::<Result<Vec<PowerEvent>, AppError>>()
-      })?,
+    events,
+    battery_thresholds,
+    interval,
   }))
 }
 
+impl PowerTrigger {
+  /// The events for what changed between two readings, each carrying the
+  /// newer one.
+  fn power_supply_events(
+    &self,
+    previous: &PowerSupply,
+    current: &PowerSupply,
+  ) -> Vec<TriggerEvent> {
+    let event = |power_event: PowerEvent| {
+      let mut event =
+        TriggerEvent::new("power").field("power_event", &power_event);
+      if let Some(online) = current.ac_online {
+        event = event.field("power_ac_online", online);
+      }
+      if let Some(percent) = current.battery_percent {
+        event = event.field("power_battery_percent", percent);
+      }
+      if let Some(status) = &current.battery_status {
+        event = event.field("power_battery_status", status);
+      }
+      if let Some(time_to_empty) = current.battery_time_to_empty {
+        event =
+          event.field("power_battery_time_to_empty", time_to_empty.as_secs());
+      }
+      (power_event, event)
+    };
+    let mut events = vec![];
+    match (previous.ac_online, current.ac_online) {
+      (Some(false), Some(true)) => events.push(event(PowerEvent::AcPlugged)),
+      (Some(true), Some(false)) => events.push(event(PowerEvent::AcUnplugged)),
+      _ => (),
+    }
+    if let (Some(before), Some(now)) =
+      (previous.battery_percent, current.battery_percent)
+    {
+      // Only the last threshold crossed fires, such as when the battery drops
+      // past several of them while asleep.
+      let falling = self
+        .battery_thresholds
+        .iter()
+        .filter(|t| before > **t && now <= **t)
+        .min();
+      let rising = self
+        .battery_thresholds
+        .iter()
+        .filter(|t| before <= **t && now > **t)
+        .max();
+      if let Some((threshold, direction)) = falling
+        .map(|t| (t, "falling"))
+        .or(rising.map(|t| (t, "rising")))
+      {
+        let (power_event, e) = event(PowerEvent::BatteryThreshold);
+        events.push((
+          power_event,
+          e.field("power_battery_threshold", threshold)
+            .field("power_battery_direction", direction),
+        ));
+      }
+    }
+    match (&previous.battery_status, &current.battery_status) {
+      (Some(before), Some(now)) if before != now => {
+        let (power_event, e) = event(PowerEvent::BatteryStatus);
+        events.push((
+          power_event,
+          e.field("power_battery_previous_status", before),
+        ));
+      }
+      _ => (),
+    }
+    events
+      .into_iter()
+      .filter(|(power_event, _)| self.events.contains(power_event))
+      .map(|(_, e)| e)
+      .collect()
+  }
+
+  /// Read the power supply until the Sytter shuts down, firing on whatever
+  /// changes.  The first reading is only something to compare against.
+  #[cfg(target_os = "linux")]
+  fn power_supply_await(
+    &self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: &Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    info!(
+      "Listening for power events {:?} from the power supply.",
+      self.events,
+    );
+    let mut previous: Option<PowerSupply> = None;
+    power_supply_listen(
+      self.interval,
+      |current| {
+        trace!("Read power supply: {:?}", current);
+        for event in previous
+          .as_ref()
+          .map(|p| self.power_supply_events(p, &current))
+          .unwrap_or_default()
+        {
+          debug!("Power supply changed: {:?}", event.fields);
+          if let Err(e) = send_to_sytter.send(event) {
+            trace!("Error triggering sytter from PowerTrigger: {:?}", e);
+          }
+        }
+        previous = Some(current);
+      },
+      receive_from_sytter,
+    )
+  }
+}
+
 #[typetag::serde]
 impl Trigger for PowerTrigger {
   fn trigger_await(
     &mut self,
-    send_to_sytter: SyncSender<String>,
-    _receive_from_sytter: Receiver<String>,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
   ) -> Result<(), AppError> {
-    let send_to_sytter_threaded = send_to_sytter.clone();
-    if cfg!(target_os = "macos") {
+    #[cfg(target_os = "macos")]
+    {
+      if self.events.iter().any(|e| e.is_power_supply()) {
+        error!("AC and battery power events are only supported on Linux!");
+      }
+      let send_to_sytter_threaded = send_to_sytter.clone();
       info!("Listening for power event {:?}", self.events);
       let events = self.events.clone();
-      // TODO: Connect plumbing such that any trigger can be shut down via
-      // a cleanup function that is returned when listening.
-      let _cleanup_fn = sleep_listen_start(Box::new(move |p: PowerEvent| {
+      let cleanup_fn = sleep_listen_start(Box::new(move |p: PowerEvent| {
         trace!("Signaling sytter from PowerTrigger for event {:?}.", p,);
         if events.contains(&p) {
-          State::set_variable(SytterVariable {
-            key: "sytter_power_event".into(),
-            value: p.to_string(),
-          });
-          match send_to_sytter_threaded.send("foo".to_string()) {
+          match send_to_sytter_threaded
+            .send(TriggerEvent::new("power").field("power_event", &p))
+          {
             Ok(_) => trace!("Signal to sytter from PowerTrigger successful!"),
             Err(e) => {
               trace!("Error triggering sytter from PowerTrigger: {:?}", e)
@@ -93,16 +307,71 @@ impl Trigger for PowerTrigger {
         }
       }))?;
       trace!("Setup listener for power events.");
-      // None of this is called at the right time. trigger_await is async
-      // so we can make the setup happen on its own thread. In the case of
-      // power_macos we already have a thread. I'm not sure if that
-      // overhead is necessary but whatever.
-      // This is the event to clean up. Just block on it.
-      // let _ = receive_from_sytter.recv();
-      // debug!("Sytter is closing. Cleaning up power hooks...");
-      // cleanup_fn()?;
-      // debug!("Power cleanup done!");
-    } else {
+      // The listener has its own thread, so all that's left for us is to wait
+      // until it's time to clean up.
+      trigger_shutdown_await(&receive_from_sytter);
+      debug!("Sytter is closing. Cleaning up power hooks...");
+      cleanup_fn()?;
+      debug!("Power cleanup done!");
+    }
+    #[cfg(target_os = "linux")]
+    {
+      let (supply, sleep): (Vec<_>, Vec<_>) =
+        self.events.iter().partition(|e| e.is_power_supply());
+      match (supply.is_empty(), sleep.is_empty()) {
+        (true, _) => {
+          sleep_listen(&self.events, send_to_sytter, receive_from_sytter)?
+        }
+        (false, true) => {
+          self.power_supply_await(send_to_sytter, &receive_from_sytter)?
+        }
+        // logind blocks its thread until the shutdown, so each listener gets a
+        // thread of its own.  Whichever stops first, whether for the shutdown
+        // or because it failed, stops the other, so a failure isn't hidden
+        // until the Sytter shuts down.
+        (false, false) => {
+          let (send_to_supply, receive_supply_control) = sync_channel(1);
+          let (send_to_sleep, receive_sleep_control) = sync_channel(1);
+          let trigger = self.clone();
+          let send_to_sytter_supply = send_to_sytter.clone();
+          let supply_thread = std::thread::spawn(move || {
+            trigger.power_supply_await(
+              send_to_sytter_supply,
+              &receive_supply_control,
+            )
+          });
+          let events = self.events.clone();
+          let sleep_thread = std::thread::spawn(move || {
+            sleep_listen(&events, send_to_sytter, receive_sleep_control)
+          });
+          while !supply_thread.is_finished() && !sleep_thread.is_finished() {
+            match receive_from_sytter.recv_timeout(POWER_LISTENER_POLL_INTERVAL)
+            {
+              Err(RecvTimeoutError::Timeout) => (),
+              Ok(TriggerControl::Shutdown)
+              | Err(RecvTimeoutError::Disconnected) => break,
+            }
+          }
+          let _ = send_to_supply.send(TriggerControl::Shutdown);
+          let _ = send_to_sleep.send(TriggerControl::Shutdown);
+          let joined = |name: &str, result: std::thread::Result<_>| {
+            result.map_err(|e| {
+              AppError::TriggerRuntimeError(format!(
+                "{} listener panicked: {:?}",
+                name, e,
+              ))
+            })
+          };
+          let supply_result = joined("Power supply", supply_thread.join())?;
+          let sleep_result = joined("Sleep", sleep_thread.join())?;
+          supply_result?;
+          sleep_result?
+        }
+      }
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+      let _ = (send_to_sytter, receive_from_sytter);
       error!("OS not supported for power events!");
     }
     Ok(())
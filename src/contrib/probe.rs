@@ -0,0 +1,505 @@
+use crate::{
+  deserialize::duration_des,
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+  io,
+  net::{SocketAddr, TcpStream, ToSocketAddrs},
+  os::{
+    fd::{AsRawFd, FromRawFd, OwnedFd},
+    unix::ffi::OsStrExt,
+  },
+  path::{Path, PathBuf},
+  str::FromStr,
+  sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender},
+  time::{Duration, Instant},
+};
+use strum_macros::{Display, EnumString};
+use toml::{Table, Value};
+use tracing::*;
+
+#[derive(
+  Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
+)]
+pub enum ProbeState {
+  Up,
+  Down,
+}
+
+/// What gets checked.  Exactly one is given.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ProbeTarget {
+  // Such as "localhost:5432".
+  Tcp(String),
+  Http(String),
+  Unix(PathBuf),
+}
+
+impl ProbeTarget {
+  fn kind(&self) -> &'static str {
+    match self {
+      ProbeTarget::Tcp(_) => "tcp",
+      ProbeTarget::Http(_) => "http",
+      ProbeTarget::Unix(_) => "unix",
+    }
+  }
+
+  fn display(&self) -> String {
+    match self {
+      ProbeTarget::Tcp(address) => address.clone(),
+      ProbeTarget::Http(url) => url.clone(),
+      ProbeTarget::Unix(path) => path.display().to_string(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProbeTrigger {
+  pub target: ProbeTarget,
+  pub events: Vec<ProbeState>,
+  // How often to check.
+  pub interval: Duration,
+  // How long to wait for a check before counting it as down.
+  pub timeout: Duration,
+  // A check slower than this counts as down, even if it otherwise passed.
+  pub latency: Option<Duration>,
+  // How many checks in a row have to agree before the state changes.
+  pub confirmations: u32,
+  // For HTTP, the status codes that count as up.  Any 2xx if empty.
+  pub status: Vec<u16>,
+  // For HTTP, a regular expression the body has to match to count as up.
+  pub body_regex: Option<String>,
+}
+
+fn probe_default_interval() -> Duration {
+  Duration::from_secs(30)
+}
+
+fn probe_default_timeout() -> Duration {
+  Duration::from_secs(5)
+}
+
+fn probe_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in probe Trigger {}",
+    field, message,
+  ))
+}
+
+fn probe_body_regex_compile(
+  pattern: &Option<String>,
+) -> Result<Option<Regex>, AppError> {
+  pattern
+    .as_ref()
+    .map(|p| {
+      Regex::new(p).map_err(|e| {
+        probe_field_error(
+          "body_regex",
+          &format!("is not a valid regular expression: {}", e),
+        )
+      })
+    })
+    .transpose()
+}
+
+fn probe_status_des(x: &Value) -> Option<u16> {
+  x.as_integer().and_then(|i| u16::try_from(i).ok())
+}
+
+pub fn probe_trigger_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let string_field = |field: &str| -> Result<Option<String>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| {
+        x.as_str()
+          .map(|s| s.to_string())
+          .ok_or(probe_field_error(field, "must be a string."))
+      })
+      .transpose()
+  };
+  let duration_field = |field: &str| -> Result<Option<Duration>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| duration_des(x).map_err(|e| probe_field_error(field, &e)))
+      .transpose()
+  };
+  let target = match (
+    string_field("tcp")?,
+    string_field("url")?,
+    string_field("unix")?,
+  ) {
+    (Some(address), None, None) => ProbeTarget::Tcp(address),
+    (None, Some(url), None) => ProbeTarget::Http(url),
+    (None, None, Some(path)) => ProbeTarget::Unix(PathBuf::from(path)),
+    _ => {
+      return Err(AppError::SytterDeserializeRawError(
+        "Probe Trigger needs exactly one of 'tcp', 'url', or 'unix'."
+          .to_string(),
+      ))
+    }
+  };
+  let events = match section_data.get("events") {
+    None => vec![ProbeState::Up, ProbeState::Down],
+    Some(events) => events
+      .as_array()
+      .ok_or(probe_field_error("events", "must be a list of strings."))?
+      .iter()
+      .map(|e| {
+        e.as_str().and_then(|e| ProbeState::from_str(e).ok()).ok_or(
+          probe_field_error(
+            "events",
+            &format!("has an unknown event '{}'.", e),
+          ),
+        )
+      })
+      .collect::<Result<Vec<ProbeState>, AppError>>()?,
+  };
+  let trigger = ProbeTrigger {
+    events,
+    interval: duration_field("interval")?.unwrap_or(probe_default_interval()),
+    timeout: duration_field("timeout")?.unwrap_or(probe_default_timeout()),
+    latency: duration_field("latency")?,
+    confirmations: match section_data.get("confirmations") {
+      None => 1,
+      Some(x) => x
+        .as_integer()
+        .filter(|i| *i >= 1)
+        .and_then(|i| u32::try_from(i).ok())
+        .ok_or(probe_field_error("confirmations", "must be 1 or more."))?,
+    },
+    status: match section_data.get("status") {
+      None => vec![],
+      Some(Value::Array(codes)) => codes
+        .iter()
+        .map(probe_status_des)
+        .collect::<Option<Vec<u16>>>()
+        .ok_or(probe_field_error(
+          "status",
+          "must be a list of status codes.",
+        ))?,
+      Some(code) => vec![probe_status_des(code)
+        .ok_or(probe_field_error("status", "must be a status code."))?],
+    },
+    body_regex: string_field("body_regex")?,
+    target,
+  };
+  let http = matches!(trigger.target, ProbeTarget::Http(_));
+  if !http && (!trigger.status.is_empty() || trigger.body_regex.is_some()) {
+    return Err(AppError::SytterDeserializeRawError(
+      "Fields 'status' and 'body_regex' in probe Trigger only work with 'url'."
+        .to_string(),
+    ));
+  }
+  if trigger.interval.is_zero() || trigger.timeout.is_zero() {
+    return Err(AppError::SytterDeserializeRawError(
+      "Fields 'interval' and 'timeout' in probe Trigger must be more than \
+       zero."
+        .to_string(),
+    ));
+  }
+  probe_body_regex_compile(&trigger.body_regex)?;
+  Ok(Box::new(trigger))
+}
+
+/// How a check went.  Down checks say why.
+struct ProbeResult {
+  state: ProbeState,
+  latency: Duration,
+  // The HTTP status, if there was a response.
+  status: Option<u16>,
+  error: Option<String>,
+}
+
+/// Where a probe stands.  A new result only takes over once enough checks in a
+/// row agree on it.
+#[derive(Default)]
+struct ProbeTracker {
+  // None until the first result is confirmed.
+  state: Option<ProbeState>,
+  candidate: Option<ProbeState>,
+  count: u32,
+}
+
+impl ProbeTracker {
+  /// Give the state that was left, if the result changed it.  Settling on the
+  /// first result isn't a change.
+  fn observe(
+    &mut self,
+    seen: &ProbeState,
+    confirmations: u32,
+  ) -> Option<ProbeState> {
+    if self.state.as_ref() == Some(seen) {
+      self.candidate = None;
+      self.count = 0;
+      return None;
+    }
+    match self.candidate.as_ref() == Some(seen) {
+      true => self.count += 1,
+      false => {
+        self.candidate = Some(seen.clone());
+        self.count = 1;
+      }
+    }
+    if self.count < confirmations {
+      return None;
+    }
+    self.candidate = None;
+    self.count = 0;
+    self.state.replace(seen.clone())
+  }
+}
+
+// A Unix socket with a full backlog is tried again this often, until the
+// timeout.
+const PROBE_UNIX_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+impl ProbeTrigger {
+  fn check(
+    &self,
+    client: &Option<reqwest::blocking::Client>,
+    body_regex: &Option<Regex>,
+  ) -> ProbeResult {
+    let started = Instant::now();
+    let checked = match (&self.target, client) {
+      (ProbeTarget::Tcp(address), _) => self.tcp_check(address).map(|_| None),
+      (ProbeTarget::Http(url), Some(client)) => {
+        self.http_check(client, url, body_regex)
+      }
+      (ProbeTarget::Http(_), None) => {
+        Err((None, "No HTTP client.".to_string()))
+      }
+      (ProbeTarget::Unix(path), _) => self.unix_check(path).map(|_| None),
+    };
+    let latency = started.elapsed();
+    let (status, error) = match checked {
+      Ok(status) => match self.latency {
+        Some(budget) if latency > budget => (
+          status,
+          Some(format!("Took {:?}, more than {:?}.", latency, budget)),
+        ),
+        _ => (status, None),
+      },
+      Err((status, error)) => (status, Some(error)),
+    };
+    ProbeResult {
+      state: match error {
+        None => ProbeState::Up,
+        Some(_) => ProbeState::Down,
+      },
+      latency,
+      status,
+      error,
+    }
+  }
+
+  /// Any of the addresses the name resolves to will do.  Resolving the name
+  /// and trying each address all come out of the one timeout.
+  fn tcp_check(&self, address: &str) -> Result<(), (Option<u16>, String)> {
+    let deadline = Instant::now() + self.timeout;
+    let timed_out = || (None, format!("Timed out after {:?}.", self.timeout));
+    // Resolving can't be given a timeout, so it's waited on from here instead.
+    // A resolver that is stuck is left to give up on its own.
+    let (send_addresses, receive_addresses) = sync_channel(1);
+    let name = address.to_string();
+    std::thread::spawn(move || {
+      let _ = send_addresses.send(
+        name
+          .to_socket_addrs()
+          .map(|a| a.collect::<Vec<SocketAddr>>()),
+      );
+    });
+    let addresses = receive_addresses
+      .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+      .map_err(|_| timed_out())?
+      .map_err(|e| (None, format!("Could not resolve: {}", e)))?;
+    let mut error = "Resolved to no addresses.".to_string();
+    for a in addresses {
+      let left = deadline.saturating_duration_since(Instant::now());
+      if left.is_zero() {
+        return Err(timed_out());
+      }
+      match TcpStream::connect_timeout(&a, left) {
+        Ok(_) => return Ok(()),
+        Err(e) => error = format!("{}: {}", a, e),
+      }
+    }
+    Err((None, error))
+  }
+
+  /// UnixStream has no connect_timeout, and a listener whose backlog is full
+  /// keeps a blocking connect waiting for as long as it stays full.  So this
+  /// connects without blocking, and tries again until the timeout.
+  fn unix_check(&self, path: &Path) -> Result<(), (Option<u16>, String)> {
+    let os_error = || (None, io::Error::last_os_error().to_string());
+    let bytes = path.as_os_str().as_bytes();
+    // Safe because an all-zero sockaddr_un is valid.
+    let mut address: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    // The path has to fit with a NUL after it.
+    if bytes.len() >= address.sun_path.len() {
+      return Err((None, "Path is too long for a Unix socket.".to_string()));
+    }
+    address.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    for (to, from) in address.sun_path.iter_mut().zip(bytes) {
+      *to = *from as libc::c_char;
+    }
+    // Safe because we check the result before taking ownership of it.
+    let raw = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0) };
+    if raw < 0 {
+      return Err(os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(raw) };
+    // Safe because the socket is ours and open.
+    if unsafe {
+      libc::fcntl(socket.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK)
+    } < 0
+    {
+      return Err(os_error());
+    }
+    let deadline = Instant::now() + self.timeout;
+    loop {
+      // Safe because the address outlives the call and its size is passed.
+      let connected = unsafe {
+        libc::connect(
+          socket.as_raw_fd(),
+          &address as *const libc::sockaddr_un as *const libc::sockaddr,
+          std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t,
+        )
+      };
+      if connected == 0 {
+        return Ok(());
+      }
+      let error = io::Error::last_os_error();
+      let now = Instant::now();
+      match error.raw_os_error() {
+        // The backlog is full.  There's nothing to wait on, so keep trying.
+        Some(libc::EAGAIN) | Some(libc::EINTR) if now < deadline => {
+          std::thread::sleep(PROBE_UNIX_RETRY_INTERVAL.min(deadline - now))
+        }
+        Some(libc::EAGAIN) => {
+          return Err((
+            None,
+            format!("Not accepted within {:?}.", self.timeout),
+          ))
+        }
+        _ => return Err((None, error.to_string())),
+      }
+    }
+  }
+
+  fn http_check(
+    &self,
+    client: &reqwest::blocking::Client,
+    url: &str,
+    body_regex: &Option<Regex>,
+  ) -> Result<Option<u16>, (Option<u16>, String)> {
+    let response = client.get(url).send().map_err(|e| (None, e.to_string()))?;
+    let status = response.status().as_u16();
+    let status_up = match self.status.is_empty() {
+      true => response.status().is_success(),
+      false => self.status.contains(&status),
+    };
+    if !status_up {
+      return Err((Some(status), format!("Got status {}.", status)));
+    }
+    if let Some(regex) = body_regex {
+      let body = response.text().map_err(|e| (Some(status), e.to_string()))?;
+      if !regex.is_match(&body) {
+        return Err((
+          Some(status),
+          "The body didn't match 'body_regex'.".to_string(),
+        ));
+      }
+    }
+    Ok(Some(status))
+  }
+
+  fn event(&self, result: &ProbeResult, previous: &ProbeState) -> TriggerEvent {
+    let mut event = TriggerEvent::new("probe")
+      .field("probe_state", &result.state)
+      .field("probe_previous_state", previous)
+      .field("probe_kind", self.target.kind())
+      .field("probe_target", self.target.display())
+      .field("probe_latency_ms", result.latency.as_millis());
+    if let Some(status) = result.status {
+      event = event.field("probe_status", status);
+    }
+    if let Some(error) = &result.error {
+      event = event.field("probe_error", error);
+    }
+    event
+  }
+}
+
+#[typetag::serde]
+impl Trigger for ProbeTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let body_regex = probe_body_regex_compile(&self.body_regex)?;
+    // Redirects aren't followed, so a captive portal sending everything to
+    // its login page shows up as down.
+    let client = match self.target {
+      ProbeTarget::Http(_) => Some(
+        reqwest::blocking::Client::builder()
+          .timeout(self.timeout)
+          .redirect(reqwest::redirect::Policy::none())
+          .build()
+          .map_err(|e| {
+            AppError::TriggerInitializeError(format!(
+              "Could not make an HTTP client: {:?}",
+              e,
+            ))
+          })?,
+      ),
+      _ => None,
+    };
+    info!(
+      "Probing {} {} every {:?}.",
+      self.target.kind(),
+      self.target.display(),
+      self.interval,
+    );
+    let mut tracker = ProbeTracker::default();
+    loop {
+      let result = self.check(&client, &body_regex);
+      trace!(
+        "Probe of {} was {} in {:?}: {:?}",
+        self.target.display(),
+        result.state,
+        result.latency,
+        result.error,
+      );
+      if let Some(previous) = tracker.observe(&result.state, self.confirmations)
+      {
+        debug!(
+          "{} went from {} to {}.",
+          self.target.display(),
+          previous,
+          result.state,
+        );
+        if self.events.contains(&result.state) {
+          if let Err(e) = send_to_sytter.send(self.event(&result, &previous)) {
+            debug!("Error triggering Sytter from probe trigger: {:?}", e);
+          }
+        }
+      }
+      match receive_from_sytter.recv_timeout(self.interval) {
+        Err(RecvTimeoutError::Timeout) => (),
+        Ok(TriggerControl::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+          debug!(
+            "Sytter is closing. No longer probing {}.",
+            self.target.display()
+          );
+          return Ok(());
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,369 @@
+#[cfg(target_os = "linux")]
+use crate::linux::process::process_connector_watch;
+#[cfg(target_os = "linux")]
+pub use crate::linux::process::process_list;
+use crate::{
+  deserialize::duration_des,
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::BTreeMap,
+  str::FromStr,
+  sync::mpsc::{Receiver, RecvTimeoutError, SyncSender},
+  time::Duration,
+};
+use strum_macros::{Display, EnumString};
+use toml::Table;
+use tracing::*;
+
+#[derive(
+  Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
+)]
+pub enum ProcessEvent {
+  Start,
+  Exit,
+}
+
+/// What we know about a running process.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ProcessInfo {
+  pub pid: u32,
+  pub name: String,
+  // Arguments joined with spaces.
+  pub cmdline: String,
+  pub uid: u32,
+  // The user's name, or their uid if they don't have one.
+  pub user: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProcessTrigger {
+  pub events: Vec<ProcessEvent>,
+  // Every one of these that is set has to match.
+  pub process_name: Option<String>,
+  pub process_regex: Option<String>,
+  pub cmdline_regex: Option<String>,
+  // How often processes are listed where the OS can't tell us about them as
+  // they come and go.
+  pub interval: Duration,
+}
+
+fn process_default_interval() -> Duration {
+  Duration::from_secs(1)
+}
+
+fn process_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in process Trigger {}",
+    field, message,
+  ))
+}
+
+fn process_regex_compile(
+  field: &str,
+  pattern: &Option<String>,
+) -> Result<Option<Regex>, AppError> {
+  pattern
+    .as_ref()
+    .map(|p| {
+      Regex::new(p).map_err(|e| {
+        AppError::SytterDeserializeRawError(format!(
+          "Field '{}' is not a valid regular expression: {}",
+          field, e,
+        ))
+      })
+    })
+    .transpose()
+}
+
+pub fn process_trigger_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let string_field = |field: &str| -> Result<Option<String>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| {
+        x.as_str()
+          .map(|s| s.to_string())
+          .ok_or(process_field_error(field, "must be a string."))
+      })
+      .transpose()
+  };
+  let trigger = ProcessTrigger {
+    events: match section_data.get("events") {
+      None => vec![ProcessEvent::Start, ProcessEvent::Exit],
+      Some(x) => x
+        .as_array()
+        .ok_or(process_field_error("events", "must be a list."))?
+        .iter()
+        .map(|e| {
+          e.as_str()
+            .and_then(|e| ProcessEvent::from_str(e).ok())
+            .ok_or(process_field_error(
+              "events",
+              "can only have Start and Exit.",
+            ))
+        })
+        .collect::<Result<Vec<ProcessEvent>, AppError>>()?,
+    },
+    process_name: string_field("process_name")?,
+    process_regex: string_field("process_regex")?,
+    cmdline_regex: string_field("cmdline_regex")?,
+    interval: section_data
+      .get("interval")
+      .map(|x| duration_des(x).map_err(|e| process_field_error("interval", &e)))
+      .transpose()?
+      .unwrap_or(process_default_interval()),
+  };
+  if trigger.interval.is_zero() {
+    return Err(process_field_error("interval", "must be more than zero."));
+  }
+  if trigger.process_name.is_none()
+    && trigger.process_regex.is_none()
+    && trigger.cmdline_regex.is_none()
+  {
+    return Err(AppError::SytterDeserializeRawError(
+      "Either 'process_name', 'process_regex', or 'cmdline_regex' must be \
+       specified"
+        .to_string(),
+    ));
+  }
+  // Catch bad patterns when the Sytter loads, rather than on every restart.
+  ProcessMatcher::new(
+    &trigger.process_name,
+    &trigger.process_regex,
+    &trigger.cmdline_regex,
+  )?;
+  Ok(Box::new(trigger))
+}
+
+/// A trigger's process patterns, compiled.
+pub struct ProcessMatcher {
+  name: Option<String>,
+  name_regex: Option<Regex>,
+  cmdline_regex: Option<Regex>,
+}
+
+impl ProcessMatcher {
+  pub fn new(
+    process_name: &Option<String>,
+    process_regex: &Option<String>,
+    cmdline_regex: &Option<String>,
+  ) -> Result<Self, AppError> {
+    Ok(ProcessMatcher {
+      name: process_name.clone(),
+      name_regex: process_regex_compile("process_regex", process_regex)?,
+      cmdline_regex: process_regex_compile("cmdline_regex", cmdline_regex)?,
+    })
+  }
+
+  pub fn matches(&self, info: &ProcessInfo) -> bool {
+    self.name.as_ref().is_none_or(|n| *n == info.name)
+      && self
+        .name_regex
+        .as_ref()
+        .is_none_or(|r| r.is_match(&info.name))
+      && self
+        .cmdline_regex
+        .as_ref()
+        .is_none_or(|r| r.is_match(&info.cmdline))
+  }
+
+  pub fn matching(
+    &self,
+    processes: BTreeMap<u32, ProcessInfo>,
+  ) -> BTreeMap<u32, ProcessInfo> {
+    processes
+      .into_iter()
+      // The trigger's own processes (such as ps) are never interesting.
+      .filter(|(pid, info)| *pid != std::process::id() && self.matches(info))
+      .collect()
+  }
+}
+
+/// Process names are cut short by the OS at `max_len`.  When a name looks cut
+/// short, the full name is taken from the command line when it starts the same
+/// way.  Scripts have their interpreter first, so the argument after it is
+/// tried too.
+pub fn process_name_full(name: &str, max_len: usize, args: &[&str]) -> String {
+  args
+    .iter()
+    .take(2)
+    .map(|arg| arg.rsplit('/').next().unwrap_or_default())
+    .find(|arg_name| name.len() >= max_len && arg_name.starts_with(name))
+    .unwrap_or(name)
+    .to_string()
+}
+
+/// Send the Sytter a process event, if it's one the trigger wants.
+/// `wait_status` is the raw status from waitpid, for exits where the OS told
+/// us.
+pub fn process_event_send(
+  send_to_sytter: &SyncSender<TriggerEvent>,
+  events: &[ProcessEvent],
+  event: ProcessEvent,
+  info: &ProcessInfo,
+  wait_status: Option<i32>,
+) {
+  if !events.contains(&event) {
+    return;
+  }
+  debug!("Process {:?} for {:?}.", event, info);
+  let trigger_event = TriggerEvent::new("process")
+    .field("process_event", &event)
+    .field("process_pid", info.pid)
+    .field("process_name", &info.name)
+    .field("process_cmdline", &info.cmdline)
+    .field("process_uid", info.uid)
+    .field("process_user", &info.user);
+  let trigger_event = match wait_status {
+    Some(s) if libc::WIFEXITED(s) => {
+      trigger_event.field("process_exit_status", libc::WEXITSTATUS(s))
+    }
+    Some(s) if libc::WIFSIGNALED(s) => {
+      trigger_event.field("process_exit_signal", libc::WTERMSIG(s))
+    }
+    _ => trigger_event,
+  };
+  if let Err(e) = send_to_sytter.send(trigger_event) {
+    debug!("Error triggering Sytter from process trigger: {:?}", e);
+  }
+}
+
+// ps cuts ucomm off at this many characters.
+#[cfg(not(target_os = "linux"))]
+const UCOMM_MAX_LEN: usize = 16;
+
+/// List processes with ps, for where there's no /proc.  Names and command
+/// lines can both have spaces, so each gets its own call where it can be the
+/// last column.
+#[cfg(not(target_os = "linux"))]
+pub fn process_list() -> Result<BTreeMap<u32, ProcessInfo>, AppError> {
+  let ps = |columns: &str| -> Result<Vec<(u32, String)>, AppError> {
+    let output = std::process::Command::new("ps")
+      .args(["-axww", "-o", columns])
+      .output()
+      .map_err(|e| {
+        AppError::TriggerRuntimeError(format!(
+          "Could not list processes with ps: {:?}",
+          e,
+        ))
+      })?;
+    Ok(
+      String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| {
+          let (pid, rest) = l.trim_start().split_once(char::is_whitespace)?;
+          Some((pid.parse::<u32>().ok()?, rest.trim().to_string()))
+        })
+        .collect(),
+    )
+  };
+  let cmdlines: BTreeMap<u32, String> = ps("pid=,args=")?.into_iter().collect();
+  Ok(
+    ps("pid=,uid=,user=,ucomm=")?
+      .into_iter()
+      .filter_map(|(pid, rest)| {
+        let mut columns = rest.splitn(3, char::is_whitespace);
+        let uid = columns.next()?.parse::<u32>().ok()?;
+        let user = columns.next()?.to_string();
+        let cmdline = cmdlines.get(&pid).cloned().unwrap_or_default();
+        let name = process_name_full(
+          columns.next()?.trim(),
+          UCOMM_MAX_LEN,
+          &cmdline.split_whitespace().collect::<Vec<&str>>(),
+        );
+        Some((
+          pid,
+          ProcessInfo {
+            pid,
+            name,
+            cmdline,
+            uid,
+            user,
+          },
+        ))
+      })
+      .collect(),
+  )
+}
+
+/// Compare process lists every interval.  Exit statuses can't be known this
+/// way, since the processes aren't ours.
+fn process_poll_watch(
+  trigger: &ProcessTrigger,
+  matcher: &ProcessMatcher,
+  send_to_sytter: &SyncSender<TriggerEvent>,
+  receive_from_sytter: &Receiver<TriggerControl>,
+) -> Result<(), AppError> {
+  let mut known = matcher.matching(process_list()?);
+  loop {
+    match receive_from_sytter.recv_timeout(trigger.interval) {
+      Err(RecvTimeoutError::Timeout) => (),
+      Ok(TriggerControl::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+        return Ok(())
+      }
+    }
+    let current = matcher.matching(process_list()?);
+    current
+      .iter()
+      .filter(|(pid, _)| !known.contains_key(pid))
+      .for_each(|(_, info)| {
+        process_event_send(
+          send_to_sytter,
+          &trigger.events,
+          ProcessEvent::Start,
+          info,
+          None,
+        )
+      });
+    known
+      .iter()
+      .filter(|(pid, _)| !current.contains_key(pid))
+      .for_each(|(_, info)| {
+        process_event_send(
+          send_to_sytter,
+          &trigger.events,
+          ProcessEvent::Exit,
+          info,
+          None,
+        )
+      });
+    known = current;
+  }
+}
+
+#[typetag::serde]
+impl Trigger for ProcessTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let matcher = ProcessMatcher::new(
+      &self.process_name,
+      &self.process_regex,
+      &self.cmdline_regex,
+    )?;
+    info!(
+      "Watching for processes {:?} {:?} {:?} to {:?}.",
+      self.process_name, self.process_regex, self.cmdline_regex, self.events,
+    );
+    #[cfg(target_os = "linux")]
+    match process_connector_watch(
+      self,
+      &matcher,
+      &send_to_sytter,
+      &receive_from_sytter,
+    ) {
+      Err(AppError::TriggerInitializeError(e)) => {
+        info!("{}  Listing processes every {:?}.", e, self.interval)
+      }
+      result => return result,
+    }
+    process_poll_watch(self, &matcher, &send_to_sytter, &receive_from_sytter)
+  }
+}
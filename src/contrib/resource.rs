@@ -0,0 +1,506 @@
+#[cfg(target_os = "linux")]
+use crate::linux::resource::ResourceSampler;
+use crate::{
+  contrib::process::{process_list, ProcessInfo, ProcessMatcher},
+  deserialize::duration_des,
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::BTreeMap,
+  str::FromStr,
+  sync::mpsc::{Receiver, RecvTimeoutError, SyncSender},
+  time::{Duration, Instant},
+};
+use strum_macros::{Display, EnumString};
+use toml::{Table, Value};
+use tracing::*;
+
+#[derive(
+  Clone,
+  Copy,
+  Debug,
+  Deserialize,
+  Display,
+  EnumString,
+  Eq,
+  Ord,
+  PartialEq,
+  PartialOrd,
+  Serialize,
+)]
+#[strum(serialize_all = "lowercase")]
+pub enum ResourceMetric {
+  // Percent of one CPU for a process, or of every CPU for the system.
+  Cpu,
+  // Resident memory, in bytes.
+  Rss,
+  // Open file descriptors.
+  Fds,
+  // Bytes read from and written to storage, per second.
+  Io,
+  // Percent of the system's memory in use.
+  Memory,
+  // The system's one minute load average.
+  Load,
+}
+
+impl ResourceMetric {
+  fn for_process(&self) -> bool {
+    matches!(self, Self::Cpu | Self::Rss | Self::Fds | Self::Io)
+  }
+
+  fn for_system(&self) -> bool {
+    matches!(self, Self::Cpu | Self::Memory | Self::Load)
+  }
+
+  /// Read an amount such as "90%" or "500MB" into the metric's own units.
+  /// Sizes are in powers of 1024.
+  fn amount_parse(&self, amount: &str) -> Result<f64, String> {
+    let split = amount
+      .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+      .unwrap_or(amount.len());
+    let (number, unit) = amount.split_at(split);
+    let n = number
+      .parse::<f64>()
+      .map_err(|_| format!("'{}' is not an amount.", amount))?;
+    let unit = unit.trim();
+    let size_scale = |unit: &str| match unit {
+      "" | "B" => Some(1.0),
+      "K" | "KB" | "KiB" => Some(1024.0),
+      "M" | "MB" | "MiB" => Some(1024.0_f64.powi(2)),
+      "G" | "GB" | "GiB" => Some(1024.0_f64.powi(3)),
+      _ => None,
+    };
+    let scale = match self {
+      Self::Cpu | Self::Memory => {
+        Some(1.0).filter(|_| ["", "%"].contains(&unit))
+      }
+      Self::Rss => size_scale(unit),
+      Self::Io => size_scale(unit.strip_suffix("/s").unwrap_or(unit)),
+      Self::Fds | Self::Load => Some(1.0).filter(|_| unit.is_empty()),
+    };
+    scale.map(|scale| n * scale).ok_or(format!(
+      "'{}' has a unit '{}' that {} isn't measured in.",
+      amount, unit, self,
+    ))
+  }
+}
+
+/// What was measured for one process, or the system.
+pub type ResourceMeasures = BTreeMap<ResourceMetric, f64>;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ResourceComparison {
+  Above,
+  AtLeast,
+  Below,
+  AtMost,
+}
+
+/// A threshold such as "cpu > 90%", with the amount in the metric's units.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ResourceThreshold {
+  pub metric: ResourceMetric,
+  pub comparison: ResourceComparison,
+  pub value: f64,
+  // As it was written, to show in events.
+  pub expression: String,
+}
+
+impl ResourceThreshold {
+  pub fn parse(expression: &str) -> Result<Self, String> {
+    let invalid = || {
+      format!(
+        "'{}' is not a threshold, such as \"cpu > 90%\".",
+        expression
+      )
+    };
+    let split = expression.find(['<', '>']).ok_or_else(invalid)?;
+    let (metric, rest) = expression.split_at(split);
+    let metric =
+      ResourceMetric::from_str(metric.trim()).map_err(|_| invalid())?;
+    let (comparison, amount) = [
+      (">=", ResourceComparison::AtLeast),
+      ("<=", ResourceComparison::AtMost),
+      (">", ResourceComparison::Above),
+      ("<", ResourceComparison::Below),
+    ]
+    .into_iter()
+    .find_map(|(op, comparison)| {
+      rest.strip_prefix(op).map(|amount| (comparison, amount))
+    })
+    .ok_or_else(invalid)?;
+    Ok(ResourceThreshold {
+      metric,
+      comparison,
+      value: metric.amount_parse(amount.trim())?,
+      expression: expression.trim().to_string(),
+    })
+  }
+
+  pub fn holds(&self, measured: f64) -> bool {
+    match self.comparison {
+      ResourceComparison::Above => measured > self.value,
+      ResourceComparison::AtLeast => measured >= self.value,
+      ResourceComparison::Below => measured < self.value,
+      ResourceComparison::AtMost => measured <= self.value,
+    }
+  }
+
+  /// Whether a measurement has come back from the threshold by at least
+  /// `hysteresis`, so the trigger can fire again.
+  pub fn cleared(&self, measured: f64, hysteresis: f64) -> bool {
+    let eased = match self.comparison {
+      ResourceComparison::Above | ResourceComparison::AtLeast => {
+        self.value - hysteresis
+      }
+      ResourceComparison::Below | ResourceComparison::AtMost => {
+        self.value + hysteresis
+      }
+    };
+    !ResourceThreshold {
+      value: eased,
+      ..self.clone()
+    }
+    .holds(measured)
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ResourceTrigger {
+  // Without any of these, the system as a whole is measured.
+  pub process_name: Option<String>,
+  pub process_regex: Option<String>,
+  pub cmdline_regex: Option<String>,
+  pub threshold: ResourceThreshold,
+  // How long the threshold has to hold before the trigger fires.
+  pub duration: Duration,
+  // How far back from the threshold a measurement has to come, in the
+  // threshold's units, before the trigger can fire again.
+  pub hysteresis: f64,
+  // How often to measure.
+  pub interval: Duration,
+}
+
+fn resource_default_interval() -> Duration {
+  Duration::from_secs(5)
+}
+
+fn resource_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in resource Trigger {}",
+    field, message,
+  ))
+}
+
+pub fn resource_trigger_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let string_field = |field: &str| -> Result<Option<String>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| {
+        x.as_str()
+          .map(|s| s.to_string())
+          .ok_or(resource_field_error(field, "must be a string."))
+      })
+      .transpose()
+  };
+  let duration_field = |field: &str| -> Result<Option<Duration>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| duration_des(x).map_err(|e| resource_field_error(field, &e)))
+      .transpose()
+  };
+  let threshold = ResourceThreshold::parse(
+    &string_field("threshold")?
+      .ok_or(resource_field_error("threshold", "is required."))?,
+  )
+  .map_err(|e| resource_field_error("threshold", &e))?;
+  let trigger = ResourceTrigger {
+    process_name: string_field("process_name")?,
+    process_regex: string_field("process_regex")?,
+    cmdline_regex: string_field("cmdline_regex")?,
+    duration: duration_field("duration")?.unwrap_or(Duration::ZERO),
+    hysteresis: match section_data.get("hysteresis") {
+      None => 0.0,
+      Some(Value::Integer(i)) if *i >= 0 => *i as f64,
+      Some(Value::Float(f)) if *f >= 0.0 => *f,
+      Some(Value::String(s)) => threshold
+        .metric
+        .amount_parse(s)
+        .map_err(|e| resource_field_error("hysteresis", &e))?,
+      Some(_) => {
+        return Err(resource_field_error(
+          "hysteresis",
+          "must be a positive amount.",
+        ))
+      }
+    },
+    interval: duration_field("interval")?
+      .unwrap_or(resource_default_interval()),
+    threshold,
+  };
+  let process = trigger.process_name.is_some()
+    || trigger.process_regex.is_some()
+    || trigger.cmdline_regex.is_some();
+  let metric = trigger.threshold.metric;
+  if process && !metric.for_process() {
+    return Err(resource_field_error(
+      "threshold",
+      &format!("can't measure {} for processes.", metric),
+    ));
+  }
+  if !process && !metric.for_system() {
+    return Err(resource_field_error(
+      "threshold",
+      &format!(
+        "can only measure {} for processes, which needs 'process_name', \
+         'process_regex', or 'cmdline_regex'.",
+        metric,
+      ),
+    ));
+  }
+  if !ResourceSampler::supports(&metric, process) {
+    return Err(resource_field_error(
+      "threshold",
+      &format!("can't measure {} on this platform.", metric),
+    ));
+  }
+  if trigger.interval.is_zero() {
+    return Err(resource_field_error("interval", "must be more than zero."));
+  }
+  ProcessMatcher::new(
+    &trigger.process_name,
+    &trigger.process_regex,
+    &trigger.cmdline_regex,
+  )?;
+  Ok(Box::new(trigger))
+}
+
+/**
+ * Where /proc isn't around, ps gives each process's CPU and memory, and the
+ * load average comes from getloadavg.  ps reports CPU as an average over
+ * recent time rather than since the last sample.
+ */
+#[cfg(not(target_os = "linux"))]
+#[derive(Default)]
+pub struct ResourceSampler {}
+
+#[cfg(not(target_os = "linux"))]
+impl ResourceSampler {
+  pub fn new() -> Self {
+    ResourceSampler {}
+  }
+
+  pub fn supports(metric: &ResourceMetric, process: bool) -> bool {
+    match process {
+      true => matches!(metric, ResourceMetric::Cpu | ResourceMetric::Rss),
+      false => matches!(metric, ResourceMetric::Load),
+    }
+  }
+
+  pub fn process_measures(
+    &mut self,
+    pids: &[u32],
+  ) -> BTreeMap<u32, ResourceMeasures> {
+    if pids.is_empty() {
+      return BTreeMap::new();
+    }
+    let pid_list = pids
+      .iter()
+      .map(|pid| pid.to_string())
+      .collect::<Vec<String>>()
+      .join(",");
+    let output = match std::process::Command::new("ps")
+      .args(["-o", "pid=,%cpu=,rss=", "-p", &pid_list])
+      .output()
+    {
+      Ok(output) => output,
+      Err(e) => {
+        warn!("Could not measure processes with ps: {:?}", e);
+        return BTreeMap::new();
+      }
+    };
+    String::from_utf8_lossy(&output.stdout)
+      .lines()
+      .filter_map(|l| {
+        let mut columns = l.split_whitespace();
+        let pid = columns.next()?.parse::<u32>().ok()?;
+        let cpu = columns.next()?.parse::<f64>().ok()?;
+        let rss_kb = columns.next()?.parse::<f64>().ok()?;
+        Some((
+          pid,
+          ResourceMeasures::from([
+            (ResourceMetric::Cpu, cpu),
+            (ResourceMetric::Rss, rss_kb * 1024.0),
+          ]),
+        ))
+      })
+      .collect()
+  }
+
+  pub fn system_measures(&mut self) -> Result<ResourceMeasures, AppError> {
+    let mut load = [0.0_f64; 3];
+    // Safe because the array outlives the call and its length is passed.
+    match unsafe { libc::getloadavg(load.as_mut_ptr(), 3) } {
+      n if n >= 1 => {
+        Ok(ResourceMeasures::from([(ResourceMetric::Load, load[0])]))
+      }
+      _ => Err(AppError::TriggerRuntimeError(
+        "Could not read the load average.".to_string(),
+      )),
+    }
+  }
+}
+
+// Where each process (or the system, as None) stands against the threshold.
+// Anything that isn't here is clear of it.
+enum ResourceState {
+  // The threshold has held since then.
+  Pending(Instant),
+  // The trigger fired, and the threshold hasn't cleared since.
+  Fired,
+}
+
+fn resource_value_format(value: f64) -> String {
+  ((value * 100.0).round() / 100.0).to_string()
+}
+
+impl ResourceTrigger {
+  fn measure(
+    &self,
+    matcher: &Option<ProcessMatcher>,
+    sampler: &mut ResourceSampler,
+  ) -> Result<Vec<(Option<ProcessInfo>, ResourceMeasures)>, AppError> {
+    match matcher {
+      None => Ok(vec![(None, sampler.system_measures()?)]),
+      Some(matcher) => {
+        let processes = matcher.matching(process_list()?);
+        let pids: Vec<u32> = processes.keys().copied().collect();
+        let mut measured = sampler.process_measures(&pids);
+        Ok(
+          processes
+            .into_iter()
+            .filter_map(|(pid, info)| {
+              Some((Some(info), measured.remove(&pid)?))
+            })
+            .collect(),
+        )
+      }
+    }
+  }
+
+  fn event(
+    &self,
+    value: f64,
+    process: &Option<ProcessInfo>,
+    measures: &ResourceMeasures,
+  ) -> TriggerEvent {
+    let event = TriggerEvent::new("resource")
+      .field("resource_metric", self.threshold.metric)
+      .field("resource_value", resource_value_format(value))
+      .field("resource_threshold", &self.threshold.expression)
+      .fields(
+        measures
+          .iter()
+          .map(|(metric, value)| {
+            (
+              format!("resource_{}", metric),
+              resource_value_format(*value),
+            )
+          })
+          .collect(),
+      );
+    match process {
+      Some(info) => event
+        .field("resource_pid", info.pid)
+        .field("resource_process_name", &info.name)
+        .field("resource_process_cmdline", &info.cmdline)
+        .field("resource_process_user", &info.user),
+      None => event,
+    }
+  }
+}
+
+#[typetag::serde]
+impl Trigger for ResourceTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let matcher = match self.process_name.is_some()
+      || self.process_regex.is_some()
+      || self.cmdline_regex.is_some()
+    {
+      true => Some(ProcessMatcher::new(
+        &self.process_name,
+        &self.process_regex,
+        &self.cmdline_regex,
+      )?),
+      false => None,
+    };
+    info!(
+      "Measuring {} every {:?} for {:?}.",
+      self.threshold.expression, self.interval, self.duration,
+    );
+    let mut sampler = ResourceSampler::new();
+    let mut states: BTreeMap<Option<u32>, ResourceState> = BTreeMap::new();
+    loop {
+      let measured = self.measure(&matcher, &mut sampler)?;
+      let now = Instant::now();
+      states.retain(|pid, _| {
+        measured
+          .iter()
+          .any(|(info, _)| info.as_ref().map(|i| i.pid) == *pid)
+      });
+      for (process, measures) in measured {
+        let pid = process.as_ref().map(|i| i.pid);
+        // CPU and I/O need two samples to be measured.
+        let Some(value) = measures.get(&self.threshold.metric).copied() else {
+          continue;
+        };
+        trace!("Measured {:?} for {:?}.", measures, pid);
+        let holds = self.threshold.holds(value);
+        match states.get(&pid) {
+          None if holds => {
+            states.insert(pid, ResourceState::Pending(now));
+          }
+          Some(ResourceState::Pending(_)) if !holds => {
+            states.remove(&pid);
+          }
+          Some(ResourceState::Fired)
+            if self.threshold.cleared(value, self.hysteresis) =>
+          {
+            debug!("{} cleared for {:?}.", self.threshold.expression, pid);
+            states.remove(&pid);
+          }
+          _ => (),
+        }
+        if let Some(ResourceState::Pending(since)) = states.get(&pid) {
+          if now.duration_since(*since) >= self.duration {
+            debug!(
+              "{} held for {:?} for {:?}.",
+              self.threshold.expression, self.duration, pid,
+            );
+            states.insert(pid, ResourceState::Fired);
+            if let Err(e) =
+              send_to_sytter.send(self.event(value, &process, &measures))
+            {
+              debug!("Error triggering Sytter from resource trigger: {:?}", e);
+            }
+          }
+        }
+      }
+      match receive_from_sytter.recv_timeout(self.interval) {
+        Err(RecvTimeoutError::Timeout) => (),
+        Ok(TriggerControl::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+          return Ok(())
+        }
+      }
+    }
+  }
+}
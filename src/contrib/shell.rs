@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::fs::{remove_file, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
 use toml::Table;
-use tracing::{error, trace};
+use tracing::{error, trace, warn};
 use uuid::Uuid;
 
 use crate::{
@@ -9,8 +12,12 @@ use crate::{
   deserialize::vec_i32_des,
   error::AppError,
   executor::Executor,
-  failure::Failure,
-  shell::{shell_exec_check, shell_exec_outputs, with_shell_functions},
+  failure::{Failure, FailureDetails},
+  run::RunContext,
+  shell::{
+    run_context_envs, shell_exec_check, shell_exec_outputs,
+    with_shell_functions,
+  },
 };
 
 fn shell_default_exit_codes() -> Vec<i32> {
@@ -54,13 +61,19 @@ pub fn shell_condition_toml_deserialize(
 
 #[typetag::serde]
 impl Condition for ShellCondition {
-  fn check_condition(&self, config: &Config) -> Result<bool, AppError> {
+  fn check_condition(
+    &self,
+    config: &Config,
+    context: &RunContext,
+  ) -> Result<bool, AppError> {
     shell_exec_check(
       config.http_port,
       &self.shell,
       &self.expected_exit_codes,
       &with_shell_functions(&self.script),
       &self.id,
+      &run_context_envs(context),
+      &context.cancel,
     )
   }
 }
@@ -98,12 +111,18 @@ pub fn shell_executor_toml_deserialize(
 
 #[typetag::serde]
 impl Executor for ShellExecutor {
-  fn execute(&self, config: &Config) -> Result<(), AppError> {
+  fn execute(
+    &self,
+    config: &Config,
+    context: &RunContext,
+  ) -> Result<(), AppError> {
     shell_exec_outputs(
       config.http_port,
       &self.shell,
       &with_shell_functions(&self.script),
       &self.id,
+      &run_context_envs(context),
+      &context.cancel,
     )
     .inspect(|_| trace!("Executed '{:?}' successfully.", self.id))
     .inspect_err(|_| {
@@ -143,16 +162,77 @@ pub fn shell_failure_toml_deserialize(
   }))
 }
 
+/// Expose the failure to the script as sytter_error_* variables.  Output can be
+/// large or awkward to quote, so the full report is also written to a file whose
+/// path is in sytter_error_file.
+fn shell_failure_envs(
+  details: &FailureDetails,
+  report_path: &str,
+) -> Vec<(String, String)> {
+  vec![
+    ("sytter_error_kind", details.kind.clone()),
+    ("sytter_error_component", details.component.clone()),
+    (
+      "sytter_error_step",
+      details.step.map(|s| s.to_string()).unwrap_or_default(),
+    ),
+    ("sytter_error_message", details.message.clone()),
+    (
+      "sytter_error_stdout",
+      details.stdout.clone().unwrap_or_default(),
+    ),
+    (
+      "sytter_error_stderr",
+      details.stderr.clone().unwrap_or_default(),
+    ),
+    (
+      "sytter_error_exit_code",
+      details.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+    ),
+    ("sytter_error_file", report_path.to_string()),
+  ]
+  .into_iter()
+  .map(|(k, v)| (k.to_string(), v))
+  .collect()
+}
+
 #[typetag::serde]
 impl Failure for ShellFailure {
-  // TODO: This should also take the status.
-  fn execute(&self, config: &Config, _error: AppError) -> Result<(), AppError> {
-    shell_exec_outputs(
+  fn execute(
+    &self,
+    config: &Config,
+    context: &RunContext,
+    error: &AppError,
+  ) -> Result<(), AppError> {
+    let details = FailureDetails::from(error);
+    let report_path =
+      std::env::temp_dir().join(format!("sytter-error-{}.txt", Uuid::new_v4()));
+    // The report has the run's output in it, which can have secrets in it, so
+    // only we can read it.  A new name each time means nothing else can have
+    // made it first.
+    OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .mode(0o600)
+      .open(&report_path)
+      .and_then(|mut f| f.write_all(details.report().as_bytes()))
+      .map_err(AppError::FailureReportWriteError)?;
+    let result = shell_exec_outputs(
       config.http_port,
       &self.shell,
       &with_shell_functions(&self.script),
       &self.id,
+      &run_context_envs(context)
+        .into_iter()
+        .chain(shell_failure_envs(&details, &report_path.to_string_lossy()))
+        .collect::<Vec<(String, String)>>(),
+      &context.cancel,
     )
-    .map(|_| ())
+    .map(|_| ());
+    // The handler has had its chance to copy the report somewhere durable.
+    let _ = remove_file(&report_path).inspect_err(|e| {
+      warn!("Could not remove '{}': {:?}", report_path.display(), e)
+    });
+    result
   }
 }
@@ -0,0 +1,170 @@
+use crate::{
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use signal_hook::{
+  consts::{SIGHUP, SIGUSR1, SIGUSR2},
+  flag::register_conditional_default,
+  iterator::Signals,
+};
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{Receiver, RecvTimeoutError, SyncSender},
+    Arc, Mutex,
+  },
+  time::Duration,
+};
+use toml::Table;
+use tracing::*;
+
+/// The signals a trigger can listen for.  SIGTERM and SIGINT are how Sytter is
+/// asked to shut down, so they aren't among them.
+const SIGNALS: [(&str, i32); 3] = [
+  ("SIGHUP", SIGHUP),
+  ("SIGUSR1", SIGUSR1),
+  ("SIGUSR2", SIGUSR2),
+];
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SignalTrigger {
+  // Such as "SIGUSR1".
+  pub signal: String,
+  pub number: i32,
+}
+
+/// Find a signal by its name, with or without "SIG" in front.
+fn signal_number(name: &str) -> Option<(&'static str, i32)> {
+  let name = name.to_uppercase();
+  let name = name.strip_prefix("SIG").unwrap_or(&name);
+  SIGNALS
+    .into_iter()
+    .find(|(n, _)| n.strip_prefix("SIG") == Some(name))
+}
+
+pub fn signal_trigger_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let name = section_data
+    .get("signal")
+    .ok_or(AppError::SytterDeserializeRawError(
+      "Field 'signal' missing from signal Trigger.".to_string(),
+    ))?
+    .as_str()
+    .ok_or(AppError::SytterDeserializeRawError(
+      "Field 'signal' in signal Trigger must be a string.".to_string(),
+    ))?;
+  let (signal, number) =
+    signal_number(name).ok_or(AppError::SytterDeserializeRawError(format!(
+      "Field 'signal' in signal Trigger must be one of {}, not '{}'.",
+      SIGNALS.map(|(n, _)| n).join(", "),
+      name,
+    )))?;
+  Ok(Box::new(SignalTrigger {
+    signal: signal.to_string(),
+    number,
+  }))
+}
+
+lazy_static! {
+  // For each signal a trigger has listened for: how many triggers are listening
+  // now, and whether the signal should do what it does by default.
+  static ref SIGNAL_LISTENERS: Mutex<HashMap<i32, (usize, Arc<AtomicBool>)>> =
+    Mutex::new(HashMap::new());
+}
+
+/// Held by a trigger while it listens for a signal.  signal-hook keeps its
+/// handler installed even once every `Signals` is dropped, which would leave
+/// the signal caught and ignored.  So the first time a signal is listened for,
+/// an action is added that ends the process like the default would, and it's
+/// switched on whenever no trigger is listening.
+struct SignalListening {
+  number: i32,
+}
+
+impl SignalListening {
+  fn new(number: i32) -> Result<Self, std::io::Error> {
+    let mut listeners =
+      SIGNAL_LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    let (count, default) = match listeners.get_mut(&number) {
+      Some(listener) => listener,
+      None => {
+        let default = Arc::new(AtomicBool::new(false));
+        register_conditional_default(number, default.clone())?;
+        listeners.entry(number).or_insert((0, default))
+      }
+    };
+    *count += 1;
+    default.store(false, Ordering::SeqCst);
+    Ok(SignalListening { number })
+  }
+}
+
+impl Drop for SignalListening {
+  fn drop(&mut self) {
+    let mut listeners =
+      SIGNAL_LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((count, default)) = listeners.get_mut(&self.number) {
+      *count -= 1;
+      if *count == 0 {
+        default.store(true, Ordering::SeqCst);
+      }
+    }
+  }
+}
+
+// Signals are checked for in slices this long, so a shutdown doesn't have to
+// wait for one.
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[typetag::serde]
+impl Trigger for SignalTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let listen_error = |e: std::io::Error| {
+      AppError::TriggerInitializeError(format!(
+        "Could not listen for {}: {:?}",
+        self.signal, e,
+      ))
+    };
+    // Dropped after `signals`, so the signal can't end Sytter while this
+    // trigger is still listening.
+    let _listening = SignalListening::new(self.number).map_err(listen_error)?;
+    // Every trigger gets its own registration, so each one hears about every
+    // signal.  It's removed when this is dropped.
+    let mut signals = Signals::new([self.number]).map_err(listen_error)?;
+    info!(
+      "Listening for {} (PID {}).",
+      self.signal,
+      std::process::id(),
+    );
+    loop {
+      // The same signal arriving more than once between checks counts once.
+      if signals.pending().next().is_some() {
+        debug!("Got {}.", self.signal);
+        let event = TriggerEvent::new("signal")
+          .field("signal_name", &self.signal)
+          .field("signal_number", self.number);
+        if let Err(e) = send_to_sytter.send(event) {
+          debug!("Error triggering Sytter from signal trigger: {:?}", e);
+        }
+      }
+      match receive_from_sytter.recv_timeout(SIGNAL_POLL_INTERVAL) {
+        Err(RecvTimeoutError::Timeout) => (),
+        Ok(TriggerControl::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+          debug!(
+            "Sytter is closing. No longer listening for {}.",
+            self.signal
+          );
+          return Ok(());
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,205 @@
+use crate::{
+  error::AppError,
+  state::{State, StateChange, StateScope},
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use glob::Pattern;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+  str::FromStr,
+  sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TryRecvError},
+  time::Duration,
+};
+use strum_macros::{Display, EnumString};
+use toml::Table;
+use tracing::*;
+
+#[derive(
+  Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
+)]
+pub enum StateEvent {
+  Create,
+  Change,
+  Delete,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StateTrigger {
+  pub events: Vec<StateEvent>,
+  // A glob for the variable names, such as "sytter_vpn_*".
+  pub key: String,
+  // Where to watch.  Run namespaces come and go with their runs, so only the
+  // global namespace and Sytters' namespaces can be watched.
+  pub scope: StateScope,
+  // The value it has to have: the new value, or for a Delete, the old one.
+  pub value: Option<String>,
+  pub value_regex: Option<String>,
+}
+
+fn state_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in state Trigger {}",
+    field, message,
+  ))
+}
+
+fn state_key_pattern(key: &str) -> Result<Pattern, AppError> {
+  Pattern::new(key).map_err(|e| {
+    state_field_error("key", &format!("is not a valid glob: {}", e))
+  })
+}
+
+fn state_value_regex_compile(
+  pattern: &Option<String>,
+) -> Result<Option<Regex>, AppError> {
+  pattern
+    .as_ref()
+    .map(|p| {
+      Regex::new(p).map_err(|e| {
+        state_field_error(
+          "value_regex",
+          &format!("is not a valid regular expression: {}", e),
+        )
+      })
+    })
+    .transpose()
+}
+
+pub fn state_trigger_toml_deserialize(
+  sytter_name: &str,
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let string_field = |field: &str| -> Result<Option<String>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| {
+        x.as_str()
+          .map(|s| s.to_string())
+          .ok_or(state_field_error(field, "must be a string."))
+      })
+      .transpose()
+  };
+  let events = match section_data.get("events") {
+    None => vec![StateEvent::Create, StateEvent::Change, StateEvent::Delete],
+    Some(events) => events
+      .as_array()
+      .ok_or(state_field_error("events", "must be a list of strings."))?
+      .iter()
+      .map(|e| {
+        e.as_str().and_then(|e| StateEvent::from_str(e).ok()).ok_or(
+          state_field_error(
+            "events",
+            &format!("has an unknown event '{}'.", e),
+          ),
+        )
+      })
+      .collect::<Result<Vec<StateEvent>, AppError>>()?,
+  };
+  let scope = match string_field("scope")?.as_deref() {
+    None | Some("global") => StateScope::Global,
+    // Another Sytter's namespace can be watched by naming it.
+    Some("sytter") => StateScope::Sytter(
+      string_field("sytter")?.unwrap_or(sytter_name.to_string()),
+    ),
+    Some(scope) => {
+      return Err(state_field_error(
+        "scope",
+        &format!("must be 'global' or 'sytter', not '{}'.", scope),
+      ))
+    }
+  };
+  let trigger = StateTrigger {
+    events,
+    key: string_field("key")?.ok_or(AppError::SytterDeserializeRawError(
+      "Field 'key' missing from state Trigger.".to_string(),
+    ))?,
+    scope,
+    value: string_field("value")?,
+    value_regex: string_field("value_regex")?,
+  };
+  state_key_pattern(&trigger.key)?;
+  state_value_regex_compile(&trigger.value_regex)?;
+  Ok(Box::new(trigger))
+}
+
+// Changes are waited on in slices this long, so a shutdown doesn't have to
+// wait for one.
+const STATE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn state_change_event(change: &StateChange) -> StateEvent {
+  match (&change.old_value, &change.new_value) {
+    (None, _) => StateEvent::Create,
+    (Some(_), Some(_)) => StateEvent::Change,
+    (Some(_), None) => StateEvent::Delete,
+  }
+}
+
+impl StateTrigger {
+  fn wants(
+    &self,
+    key: &Pattern,
+    value_regex: &Option<Regex>,
+    change: &StateChange,
+  ) -> bool {
+    let value = change.new_value.as_ref().or(change.old_value.as_ref());
+    change.scope == self.scope
+      && key.matches(&change.key)
+      && self.events.contains(&state_change_event(change))
+      && self.value.as_ref().is_none_or(|v| value == Some(v))
+      && value_regex
+        .as_ref()
+        .is_none_or(|r| value.is_some_and(|v| r.is_match(v)))
+  }
+}
+
+#[typetag::serde]
+impl Trigger for StateTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let key = state_key_pattern(&self.key)?;
+    let value_regex = state_value_regex_compile(&self.value_regex)?;
+    let changes = State::subscribe();
+    info!(
+      "Listening for state events {:?} on '{}' in {:?}.",
+      self.events, self.key, self.scope,
+    );
+    loop {
+      match changes.recv_timeout(STATE_POLL_INTERVAL) {
+        Ok(change) if self.wants(&key, &value_regex, &change) => {
+          debug!("State change {:?}.", change);
+          let mut event = TriggerEvent::new("state")
+            .field("state_event", state_change_event(&change))
+            .field("state_key", &change.key);
+          if let Some(old_value) = &change.old_value {
+            event = event.field("state_old_value", old_value);
+          }
+          if let Some(new_value) = &change.new_value {
+            event = event.field("state_new_value", new_value);
+          }
+          if let Err(e) = send_to_sytter.send(event) {
+            debug!("Error triggering Sytter from state trigger: {:?}", e);
+          }
+        }
+        Ok(change) => trace!("State change {:?} skipped.", change),
+        Err(RecvTimeoutError::Timeout) => (),
+        // The state keeps every subscription it hands out until it's dropped.
+        Err(RecvTimeoutError::Disconnected) => {
+          return Err(AppError::TriggerRuntimeError(
+            "Stopped hearing about state changes.".to_string(),
+          ))
+        }
+      }
+      match receive_from_sytter.try_recv() {
+        Err(TryRecvError::Empty) => (),
+        Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+          debug!("Sytter is closing. Ignoring state changes...");
+          return Ok(());
+        }
+      }
+    }
+  }
+}
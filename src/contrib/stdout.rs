@@ -0,0 +1,231 @@
+use crate::{
+  error::AppError,
+  shell::process_group_terminate,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::BTreeMap,
+  io::{BufRead, BufReader, Read},
+  os::unix::process::CommandExt,
+  process::{Child, Command, Stdio},
+  sync::mpsc::{channel, Receiver, RecvTimeoutError, SyncSender, TryRecvError},
+  time::Duration,
+};
+use toml::Table;
+use tracing::*;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StdoutTrigger {
+  // Run with the shell, and expected to keep running.
+  pub command: String,
+  pub shell: String,
+  // Only lines matching this fire, when given.
+  pub line_regex: Option<String>,
+}
+
+fn stdout_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in stdout Trigger {}",
+    field, message,
+  ))
+}
+
+fn stdout_line_regex_compile(
+  pattern: &Option<String>,
+) -> Result<Option<Regex>, AppError> {
+  pattern
+    .as_ref()
+    .map(|p| {
+      Regex::new(p).map_err(|e| {
+        stdout_field_error(
+          "line_regex",
+          &format!("is not a valid regular expression: {}", e),
+        )
+      })
+    })
+    .transpose()
+}
+
+pub fn stdout_trigger_toml_deserialize(
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let string_field = |field: &str| -> Result<Option<String>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| {
+        x.as_str()
+          .map(|s| s.to_string())
+          .ok_or(stdout_field_error(field, "must be a string."))
+      })
+      .transpose()
+  };
+  let trigger = StdoutTrigger {
+    command: string_field("command")?.ok_or(
+      AppError::SytterDeserializeRawError(
+        "Field 'command' missing from stdout Trigger.".to_string(),
+      ),
+    )?,
+    shell: string_field("shell")?.unwrap_or("/bin/bash".to_string()),
+    line_regex: string_field("line_regex")?,
+  };
+  // Catch a bad pattern when the Sytter loads, rather than on every restart.
+  stdout_line_regex_compile(&trigger.line_regex)?;
+  Ok(Box::new(trigger))
+}
+
+// The command is waited on in slices this long, so a shutdown doesn't have to
+// wait for a line.
+const STDOUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The running command, which is stopped (along with anything it started)
+/// however the trigger ends.
+struct StdoutCommand(Child);
+
+impl Drop for StdoutCommand {
+  fn drop(&mut self) {
+    // Once it has been waited on, its process group could belong to someone
+    // else.
+    if let Ok(None) = self.0.try_wait() {
+      process_group_terminate(&mut self.0);
+    }
+  }
+}
+
+/// Send each line from a pipe until it closes.  Lines don't have to be UTF-8.
+fn pipe_lines(
+  pipe: impl Read + Send + 'static,
+  mut callback: impl FnMut(String) + Send + 'static,
+) {
+  std::thread::spawn(move || {
+    let mut reader = BufReader::new(pipe);
+    let mut line = vec![];
+    while let Ok(n) = reader.read_until(b'\n', &mut line) {
+      if n == 0 {
+        break;
+      }
+      callback(
+        String::from_utf8_lossy(&line)
+          .trim_end_matches(['\r', '\n'])
+          .to_string(),
+      );
+      line.clear();
+    }
+  });
+}
+
+/// What a pattern captured from a line, as "<kind>_capture_<group>" fields:
+/// groups by number, and named groups by name too.  Groups that didn't take
+/// part are left out.  Nothing if the line doesn't match.
+pub fn line_captures(
+  kind: &str,
+  regex: &Regex,
+  line: &str,
+) -> Option<BTreeMap<String, String>> {
+  let captures = regex.captures(line)?;
+  let mut fields = BTreeMap::new();
+  for (i, m) in captures.iter().enumerate().skip(1) {
+    if let Some(m) = m {
+      fields.insert(format!("{}_capture_{}", kind, i), m.as_str().to_string());
+    }
+  }
+  for name in regex.capture_names().flatten() {
+    if let Some(m) = captures.name(name) {
+      fields
+        .insert(format!("{}_capture_{}", kind, name), m.as_str().to_string());
+    }
+  }
+  Some(fields)
+}
+
+/// The line, and what the pattern captured from it.
+fn stdout_event(line: String, regex: &Option<Regex>) -> Option<TriggerEvent> {
+  let captures = match regex {
+    Some(regex) => line_captures("stdout", regex, &line)?,
+    None => BTreeMap::new(),
+  };
+  Some(
+    TriggerEvent::new("stdout")
+      .fields(captures)
+      .field("stdout_line", line),
+  )
+}
+
+#[typetag::serde]
+impl Trigger for StdoutTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let regex = stdout_line_regex_compile(&self.line_regex)?;
+    // The command gets its own process group so stopping it doesn't leave its
+    // children behind.
+    let mut command = StdoutCommand(
+      Command::new(&self.shell)
+        .args(["-c", &self.command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()
+        .map_err(|e| {
+          AppError::TriggerInitializeError(format!(
+            "Could not run '{}': {:?}",
+            self.command, e,
+          ))
+        })?,
+    );
+    info!(
+      "Watching the output of '{}' (PID {}).",
+      self.command,
+      command.0.id(),
+    );
+    let (send_line, receive_line) = channel::<String>();
+    if let Some(stdout) = command.0.stdout.take() {
+      pipe_lines(stdout, move |line| {
+        let _ = send_line.send(line);
+      });
+    }
+    if let Some(stderr) = command.0.stderr.take() {
+      let name = self.command.clone();
+      pipe_lines(stderr, move |line| debug!("{}: {}", name, line));
+    }
+    loop {
+      match receive_line.recv_timeout(STDOUT_POLL_INTERVAL) {
+        Ok(line) => {
+          trace!("Line from '{}': {}", self.command, line);
+          if let Some(event) = stdout_event(line, &regex) {
+            if let Err(e) = send_to_sytter.send(event) {
+              debug!("Error triggering Sytter from stdout trigger: {:?}", e);
+            }
+          }
+        }
+        Err(RecvTimeoutError::Timeout) => (),
+        // The command closed its output, which almost always means it has
+        // exited.  Either way it is done, and is started over.
+        Err(RecvTimeoutError::Disconnected) => {
+          process_group_terminate(&mut command.0);
+          let status = command.0.wait().map_err(|e| {
+            AppError::TriggerRuntimeError(format!(
+              "Could not wait on '{}': {:?}",
+              self.command, e,
+            ))
+          })?;
+          return Err(AppError::TriggerRuntimeError(format!(
+            "'{}' stopped ({}).",
+            self.command, status,
+          )));
+        }
+      }
+      match receive_from_sytter.try_recv() {
+        Err(TryRecvError::Empty) => (),
+        Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+          debug!("Sytter is closing. Stopping '{}'...", self.command);
+          return Ok(());
+        }
+      }
+    }
+  }
+}
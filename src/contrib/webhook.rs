@@ -0,0 +1,362 @@
+use crate::{
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::{
+  collections::BTreeMap,
+  fmt::Debug,
+  sync::{
+    mpsc::{
+      channel, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError,
+    },
+    Arc, Mutex,
+  },
+  time::Duration,
+};
+use toml::Table;
+use tracing::*;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct WebhookTrigger {
+  // The Sytter this belongs to, which is the first part of the route.
+  pub sytter: String,
+  pub name: String,
+  // Callers send this as "Authorization: Bearer <secret>".
+  pub secret: Option<String>,
+  // Callers sign the body with HMAC-SHA256 using this, and send the signature
+  // in signature_header.
+  pub signature_secret: Option<String>,
+  pub signature_header: String,
+}
+
+// Sytters are logged whole at debug level, and the secrets shouldn't be.
+impl Debug for WebhookTrigger {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let redacted =
+      |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+    f.debug_struct("WebhookTrigger")
+      .field("sytter", &self.sytter)
+      .field("name", &self.name)
+      .field("secret", &redacted(&self.secret))
+      .field("signature_secret", &redacted(&self.signature_secret))
+      .field("signature_header", &self.signature_header)
+      .finish()
+  }
+}
+
+fn webhook_field_error(field: &str, message: &str) -> AppError {
+  AppError::SytterDeserializeRawError(format!(
+    "Field '{}' in webhook Trigger {}",
+    field, message,
+  ))
+}
+
+pub fn webhook_trigger_toml_deserialize(
+  sytter_name: &str,
+  section_data: &Table,
+) -> Result<Box<dyn Trigger>, AppError> {
+  let string_field = |field: &str| -> Result<Option<String>, AppError> {
+    section_data
+      .get(field)
+      .map(|x| {
+        x.as_str()
+          .map(|s| s.to_string())
+          .ok_or(webhook_field_error(field, "must be a string."))
+      })
+      .transpose()
+  };
+  let trigger = WebhookTrigger {
+    sytter: sytter_name.to_string(),
+    name: string_field("name")?.ok_or(AppError::SytterDeserializeRawError(
+      "Field 'name' missing from webhook Trigger.".to_string(),
+    ))?,
+    secret: string_field("secret")?,
+    signature_secret: string_field("signature_secret")?,
+    signature_header: string_field("signature_header")?
+      .unwrap_or("X-Hub-Signature-256".to_string()),
+  };
+  if trigger.name.is_empty() || trigger.name.contains('/') {
+    return Err(webhook_field_error(
+      "name",
+      "must be non-empty and can't have a '/' in it.",
+    ));
+  }
+  if trigger.secret.as_ref().is_some_and(|s| s.is_empty())
+    || trigger
+      .signature_secret
+      .as_ref()
+      .is_some_and(|s| s.is_empty())
+  {
+    return Err(AppError::SytterDeserializeRawError(
+      "Secrets in webhook Triggers can't be empty.".to_string(),
+    ));
+  }
+  Ok(Box::new(trigger))
+}
+
+/// Why a webhook was turned away.
+#[derive(Debug, PartialEq)]
+pub enum WebhookRejection {
+  // Nothing is listening on that route, at least right now.
+  NotFound,
+  // The secret or signature is missing or wrong.
+  Unauthorized,
+  // The body isn't JSON.
+  BadRequest(String),
+}
+
+/// What arrived over HTTP, for the trigger to check and turn into an event.
+pub struct WebhookRequest<'a> {
+  // Keyed by lowercase name.
+  pub headers: &'a BTreeMap<String, String>,
+  pub query: &'a BTreeMap<String, String>,
+  pub body: &'a [u8],
+  pub remote_address: Option<String>,
+}
+
+struct WebhookRoute {
+  trigger: WebhookTrigger,
+  send_to_trigger: Sender<TriggerEvent>,
+}
+
+lazy_static! {
+  // Keyed by Sytter name and webhook name.  Triggers add themselves while they
+  // are listening.
+  static ref WEBHOOK_ROUTES: Arc<Mutex<BTreeMap<(String, String), WebhookRoute>>> =
+    Arc::new(Mutex::new(BTreeMap::new()));
+}
+
+/// Takes the route out of service when the trigger stops, however it stops.
+struct WebhookRegistration((String, String));
+
+impl Drop for WebhookRegistration {
+  fn drop(&mut self) {
+    if let Ok(mut routes) = WEBHOOK_ROUTES.lock() {
+      routes.remove(&self.0);
+    }
+  }
+}
+
+/// Compare without stopping at the first difference, so how long it takes
+/// doesn't give away how much of a guess was right.
+fn secret_matches(given: &[u8], expected: &[u8]) -> bool {
+  given.len() == expected.len()
+    && given
+      .iter()
+      .zip(expected)
+      .fold(0, |difference, (a, b)| difference | (a ^ b))
+      == 0
+}
+
+/// Make a name safe to use in an environment variable name.
+fn webhook_key(key: &str) -> String {
+  key
+    .chars()
+    .map(|c| match c.is_ascii_alphanumeric() {
+      true => c,
+      false => '_',
+    })
+    .collect()
+}
+
+/// Flatten JSON into fields, with the keys (or indexes) on the way to each
+/// value joined by "_".  Strings are given as they are, and everything else
+/// as JSON.
+fn json_fields(
+  prefix: &str,
+  value: &Value,
+  fields: &mut BTreeMap<String, String>,
+) {
+  match value {
+    Value::Object(map) => map.iter().for_each(|(k, v)| {
+      json_fields(&format!("{}_{}", prefix, webhook_key(k)), v, fields)
+    }),
+    Value::Array(values) => values
+      .iter()
+      .enumerate()
+      .for_each(|(i, v)| json_fields(&format!("{}_{}", prefix, i), v, fields)),
+    Value::String(s) => {
+      fields.insert(prefix.to_string(), s.clone());
+    }
+    Value::Null => {
+      fields.insert(prefix.to_string(), String::new());
+    }
+    v => {
+      fields.insert(prefix.to_string(), v.to_string());
+    }
+  }
+}
+
+impl WebhookRequest<'_> {
+  fn header(&self, name: &str) -> Option<&str> {
+    self.headers.get(&name.to_lowercase()).map(|h| h.as_str())
+  }
+}
+
+impl WebhookTrigger {
+  fn authorized(&self, request: &WebhookRequest) -> bool {
+    let secret_ok = self.secret.as_ref().is_none_or(|secret| {
+      request
+        .header("Authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .is_some_and(|given| {
+          secret_matches(given.trim().as_bytes(), secret.as_bytes())
+        })
+    });
+    let signature_ok = self.signature_secret.as_ref().is_none_or(|secret| {
+      // Most services put the hash's name in front, such as "sha256=<hex>".
+      let Some(signature) = request
+        .header(&self.signature_header)
+        .and_then(|h| hex::decode(h.trim().trim_start_matches("sha256=")).ok())
+      else {
+        return false;
+      };
+      Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map(|mut mac| {
+          mac.update(request.body);
+          mac.verify_slice(&signature).is_ok()
+        })
+        .unwrap_or(false)
+    });
+    secret_ok && signature_ok
+  }
+
+  fn event(
+    &self,
+    request: &WebhookRequest,
+  ) -> Result<TriggerEvent, WebhookRejection> {
+    let mut fields: BTreeMap<String, String> = request
+      .query
+      .iter()
+      .map(|(k, v)| (format!("webhook_query_{}", webhook_key(k)), v.clone()))
+      .collect();
+    if !request.body.is_empty() {
+      let body: Value = serde_json::from_slice(request.body).map_err(|e| {
+        WebhookRejection::BadRequest(format!("The body isn't JSON: {}", e))
+      })?;
+      json_fields("webhook_body", &body, &mut fields);
+      // The whole body as it was sent, which takes the place of a body that
+      // is only a string or number.
+      fields.insert(
+        "webhook_body".to_string(),
+        String::from_utf8_lossy(request.body).to_string(),
+      );
+    }
+    if let Some(address) = &request.remote_address {
+      fields.insert("webhook_remote_address".to_string(), address.clone());
+    }
+    Ok(
+      TriggerEvent::new("webhook")
+        .field("webhook_name", &self.name)
+        .fields(fields),
+    )
+  }
+}
+
+/// Hand a request to the webhook trigger listening on the route, if there is
+/// one and the request checks out.
+pub fn webhook_deliver(
+  sytter: &str,
+  name: &str,
+  request: WebhookRequest,
+) -> Result<(), WebhookRejection> {
+  let routes = WEBHOOK_ROUTES
+    .lock()
+    .map_err(|_| WebhookRejection::NotFound)?;
+  let route = routes
+    .get(&(sytter.to_string(), name.to_string()))
+    .ok_or(WebhookRejection::NotFound)?;
+  if !route.trigger.authorized(&request) {
+    warn!(
+      "Webhook {}/{} from {} was not authorized.",
+      sytter,
+      name,
+      request.remote_address.as_deref().unwrap_or("unknown"),
+    );
+    return Err(WebhookRejection::Unauthorized);
+  }
+  let event = route.trigger.event(&request)?;
+  // The trigger is stopping if this fails, which is as good as not being
+  // there.
+  route
+    .send_to_trigger
+    .send(event)
+    .map_err(|_| WebhookRejection::NotFound)
+}
+
+// Webhooks are waited on in slices this long, so a shutdown doesn't have to
+// wait for one.
+const WEBHOOK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[typetag::serde]
+impl Trigger for WebhookTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    // The HTTP server hands webhooks over on this, so it never has to wait on
+    // the Sytter.
+    let (send_to_trigger, receive_from_server) = channel::<TriggerEvent>();
+    let key = (self.sytter.clone(), self.name.clone());
+    {
+      let mut routes = WEBHOOK_ROUTES.lock().map_err(|e| {
+        AppError::TriggerInitializeError(format!(
+          "Webhook routes lock poisoned: {:?}",
+          e,
+        ))
+      })?;
+      if routes.contains_key(&key) {
+        return Err(AppError::TriggerInitializeError(format!(
+          "Another webhook Trigger in {} is already named '{}'.",
+          self.sytter, self.name,
+        )));
+      }
+      routes.insert(
+        key.clone(),
+        WebhookRoute {
+          trigger: self.clone(),
+          send_to_trigger,
+        },
+      );
+    }
+    let _registration = WebhookRegistration(key);
+    if self.secret.is_none() && self.signature_secret.is_none() {
+      warn!(
+        "Webhook {}/{} has no secret, so anything that can reach Sytter's \
+         HTTP port can trigger it.",
+        self.sytter, self.name,
+      );
+    }
+    info!(
+      "Listening for webhooks at /hooks/{}/{}.",
+      self.sytter, self.name
+    );
+    loop {
+      match receive_from_server.recv_timeout(WEBHOOK_POLL_INTERVAL) {
+        Ok(event) => {
+          debug!("Webhook {}/{} received.", self.sytter, self.name);
+          if let Err(e) = send_to_sytter.send(event) {
+            debug!("Error triggering Sytter from webhook trigger: {:?}", e);
+          }
+        }
+        Err(RecvTimeoutError::Timeout) => (),
+        // The route holds the other end, so this can't happen while we're
+        // registered.
+        Err(RecvTimeoutError::Disconnected) => return Ok(()),
+      }
+      match receive_from_sytter.try_recv() {
+        Err(TryRecvError::Empty) => (),
+        Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+          debug!("Sytter is closing. Removing webhook {}...", self.name);
+          return Ok(());
+        }
+      }
+    }
+  }
+}
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use toml::Value;
 
 pub fn i32_des(x: &Value) -> Option<i32> {
@@ -11,3 +12,31 @@ pub fn vec_i32_des(o: Option<&Value>) -> Vec<i32> {
     })
     .unwrap_or(vec![0_i32])
 }
+
+/// Durations are written as a number with a unit, such as "500ms", "2s", "5m",
+/// or "1h".  A bare integer is taken as seconds.
+pub fn duration_des(x: &Value) -> Result<Duration, String> {
+  match x {
+    Value::Integer(i) if *i >= 0 => Ok(Duration::from_secs(*i as u64)),
+    Value::String(s) => {
+      let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+      let (number, unit) = s.split_at(split);
+      let n = number
+        .parse::<u64>()
+        .map_err(|_| format!("'{}' is not a duration.", s))?;
+      let seconds = |per: u64| {
+        n.checked_mul(per)
+          .map(Duration::from_secs)
+          .ok_or(format!("'{}' is not a duration.", s))
+      };
+      match unit.trim() {
+        "ms" => Ok(Duration::from_millis(n)),
+        "" | "s" => Ok(Duration::from_secs(n)),
+        "m" => seconds(60),
+        "h" => seconds(60 * 60),
+        _ => Err(format!("'{}' has an unknown unit '{}'.", s, unit)),
+      }
+    }
+    _ => Err(format!("'{}' is not a duration.", x)),
+  }
+}
@@ -1,16 +1,20 @@
 use std::env::VarError;
 
-use actix_web::ResponseError;
+use actix_web::{http::StatusCode, ResponseError};
 use strum_macros::Display;
 
 #[derive(Debug, Display)]
 pub enum AppError {
+  ConditionCheckError(Box<AppError>),
   ConfigEnvVarError(VarError),
   ConfigInvalidLogLevel(String),
+  ConfigInvalidShutdownGracePeriod(String),
   DeviceConnectionEventsMissingError(),
   DeviceConnectionEventsParseError(),
   DeviceConnectionEventParseError(),
   EventMutexLockError(String),
+  ExecutorStepError(usize, Box<AppError>),
+  FailureReportWriteError(std::io::Error),
   HttpBindError(std::io::Error),
   HttpHeaderValueToStringError(actix_web::http::header::ToStrError),
   HttpJsonSerializeError(serdeconv::Error),
@@ -22,11 +26,15 @@ pub enum AppError {
   PowerHookRegistrationFailed,
   PowerEventParseError,
   PowerEventsMissingError,
+  RunCancelledError,
   ShellChildTerminatedError,
-  ShellExecError((String, String)),
+  ShellExecError((String, String, i32)),
   ShellSpawnError(std::io::Error),
   ShellUtf8ConversionError(std::str::Utf8Error),
+  SignalHandlerError(std::io::Error),
   StateMutexPoisonedError(),
+  StateScopeInvalidError(String),
+  StateScopeUnresolvedError(String),
   SytterDeserializeError(toml::de::Error),
   SytterDeserializeRawError(String),
   SytterMissingComponentError(String),
@@ -38,4 +46,13 @@ pub enum AppError {
   // SytterVariableUpdateError(SytterVariable),
 }
 
-impl ResponseError for AppError {}
+impl ResponseError for AppError {
+  // Anything not named here is our problem, not the caller's.
+  fn status_code(&self) -> StatusCode {
+    match self {
+      AppError::StateScopeInvalidError(_)
+      | AppError::StateScopeUnresolvedError(_) => StatusCode::BAD_REQUEST,
+      _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+}
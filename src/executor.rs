@@ -1,10 +1,46 @@
-use crate::{config::Config, error::AppError};
+use crate::{config::Config, error::AppError, run::RunContext};
 use core::fmt::Debug;
 use dyn_clone::DynClone;
+use serde::{Deserialize, Serialize};
 
 #[typetag::serde(tag = "type")]
 pub trait Executor: Debug + Sync + Send + DynClone {
-  fn execute(&self, config: &Config) -> Result<(), AppError>;
+  fn execute(
+    &self,
+    config: &Config,
+    context: &RunContext,
+  ) -> Result<(), AppError>;
 }
 
 dyn_clone::clone_trait_object!(Executor);
+
+/// What the pipeline does after an executor fails.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutorErrorPolicy {
+  #[default]
+  Stop,
+  Continue,
+}
+
+/// An executor as it sits in a Sytter's pipeline.  The error policy is
+/// structural rather than part of any one executor kind, so it lives here
+/// instead of on each Executor implementation.
+#[derive(Clone, Debug)]
+pub struct ExecutorStep {
+  pub executor: Box<dyn Executor>,
+  pub on_error: ExecutorErrorPolicy,
+}
+
+pub fn executor_error_policy_deserialize(
+  s: &str,
+) -> Result<ExecutorErrorPolicy, AppError> {
+  match s {
+    "stop" => Ok(ExecutorErrorPolicy::Stop),
+    "continue" => Ok(ExecutorErrorPolicy::Continue),
+    _ => Err(AppError::SytterDeserializeRawError(format!(
+      "Field 'on_error' must be 'stop' or 'continue', got '{}'.",
+      s,
+    ))),
+  }
+}
@@ -1,10 +1,78 @@
-use crate::{config::Config, error::AppError};
+use crate::{config::Config, error::AppError, run::RunContext};
 use core::fmt::Debug;
 use dyn_clone::DynClone;
 
 #[typetag::serde(tag = "type")]
 pub trait Failure: Debug + Sync + Send + DynClone {
-  fn execute(&self, config: &Config, error: AppError) -> Result<(), AppError>;
+  fn execute(
+    &self,
+    config: &Config,
+    context: &RunContext,
+    error: &AppError,
+  ) -> Result<(), AppError>;
 }
 
 dyn_clone::clone_trait_object!(Failure);
+
+/// The parts of an error that a failure handler can do something useful with,
+/// such as putting them in a notification.  Wrapping errors (which say where
+/// the error happened) are peeled away so `kind` names the underlying problem.
+#[derive(Clone, Debug, Default)]
+pub struct FailureDetails {
+  pub kind: String,
+  pub component: String,
+  pub step: Option<usize>,
+  pub message: String,
+  pub stdout: Option<String>,
+  pub stderr: Option<String>,
+  pub exit_code: Option<i32>,
+}
+
+impl FailureDetails {
+  /// A plain text account of the failure, suitable for dropping into an email
+  /// or a log file.
+  pub fn report(&self) -> String {
+    format!(
+      "Kind: {}\nComponent: {}\nStep: {}\nExit code: {}\nMessage: {}\n\
+       \n--- stdout ---\n{}\n--- stderr ---\n{}\n",
+      self.kind,
+      self.component,
+      self.step.map(|s| s.to_string()).unwrap_or_default(),
+      self.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+      self.message,
+      self.stdout.clone().unwrap_or_default(),
+      self.stderr.clone().unwrap_or_default(),
+    )
+  }
+}
+
+impl From<&AppError> for FailureDetails {
+  fn from(error: &AppError) -> Self {
+    match error {
+      AppError::ConditionCheckError(e) => FailureDetails {
+        component: "condition".into(),
+        ..FailureDetails::from(e.as_ref())
+      },
+      AppError::ExecutorStepError(index, e) => FailureDetails {
+        component: "executor".into(),
+        step: Some(*index),
+        ..FailureDetails::from(e.as_ref())
+      },
+      AppError::ShellExecError((stdout, stderr, exit_code)) => FailureDetails {
+        kind: error.to_string(),
+        component: "sytter".into(),
+        message: format!("Shell exited with code {}.", exit_code),
+        stdout: Some(stdout.clone()),
+        stderr: Some(stderr.clone()),
+        exit_code: Some(*exit_code),
+        ..Default::default()
+      },
+      _ => FailureDetails {
+        kind: error.to_string(),
+        component: "sytter".into(),
+        message: format!("{:?}", error),
+        ..Default::default()
+      },
+    }
+  }
+}
@@ -1,11 +1,16 @@
-use crate::state::{State, SytterVariable};
+use crate::{
+  contrib::webhook::{webhook_deliver, WebhookRejection, WebhookRequest},
+  state::{State, StateScope, SytterVariable},
+  status::sytter_statuses,
+};
 use actix_web::{
+  dev::Server,
   http::header,
   web::{self, Data},
   App, HttpRequest, HttpResponse, HttpServer,
 };
-use futures::TryFutureExt;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use tracing::*;
 
 use crate::error::AppError;
@@ -18,8 +23,35 @@ fn data_to_text(data: &Vec<SytterVariable>) -> String {
     .join("\n")
 }
 
-pub async fn index(req: HttpRequest) -> Result<HttpResponse, AppError> {
-  let data = State::get_variables();
+/// Shell components identify themselves with these headers, which carry their
+/// run context.  Anything else (such as a person with curl) only sees the
+/// global namespace.
+const SYTTER_NAME_HEADER: &str = "X-Sytter-Name";
+const SYTTER_RUN_ID_HEADER: &str = "X-Sytter-Run-Id";
+
+fn header_value<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+  req
+    .headers()
+    .get(name)
+    .and_then(|h| h.to_str().ok())
+    .filter(|h| !h.is_empty())
+}
+
+#[derive(Deserialize)]
+pub struct StateQuery {
+  pub scope: Option<String>,
+}
+
+pub async fn index(
+  req: HttpRequest,
+  query: web::Query<StateQuery>,
+) -> Result<HttpResponse, AppError> {
+  let sytter_name = header_value(&req, SYTTER_NAME_HEADER);
+  let run_id = header_value(&req, SYTTER_RUN_ID_HEADER);
+  let data = State::get_variables(&match &query.scope {
+    Some(scope) => vec![StateScope::from_name(scope, sytter_name, run_id)?],
+    None => StateScope::visible(sytter_name, run_id),
+  });
   Ok(
     HttpResponse::Ok().body(
       match req
@@ -42,20 +74,71 @@ pub async fn index(req: HttpRequest) -> Result<HttpResponse, AppError> {
   )
 }
 
+#[derive(Clone, Deserialize)]
+pub struct StateWrite {
+  pub key: String,
+  pub value: String,
+  pub scope: Option<String>,
+}
+
+// Writes (and deletes) go to the calling Sytter's namespace unless told
+// otherwise.
+fn write_scope(
+  req: &HttpRequest,
+  scope: Option<&str>,
+) -> Result<StateScope, AppError> {
+  let sytter_name = header_value(req, SYTTER_NAME_HEADER);
+  let run_id = header_value(req, SYTTER_RUN_ID_HEADER);
+  StateScope::from_name(
+    scope.unwrap_or(sytter_name.map(|_| "sytter").unwrap_or("global")),
+    sytter_name,
+    run_id,
+  )
+}
+
 pub async fn upsert(
-  payload: web::Json<SytterVariable>,
+  req: HttpRequest,
+  payload: web::Json<StateWrite>,
 ) -> Result<HttpResponse, AppError> {
-  State::set_variable(payload.to_owned());
+  let scope = write_scope(&req, payload.scope.as_deref())?;
+  State::set_variable(
+    scope,
+    SytterVariable {
+      key: payload.key.clone(),
+      value: payload.value.clone(),
+    },
+  );
   Ok(HttpResponse::Ok().finish())
 }
 
+#[derive(Deserialize)]
+pub struct StateDelete {
+  pub key: String,
+  pub scope: Option<String>,
+}
+
+/// Returns HTTP 404 Not Found if there was no such variable to delete.
+pub async fn delete(
+  req: HttpRequest,
+  query: web::Query<StateDelete>,
+) -> Result<HttpResponse, AppError> {
+  let scope = write_scope(&req, query.scope.as_deref())?;
+  Ok(match State::delete_variable(&scope, &query.key) {
+    true => HttpResponse::Ok().finish(),
+    false => HttpResponse::NotFound().finish(),
+  })
+}
+
 /// Health check response structure following standard REST API conventions.
-/// Returns HTTP 200 with JSON body containing status and version.
+/// Returns JSON body containing status and version.
 #[derive(Serialize)]
 struct HealthResponse {
   status: String,
   #[serde(skip_serializing_if = "Option::is_none")]
   version: Option<String>,
+  // What is wrong, keyed by Sytter.  Only present when unhealthy.
+  #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+  problems: BTreeMap<String, Vec<String>>,
 }
 
 /// Health check endpoint handler.
@@ -72,39 +155,119 @@ struct HealthResponse {
 /// }
 /// ```
 ///
-/// This is a basic liveness check that indicates the HTTP server is responding.
+/// If any Sytter failed to start, or has a trigger that keeps crashing, this
+/// returns HTTP 503 Service Unavailable with a status of "unhealthy" and the
+/// problems listed by Sytter.
+///
 /// Future enhancements could include:
 /// - Database connectivity checks.
-/// - Trigger health status.
 /// - System resource checks.
 pub async fn health() -> HttpResponse {
+  let problems: BTreeMap<String, Vec<String>> = sytter_statuses()
+    .into_iter()
+    .map(|(name, status)| (name, status.problems()))
+    .filter(|(_, problems)| !problems.is_empty())
+    .collect();
   let response = HealthResponse {
-    status: "ok".to_string(),
+    status: if problems.is_empty() {
+      "ok"
+    } else {
+      "unhealthy"
+    }
+    .to_string(),
     version: Some(env!("CARGO_PKG_VERSION").to_string()),
+    problems,
   };
-  HttpResponse::Ok().json(response)
+  if response.problems.is_empty() {
+    HttpResponse::Ok().json(response)
+  } else {
+    HttpResponse::ServiceUnavailable().json(response)
+  }
+}
+
+/// Event and run counts, and trigger health, for every Sytter, as JSON keyed by
+/// Sytter name.
+pub async fn status() -> HttpResponse {
+  HttpResponse::Ok().json(sytter_statuses())
+}
+
+/// Hand a webhook to the webhook trigger registered for it.  The JSON body and
+/// query parameters go into the trigger's event.
+///
+/// Returns HTTP 202 Accepted once the trigger has it, 404 Not Found if no
+/// trigger is listening there, 401 Unauthorized if the secret or signature
+/// doesn't check out, and 400 Bad Request if the body isn't JSON.
+pub async fn webhook(
+  req: HttpRequest,
+  path: web::Path<(String, String)>,
+  query: web::Query<BTreeMap<String, String>>,
+  body: web::Bytes,
+) -> HttpResponse {
+  let (sytter, name) = path.into_inner();
+  let headers: BTreeMap<String, String> = req
+    .headers()
+    .iter()
+    .filter_map(|(k, v)| {
+      v.to_str()
+        .ok()
+        .map(|v| (k.as_str().to_lowercase(), v.to_string()))
+    })
+    .collect();
+  match webhook_deliver(
+    &sytter,
+    &name,
+    WebhookRequest {
+      headers: &headers,
+      query: &query,
+      body: &body,
+      remote_address: req.peer_addr().map(|a| a.ip().to_string()),
+    },
+  ) {
+    Ok(()) => HttpResponse::Accepted().finish(),
+    Err(WebhookRejection::NotFound) => HttpResponse::NotFound().finish(),
+    Err(WebhookRejection::Unauthorized) => {
+      HttpResponse::Unauthorized().finish()
+    }
+    Err(WebhookRejection::BadRequest(message)) => {
+      HttpResponse::BadRequest().body(message)
+    }
+  }
 }
 
-/// Start the HTTP server with the configured port.
+/// Create the HTTP server with the configured port.  It starts serving once the
+/// returned Server is awaited.
 ///
 /// Provides the following endpoints:
 /// - `GET /health` - Health check endpoint (standard REST convention).
 /// - `GET /healthz` - Health check endpoint (Kubernetes convention).
-/// - `GET /state` - Get all state variables.
-/// - `POST /state` - Set/update a state variable.
-pub async fn http_server(port: usize) -> Result<(), AppError> {
+/// - `GET /state` - Get the state variables visible to the caller.
+/// - `POST /state` - Set/update a state variable in the caller's namespace.
+/// - `DELETE /state` - Delete a state variable from the caller's namespace.
+/// - `GET /status` - Event and run counts, and trigger health, per Sytter.
+/// - `POST /hooks/{sytter}/{name}` - Fire a Sytter's webhook trigger.
+pub fn http_server(port: usize) -> Result<Server, AppError> {
   info!("HTTP server starting on port {}...", port);
-  HttpServer::new(move || {
-    App::new()
-      // Health check endpoints - support both common conventions.
-      .service(web::resource("/health").get(health))
-      .service(web::resource("/healthz").get(health))
-      // State management endpoints.
-      .service(web::resource("/state").get(index).post(upsert))
-  })
-  .bind(("0.0.0.0", port as u16))
-  .map_err(AppError::HttpBindError)?
-  .run()
-  .map_err(AppError::HttpStartError)
-  .await
+  Ok(
+    HttpServer::new(move || {
+      App::new()
+        // Health check endpoints - support both common conventions.
+        .service(web::resource("/health").get(health))
+        .service(web::resource("/healthz").get(health))
+        // State management endpoints.
+        .service(
+          web::resource("/state")
+            .get(index)
+            .post(upsert)
+            .delete(delete),
+        )
+        .service(web::resource("/status").get(status))
+        .service(web::resource("/hooks/{sytter}/{name}").post(webhook))
+    })
+    // Signals are handled by main, which has Sytters to stop before the server
+    // can go.
+    .disable_signals()
+    .bind(("0.0.0.0", port as u16))
+    .map_err(AppError::HttpBindError)?
+    .run(),
+  )
 }
@@ -0,0 +1,156 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  contrib::device::{DeviceConnection, DeviceConnectionEvent},
+  error::AppError,
+  linux::{
+    sysfs_path,
+    uevent::{Uevent, UeventSocket},
+  },
+  trigger::TriggerControl,
+};
+use std::{
+  collections::BTreeMap,
+  path::PathBuf,
+  sync::mpsc::{Receiver, TryRecvError},
+  time::Duration,
+};
+use tracing::*;
+
+/// Which uevents a listener wants.  The device type narrows the subsystem
+/// further, such as to whole USB devices rather than each of their interfaces.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinuxSubsystem {
+  pub subsystem: String,
+  pub device_type: Option<String>,
+}
+
+impl LinuxSubsystem {
+  fn matches(&self, uevent: &Uevent) -> bool {
+    uevent.property("SUBSYSTEM") == Some(self.subsystem.as_str())
+      && self
+        .device_type
+        .as_ref()
+        .is_none_or(|t| uevent.property("DEVTYPE") == Some(t.as_str()))
+  }
+}
+
+// The kernel is waited on in slices this long, so a shutdown doesn't have to
+// wait for a device.
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where the device is in sysfs.  Its devpath is relative to the root of
+/// sysfs, though it starts with a '/'.
+fn sysfs_device_path(devpath: &str) -> PathBuf {
+  sysfs_path().join(devpath.trim_start_matches('/'))
+}
+
+fn sysfs_attribute_read(devpath: &str, attribute: &str) -> Option<String> {
+  std::fs::read_to_string(sysfs_device_path(devpath).join(attribute))
+    .ok()
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+}
+
+/// USB devices report "PRODUCT=<vendor>/<product>/<release>" in hex, without
+/// padding.
+fn usb_product_id_format(product: &str, index: usize) -> Option<String> {
+  product
+    .split('/')
+    .nth(index)
+    .and_then(|id| u32::from_str_radix(id, 16).ok())
+    .map(|id| format!("0x{:04x}", id))
+}
+
+/// Extract the device properties we know how to read, keyed by their trigger
+/// event field names.  Attributes only exist in sysfs while the device does,
+/// so this only gets all of them for added devices.
+fn device_properties(uevent: &Uevent) -> BTreeMap<String, String> {
+  let devpath = uevent.property("DEVPATH").unwrap_or_default();
+  let product = uevent.property("PRODUCT").unwrap_or_default();
+  [
+    (
+      "device_name",
+      sysfs_attribute_read(devpath, "product")
+        .or(uevent.property("INTERFACE").map(|s| s.to_string()))
+        .or(devpath.rsplit('/').next().map(|s| s.to_string()))
+        .filter(|s| !s.is_empty()),
+    ),
+    (
+      "device_vendor_id",
+      usb_product_id_format(product, 0).or(
+        sysfs_attribute_read(devpath, "idVendor").map(|s| format!("0x{}", s)),
+      ),
+    ),
+    (
+      "device_product_id",
+      usb_product_id_format(product, 1).or(
+        sysfs_attribute_read(devpath, "idProduct").map(|s| format!("0x{}", s)),
+      ),
+    ),
+    (
+      "device_serial_number",
+      sysfs_attribute_read(devpath, "serial"),
+    ),
+    (
+      "device_devnode",
+      uevent.property("DEVNAME").map(|n| format!("/dev/{}", n)),
+    ),
+    (
+      "device_sys_path",
+      Some(sysfs_device_path(devpath).display().to_string())
+        .filter(|_| !devpath.is_empty()),
+    ),
+  ]
+  .into_iter()
+  .filter_map(|(k, v)| v.map(|v| (k.to_string(), v)))
+  .collect()
+}
+
+/// Listen for kernel uevents in any of the subsystems until the Sytter shuts
+/// down.  The callback gets the subsystem the device matched along with the
+/// device.
+pub fn device_connection_listen(
+  subsystems: &[LinuxSubsystem],
+  mut callback: impl FnMut(&LinuxSubsystem, DeviceConnection),
+  receive_from_sytter: &Receiver<TriggerControl>,
+) -> Result<(), AppError> {
+  let socket = UeventSocket::new()?;
+  // Removed devices are gone from sysfs by the time we hear about it, so what
+  // was read when they were added is kept to fill in their Remove event.
+  let mut known: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+  loop {
+    if let Some(uevent) = socket.uevent_await(DEVICE_POLL_INTERVAL)? {
+      trace!("Got uevent: {:?}", uevent);
+      let matched = subsystems.iter().find(|s| s.matches(&uevent));
+      let event = match uevent.action.as_str() {
+        "add" => Some(DeviceConnectionEvent::Add),
+        "remove" => Some(DeviceConnectionEvent::Remove),
+        _ => None,
+      };
+      if let (Some(subsystem), Some(event)) = (matched, event) {
+        let devpath =
+          uevent.property("DEVPATH").unwrap_or_default().to_string();
+        let mut properties = device_properties(&uevent);
+        match event {
+          DeviceConnectionEvent::Add => {
+            known.insert(devpath, properties.clone());
+          }
+          DeviceConnectionEvent::Remove => {
+            // What was read from sysfs beats the fallbacks used now that
+            // it's gone, such as the kernel's name for the device.
+            properties.extend(known.remove(&devpath).unwrap_or_default());
+          }
+        }
+        callback(subsystem, DeviceConnection { event, properties });
+      }
+    }
+    match receive_from_sytter.try_recv() {
+      Err(TryRecvError::Empty) => (),
+      Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+        debug!("Sytter is closing. Closing the uevent socket...");
+        return Ok(());
+      }
+    }
+  }
+}
@@ -0,0 +1,252 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  contrib::file_watch::{FileWatchEvent, FileWatchTrigger},
+  error::AppError,
+  linux::inotify::{InotifyEvent, InotifyWatcher},
+  trigger::{TriggerControl, TriggerEvent},
+};
+use glob::{MatchOptions, Pattern};
+use std::{
+  collections::BTreeMap,
+  path::{Component, Path, PathBuf},
+  sync::mpsc::{Receiver, SyncSender, TryRecvError},
+  time::{Duration, Instant},
+};
+use tracing::*;
+
+/// The directory to watch for one of the configured paths, and which of the
+/// entries under it count.
+#[derive(Clone, Debug)]
+struct FileWatchSpec {
+  dir: PathBuf,
+  pattern: Option<Pattern>,
+  recursive: bool,
+}
+
+fn glob_component_is(c: &Component) -> bool {
+  c.as_os_str().to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Directories are watched rather than files, so a file that doesn't exist yet
+/// (or gets replaced) is still seen.  Globs are watched from the deepest
+/// directory that has no glob in it.
+fn file_watch_spec(path: &str, recursive: bool) -> FileWatchSpec {
+  // Leading "./"s are dropped so relative paths can be matched the same way
+  // no matter how they were written.
+  let components: Vec<Component> = Path::new(path)
+    .components()
+    .filter(|c| *c != Component::CurDir)
+    .collect();
+  let normalized: PathBuf = components.iter().collect();
+  let dir_of = |p: &Path| match p.as_os_str().is_empty() {
+    true => PathBuf::from("."),
+    false => p.to_path_buf(),
+  };
+  match components.iter().position(glob_component_is) {
+    Some(first_glob) => FileWatchSpec {
+      dir: dir_of(&components[..first_glob].iter().collect::<PathBuf>()),
+      // Already checked when the trigger was deserialized.
+      pattern: Pattern::new(&normalized.to_string_lossy()).ok(),
+      recursive: recursive
+        || path.contains("**")
+        || components.len() - first_glob > 1,
+    },
+    None if normalized.is_dir() => FileWatchSpec {
+      dir: dir_of(&normalized),
+      pattern: None,
+      recursive,
+    },
+    None => FileWatchSpec {
+      dir: dir_of(normalized.parent().unwrap_or(Path::new(""))),
+      pattern: Pattern::new(&Pattern::escape(&normalized.to_string_lossy()))
+        .ok(),
+      recursive: false,
+    },
+  }
+}
+
+impl FileWatchSpec {
+  fn matches(&self, path: &Path) -> bool {
+    let relative = match path.strip_prefix(&self.dir) {
+      Ok(r) if !r.as_os_str().is_empty() => r,
+      _ => return false,
+    };
+    (self.recursive || relative.components().count() == 1)
+      && self.pattern.as_ref().is_none_or(|p| {
+        // Relative paths are watched from ".", which inotify hands back as a
+        // "./" prefix that the pattern doesn't have.
+        p.matches_path_with(
+          path.strip_prefix(".").unwrap_or(path),
+          MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+          },
+        )
+      })
+  }
+}
+
+fn dirs_watch(
+  watcher: &mut InotifyWatcher,
+  dir: &Path,
+  recursive: bool,
+) -> Result<(), AppError> {
+  watcher.dir_watch(dir)?;
+  if recursive {
+    std::fs::read_dir(dir)
+      .map_err(|e| {
+        AppError::TriggerInitializeError(format!(
+          "Could not read directory '{}': {:?}",
+          dir.display(),
+          e,
+        ))
+      })?
+      .flatten()
+      .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+      .try_for_each(|entry| dirs_watch(watcher, &entry.path(), true))?;
+  }
+  Ok(())
+}
+
+#[derive(Debug)]
+struct FileWatchChange {
+  event: FileWatchEvent,
+  path: PathBuf,
+  old_path: Option<PathBuf>,
+  last_seen: Instant,
+}
+
+impl FileWatchChange {
+  fn to_event(&self) -> TriggerEvent {
+    let event = TriggerEvent::new("file-watch")
+      .field("file_watch_event", &self.event)
+      .field("file_watch_path", self.path.display());
+    match &self.old_path {
+      Some(old_path) => event.field("file_watch_old_path", old_path.display()),
+      None => event,
+    }
+  }
+}
+
+// Inotify is waited on in slices this long, so a shutdown doesn't have to wait
+// for a change.
+const FILE_WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn file_watch_listen(
+  trigger: &FileWatchTrigger,
+  send_to_sytter: SyncSender<TriggerEvent>,
+  receive_from_sytter: Receiver<TriggerControl>,
+) -> Result<(), AppError> {
+  let specs: Vec<FileWatchSpec> = trigger
+    .paths
+    .iter()
+    .map(|p| file_watch_spec(p, trigger.recursive))
+    .collect();
+  let mut watcher = InotifyWatcher::new()?;
+  specs
+    .iter()
+    .try_for_each(|s| dirs_watch(&mut watcher, &s.dir, s.recursive))?;
+  info!("Watching {:?} for {:?}.", trigger.paths, trigger.events);
+  let send = |change: &FileWatchChange| {
+    debug!("File watch trigger fired for {:?}.", change);
+    if let Err(e) = send_to_sytter.send(change.to_event()) {
+      debug!("Error triggering Sytter from file watch: {:?}", e);
+    }
+  };
+  // Keyed by path, so each path settles on its own.
+  let mut pending: BTreeMap<PathBuf, FileWatchChange> = BTreeMap::new();
+  loop {
+    let timeout = trigger
+      .settle
+      .and_then(|settle| pending.values().map(|c| c.last_seen + settle).min())
+      .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+      .unwrap_or(FILE_WATCH_POLL_INTERVAL)
+      .min(FILE_WATCH_POLL_INTERVAL);
+    for inotify_event in watcher.events_await(timeout)? {
+      let (event, path, old_path, is_dir) = match inotify_event {
+        InotifyEvent::Overflow => {
+          warn!("Too many file changes at once, some were missed.");
+          continue;
+        }
+        InotifyEvent::DirGone(dir) if specs.iter().any(|s| s.dir == dir) => {
+          return Err(AppError::TriggerRuntimeError(format!(
+            "Watched directory '{}' was deleted or moved.",
+            dir.display(),
+          )));
+        }
+        InotifyEvent::DirGone(_) => continue,
+        InotifyEvent::File {
+          event,
+          path,
+          old_path,
+          is_dir,
+        } => (event, path, old_path, is_dir),
+      };
+      // New directories under a recursive watch need watches of their own.
+      if let Some(dir) = path.as_ref().filter(|_| is_dir) {
+        if specs.iter().any(|s| s.recursive && dir.starts_with(&s.dir)) {
+          dirs_watch(&mut watcher, dir, true).unwrap_or_else(|e| {
+            warn!("Could not watch new directory: {:?}", e)
+          });
+        }
+      }
+      let matched = specs
+        .iter()
+        .any(|s| path.iter().chain(old_path.iter()).any(|p| s.matches(p)));
+      if !matched || !trigger.events.contains(&event) {
+        continue;
+      }
+      // A move out of the watched directories only has an old path, which is
+      // the path that changed as far as the Sytter is concerned.
+      let change = match path.or(old_path.clone()) {
+        Some(path) => FileWatchChange {
+          event,
+          path,
+          old_path,
+          last_seen: Instant::now(),
+        },
+        None => continue,
+      };
+      match trigger.settle {
+        None => send(&change),
+        Some(_) => {
+          let merged = match pending.remove(&change.path) {
+            // A file that is created and then written to is still new.
+            Some(earlier)
+              if earlier.event == FileWatchEvent::Create
+                && change.event == FileWatchEvent::Modify =>
+            {
+              FileWatchChange {
+                last_seen: change.last_seen,
+                ..earlier
+              }
+            }
+            _ => change,
+          };
+          pending.insert(merged.path.clone(), merged);
+        }
+      }
+    }
+    if let Some(settle) = trigger.settle {
+      let now = Instant::now();
+      let settled: Vec<PathBuf> = pending
+        .iter()
+        .filter(|(_, c)| c.last_seen + settle <= now)
+        .map(|(p, _)| p.clone())
+        .collect();
+      settled
+        .iter()
+        .filter_map(|p| pending.remove(p))
+        .for_each(|c| send(&c));
+    }
+    match receive_from_sytter.try_recv() {
+      Err(TryRecvError::Empty) => (),
+      Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+        debug!("File watch trigger for {:?} shutting down.", trigger.paths);
+        // The watches go away along with inotify's file descriptor.
+        return Ok(());
+      }
+    }
+  }
+}
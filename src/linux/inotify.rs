@@ -0,0 +1,168 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  contrib::file_watch::FileWatchEvent, error::AppError,
+  linux::fd_readable_await,
+};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::{
+  collections::HashMap,
+  ffi::OsStr,
+  io::ErrorKind,
+  os::fd::AsRawFd,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+use tracing::*;
+
+/// Something that happened in a watched directory.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InotifyEvent {
+  File {
+    event: FileWatchEvent,
+    // None for the side of a move that happened outside of any watched
+    // directory.
+    path: Option<PathBuf>,
+    old_path: Option<PathBuf>,
+    is_dir: bool,
+  },
+  /// A watched directory was deleted or moved away.  Its watch is gone.
+  DirGone(PathBuf),
+  /// The kernel's queue filled up, so events were lost.
+  Overflow,
+}
+
+/// Watches directories (not files, since a file can be replaced out from under
+/// its watch) and reports changes to their entries.
+pub struct InotifyWatcher {
+  inotify: Inotify,
+  dirs: HashMap<WatchDescriptor, PathBuf>,
+  buffer: Vec<u8>,
+}
+
+fn inotify_dir_mask() -> WatchMask {
+  WatchMask::CREATE
+    | WatchMask::MODIFY
+    | WatchMask::DELETE
+    | WatchMask::MOVED_FROM
+    | WatchMask::MOVED_TO
+    | WatchMask::DELETE_SELF
+    | WatchMask::MOVE_SELF
+    | WatchMask::ONLYDIR
+}
+
+impl InotifyWatcher {
+  pub fn new() -> Result<Self, AppError> {
+    Ok(InotifyWatcher {
+      inotify: Inotify::init().map_err(|e| {
+        AppError::TriggerInitializeError(format!(
+          "Could not initialize inotify: {:?}",
+          e,
+        ))
+      })?,
+      dirs: HashMap::new(),
+      buffer: vec![0; 4096],
+    })
+  }
+
+  pub fn dir_watch(&mut self, dir: &Path) -> Result<(), AppError> {
+    let wd = self
+      .inotify
+      .watches()
+      .add(dir, inotify_dir_mask())
+      .map_err(|e| {
+        AppError::TriggerInitializeError(format!(
+          "Could not watch '{}': {:?}",
+          dir.display(),
+          e,
+        ))
+      })?;
+    trace!("Watching directory '{}'.", dir.display());
+    self.dirs.insert(wd, dir.to_path_buf());
+    Ok(())
+  }
+
+  fn entry_path(
+    &self,
+    wd: &WatchDescriptor,
+    name: Option<&OsStr>,
+  ) -> Option<PathBuf> {
+    self
+      .dirs
+      .get(wd)
+      .map(|dir| name.map(|n| dir.join(n)).unwrap_or(dir.clone()))
+  }
+
+  /// Wait up to `timeout` for something to happen.  Returns nothing if the
+  /// time ran out first.
+  pub fn events_await(
+    &mut self,
+    timeout: Duration,
+  ) -> Result<Vec<InotifyEvent>, AppError> {
+    if !fd_readable_await(self.inotify.as_raw_fd(), timeout) {
+      return Ok(vec![]);
+    }
+    let raw = match self.inotify.read_events(&mut self.buffer) {
+      Ok(events) => events
+        .map(|e| (e.wd, e.mask, e.cookie, e.name.map(|n| n.to_os_string())))
+        .collect::<Vec<_>>(),
+      Err(e) if e.kind() == ErrorKind::WouldBlock => vec![],
+      Err(e) => {
+        return Err(AppError::TriggerRuntimeError(format!(
+          "Could not read inotify events: {:?}",
+          e,
+        )))
+      }
+    };
+    // Both halves of a move share a cookie, and the kernel sends them one
+    // after the other.
+    let mut moved_from: HashMap<u32, (PathBuf, bool)> = HashMap::new();
+    let mut events = vec![];
+    for (wd, mask, cookie, name) in raw {
+      let path = self.entry_path(&wd, name.as_deref());
+      let is_dir = mask.contains(EventMask::ISDIR);
+      if mask.contains(EventMask::Q_OVERFLOW) {
+        events.push(InotifyEvent::Overflow);
+      } else if mask.contains(EventMask::IGNORED) {
+        self.dirs.remove(&wd);
+      } else if mask.intersects(EventMask::DELETE_SELF | EventMask::MOVE_SELF) {
+        events.extend(path.map(InotifyEvent::DirGone));
+      } else if mask.contains(EventMask::MOVED_FROM) {
+        if let Some(p) = path {
+          moved_from.insert(cookie, (p, is_dir));
+        }
+      } else if mask.contains(EventMask::MOVED_TO) {
+        events.push(InotifyEvent::File {
+          event: FileWatchEvent::Move,
+          path,
+          old_path: moved_from.remove(&cookie).map(|(p, _)| p),
+          is_dir,
+        });
+      } else {
+        let event = if mask.contains(EventMask::CREATE) {
+          FileWatchEvent::Create
+        } else if mask.contains(EventMask::MODIFY) {
+          FileWatchEvent::Modify
+        } else {
+          FileWatchEvent::Delete
+        };
+        events.push(InotifyEvent::File {
+          event,
+          path,
+          old_path: None,
+          is_dir,
+        });
+      }
+    }
+    // Whatever is left was moved somewhere we aren't watching.
+    events.extend(moved_from.into_values().map(|(old_path, is_dir)| {
+      InotifyEvent::File {
+        event: FileWatchEvent::Move,
+        path: None,
+        old_path: Some(old_path),
+        is_dir,
+      }
+    }));
+    Ok(events)
+  }
+}
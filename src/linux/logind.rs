@@ -0,0 +1,146 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  contrib::power::PowerEvent,
+  error::AppError,
+  trigger::{trigger_shutdown_await, TriggerControl, TriggerEvent},
+};
+use futures::{
+  channel::oneshot,
+  future::{select, Either},
+  StreamExt,
+};
+use std::sync::mpsc::{Receiver, SyncSender};
+use tracing::*;
+use zbus::{zvariant::OwnedFd, Connection, Proxy};
+
+/**
+ * See https://www.freedesktop.org/software/systemd/man/latest/org.freedesktop.login1.html
+ * and https://systemd.io/INHIBITOR_LOCKS/ for how sleep is announced, and how
+ * it can be held off.
+ */
+const LOGIND_DESTINATION: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+
+fn logind_error(context: &str, e: zbus::Error) -> AppError {
+  AppError::TriggerInitializeError(format!("{}: {:?}", context, e))
+}
+
+/// A delay lock holds off sleep until it is closed (or logind's
+/// InhibitDelayMaxSec runs out).  Failing to get one isn't fatal, it just means
+/// sleep won't wait on the run.
+async fn sleep_delay_inhibit(manager: &Proxy<'_>) -> Option<OwnedFd> {
+  manager
+    .call(
+      "Inhibit",
+      &("sleep", "sytter", "Running Sytters before sleep", "delay"),
+    )
+    .await
+    .inspect(|_| trace!("Took a sleep delay lock."))
+    .inspect_err(|e| {
+      warn!(
+        "Could not take a sleep delay lock, sleep won't wait: {:?}",
+        e
+      )
+    })
+    .ok()
+}
+
+async fn sleep_listen_async(
+  events: &[PowerEvent],
+  send_to_sytter: SyncSender<TriggerEvent>,
+  mut shutdown: oneshot::Receiver<()>,
+) -> Result<(), AppError> {
+  // Honors DBUS_SYSTEM_BUS_ADDRESS, which is how tests point this elsewhere.
+  let connection = Connection::system()
+    .await
+    .map_err(|e| logind_error("Could not connect to the system bus", e))?;
+  let manager = Proxy::new(
+    &connection,
+    LOGIND_DESTINATION,
+    LOGIND_PATH,
+    LOGIND_MANAGER_INTERFACE,
+  )
+  .await
+  .map_err(|e| logind_error("Could not reach logind", e))?;
+  // Subscribe before taking the lock, so no sleep can slip in between.
+  let mut signals = manager
+    .receive_signal("PrepareForSleep")
+    .await
+    .map_err(|e| logind_error("Could not listen for PrepareForSleep", e))?;
+  let sleep_wanted = events.contains(&PowerEvent::Sleep);
+  let mut lock = match sleep_wanted {
+    true => sleep_delay_inhibit(&manager).await,
+    false => None,
+  };
+  info!("Listening for power events {:?} from logind.", events);
+  loop {
+    let message = match select(signals.next(), &mut shutdown).await {
+      Either::Left((Some(message), _)) => message,
+      Either::Left((None, _)) => {
+        return Err(AppError::TriggerRuntimeError(
+          "Lost the connection to logind.".to_string(),
+        ))
+      }
+      Either::Right(_) => {
+        debug!("Sytter is closing. Releasing the sleep delay lock...");
+        drop(lock);
+        return Ok(());
+      }
+    };
+    let sleeping: bool = message.body().deserialize().map_err(|e| {
+      AppError::TriggerRuntimeError(format!(
+        "PrepareForSleep had an unexpected body: {:?}",
+        e,
+      ))
+    })?;
+    let power_event = match sleeping {
+      true => PowerEvent::Sleep,
+      false => PowerEvent::Wake,
+    };
+    debug!("Got logind power event {:?}.", power_event);
+    if !sleeping && sleep_wanted {
+      // The last lock went with the Sleep event, so the next sleep needs a new
+      // one.
+      lock = sleep_delay_inhibit(&manager).await;
+    }
+    if events.contains(&power_event) {
+      let event = TriggerEvent::new("power").field("power_event", &power_event);
+      // Sleep waits until the run lets go of the lock.
+      let event = match sleeping.then(|| lock.take()).flatten() {
+        Some(held) => event.hold(held),
+        None => event,
+      };
+      match send_to_sytter.send(event) {
+        Ok(_) => trace!("Signal to sytter from PowerTrigger successful!"),
+        Err(e) => trace!("Error triggering sytter from PowerTrigger: {:?}", e),
+      }
+    } else {
+      trace!(
+        "Power event {:?} skipped because it is not in {:?}",
+        power_event,
+        events,
+      );
+    }
+  }
+}
+
+/// Listen for logind's PrepareForSleep until the Sytter shuts down.
+pub fn sleep_listen(
+  events: &[PowerEvent],
+  send_to_sytter: SyncSender<TriggerEvent>,
+  receive_from_sytter: Receiver<TriggerControl>,
+) -> Result<(), AppError> {
+  let (send_shutdown, receive_shutdown) = oneshot::channel();
+  // The control channel can't be awaited, so a thread waits on it instead.
+  std::thread::spawn(move || {
+    trigger_shutdown_await(&receive_from_sytter);
+    let _ = send_shutdown.send(());
+  });
+  futures::executor::block_on(sleep_listen_async(
+    events,
+    send_to_sytter,
+    receive_shutdown,
+  ))
+}
@@ -0,0 +1,136 @@
+use crate::error::AppError;
+use std::{
+  io,
+  os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+  path::PathBuf,
+  time::Duration,
+};
+use tracing::*;
+
+pub mod device;
+pub mod file_watch;
+pub mod inotify;
+pub mod logind;
+pub mod network;
+pub mod power_supply;
+pub mod process;
+pub mod resource;
+pub mod rtnetlink;
+pub mod uevent;
+
+/// Where sysfs is mounted.  SYSFS_PATH moves it elsewhere, as it does for udev,
+/// which is how tests stand in for hardware they don't have.
+pub fn sysfs_path() -> PathBuf {
+  std::env::var_os("SYSFS_PATH")
+    .map(PathBuf::from)
+    .unwrap_or(PathBuf::from("/sys"))
+}
+
+/// Wait up to `timeout` for `fd` to have something to read.  Listeners wait in
+/// short slices like this so they can still notice a shutdown.
+pub fn fd_readable_await(fd: RawFd, timeout: Duration) -> bool {
+  let mut poll_fd = libc::pollfd {
+    fd,
+    events: libc::POLLIN,
+    revents: 0,
+  };
+  // Safe because poll_fd outlives the call and we only pass one.
+  let ready = unsafe {
+    libc::poll(
+      &mut poll_fd,
+      1,
+      timeout.as_millis().min(i32::MAX as u128) as i32,
+    )
+  };
+  ready > 0
+}
+
+/// Open a netlink socket for `protocol`, joined to the multicast `groups`.
+pub fn netlink_socket(
+  protocol: libc::c_int,
+  groups: u32,
+) -> Result<OwnedFd, io::Error> {
+  // Safe because we check the result before taking ownership of it.
+  let raw = unsafe {
+    libc::socket(
+      libc::AF_NETLINK,
+      libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+      protocol,
+    )
+  };
+  if raw < 0 {
+    return Err(io::Error::last_os_error());
+  }
+  let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+  // Safe because an all-zero sockaddr_nl is valid, and it outlives the call.
+  let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+  address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+  address.nl_groups = groups;
+  let bound = unsafe {
+    libc::bind(
+      fd.as_raw_fd(),
+      &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+      std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+    )
+  };
+  match bound < 0 {
+    true => Err(io::Error::last_os_error()),
+    false => Ok(fd),
+  }
+}
+
+/// Send a request to the kernel on a netlink socket.
+pub fn netlink_send(fd: &OwnedFd, message: &[u8]) -> Result<(), io::Error> {
+  // Safe because the message outlives the call and its length is passed.
+  let sent = unsafe {
+    libc::send(
+      fd.as_raw_fd(),
+      message.as_ptr() as *const libc::c_void,
+      message.len(),
+      0,
+    )
+  };
+  match sent < 0 {
+    true => Err(io::Error::last_os_error()),
+    false => Ok(()),
+  }
+}
+
+/// Wait up to `timeout` for a message on a netlink socket, and read it into
+/// `buffer`.  Gives the length read, if anything was.  `what` names the
+/// messages, for errors.
+pub fn netlink_receive(
+  fd: &OwnedFd,
+  buffer: &mut [u8],
+  timeout: Duration,
+  what: &str,
+) -> Result<Option<usize>, AppError> {
+  if !fd_readable_await(fd.as_raw_fd(), timeout) {
+    return Ok(None);
+  }
+  // Safe because the buffer outlives the call and its length is passed.
+  let read = unsafe {
+    libc::recv(
+      fd.as_raw_fd(),
+      buffer.as_mut_ptr() as *mut libc::c_void,
+      buffer.len(),
+      libc::MSG_DONTWAIT,
+    )
+  };
+  match read {
+    n if n >= 0 => Ok(Some(n as usize)),
+    _ => match io::Error::last_os_error() {
+      e if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+      // The kernel drops messages when we fall behind.  The ones after that
+      // still come through.
+      e if e.raw_os_error() == Some(libc::ENOBUFS) => {
+        warn!("Too many {} at once, some were missed.", what);
+        Ok(None)
+      }
+      e => Err(AppError::TriggerRuntimeError(format!(
+        "Could not read {}: {:?}",
+        what, e,
+      ))),
+    },
+  }
+}
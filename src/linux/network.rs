@@ -0,0 +1,267 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  contrib::network::NetworkEvent,
+  error::AppError,
+  linux::rtnetlink::{rtnetlink_current, RtnetlinkMessage, RtnetlinkSocket},
+  trigger::TriggerControl,
+};
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  net::IpAddr,
+  sync::mpsc::{Receiver, TryRecvError},
+  time::Duration,
+};
+use tracing::*;
+
+/// A network event along with what changed.  Property keys become trigger
+/// event fields, such as "network_address".
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkChange {
+  pub event: NetworkEvent,
+  pub interface: Option<String>,
+  pub properties: BTreeMap<String, String>,
+}
+
+// The kernel is waited on in slices this long, so a shutdown doesn't have to
+// wait for the network to change.
+const NETWORK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Address {
+  index: u32,
+  address: IpAddr,
+  prefix_length: u8,
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct DefaultRoute {
+  ipv6: bool,
+  index: Option<u32>,
+  gateway: Option<IpAddr>,
+}
+
+/**
+ * What the network looks like, so changes are only reported when something
+ * actually changed.  The kernel announces links for all sorts of reasons (such
+ * as a changed MTU), and the same thing can arrive twice when a change races
+ * the initial listing.
+ */
+#[derive(Default)]
+struct NetworkState {
+  // Names and whether they're up, by index.
+  links: BTreeMap<u32, (Option<String>, bool)>,
+  addresses: BTreeSet<Address>,
+  default_routes: BTreeSet<DefaultRoute>,
+}
+
+fn family(ipv6: bool) -> String {
+  match ipv6 {
+    true => "ipv6".to_string(),
+    false => "ipv4".to_string(),
+  }
+}
+
+impl NetworkState {
+  fn interface(&self, index: Option<u32>) -> Option<String> {
+    index
+      .and_then(|i| self.links.get(&i))
+      .and_then(|(name, _)| name.clone())
+  }
+
+  fn change(
+    &self,
+    event: NetworkEvent,
+    index: Option<u32>,
+    properties: Vec<(&str, Option<String>)>,
+  ) -> NetworkChange {
+    let interface = self.interface(index);
+    NetworkChange {
+      event,
+      interface: interface.clone(),
+      properties: [("network_interface", interface)]
+        .into_iter()
+        .chain(properties)
+        .filter_map(|(k, v)| v.map(|v| (k.to_string(), v)))
+        .collect(),
+    }
+  }
+
+  fn address_change(
+    &self,
+    event: NetworkEvent,
+    address: &Address,
+  ) -> NetworkChange {
+    self.change(
+      event,
+      Some(address.index),
+      vec![
+        ("network_address", Some(address.address.to_string())),
+        (
+          "network_prefix_length",
+          Some(address.prefix_length.to_string()),
+        ),
+        ("network_family", Some(family(address.address.is_ipv6()))),
+      ],
+    )
+  }
+
+  fn default_route_change(
+    &self,
+    event: NetworkEvent,
+    route: &DefaultRoute,
+  ) -> NetworkChange {
+    self.change(
+      event,
+      route.index,
+      vec![
+        ("network_gateway", route.gateway.map(|g| g.to_string())),
+        ("network_family", Some(family(route.ipv6))),
+      ],
+    )
+  }
+
+  /// Forget the default routes through a link.  The kernel drops IPv4 routes
+  /// when their link goes down or away without saying so.
+  fn default_routes_remove(&mut self, index: u32) -> Vec<NetworkChange> {
+    let (gone, kept) =
+      std::mem::take(&mut self.default_routes)
+        .into_iter()
+        .partition::<BTreeSet<DefaultRoute>, _>(|r| r.index == Some(index));
+    self.default_routes = kept;
+    gone
+      .iter()
+      .map(|r| self.default_route_change(NetworkEvent::DefaultRouteRemove, r))
+      .collect()
+  }
+
+  /// Bring the state up to date with a message, giving what changed.
+  fn apply(&mut self, message: RtnetlinkMessage) -> Vec<NetworkChange> {
+    let mut changes = vec![];
+    match message {
+      RtnetlinkMessage::Link {
+        index,
+        name,
+        up,
+        removed: false,
+      } => {
+        let (previous_name, was_up) =
+          self.links.get(&index).cloned().unwrap_or((None, false));
+        self.links.insert(index, (name.or(previous_name), up));
+        if up && !was_up {
+          changes.push(self.change(NetworkEvent::LinkUp, Some(index), vec![]));
+        }
+        if !up && was_up {
+          changes.push(self.change(
+            NetworkEvent::LinkDown,
+            Some(index),
+            vec![],
+          ));
+          changes.extend(self.default_routes_remove(index));
+        }
+      }
+      RtnetlinkMessage::Link {
+        index,
+        removed: true,
+        ..
+      } => {
+        if self.links.get(&index).is_some_and(|(_, up)| *up) {
+          changes.push(self.change(
+            NetworkEvent::LinkDown,
+            Some(index),
+            vec![],
+          ));
+        }
+        // Anything still on the link went with it.
+        let (gone, kept) =
+          std::mem::take(&mut self.addresses)
+            .into_iter()
+            .partition::<BTreeSet<Address>, _>(|a| a.index == index);
+        self.addresses = kept;
+        changes.extend(
+          gone
+            .iter()
+            .map(|a| self.address_change(NetworkEvent::AddressRemove, a)),
+        );
+        changes.extend(self.default_routes_remove(index));
+        self.links.remove(&index);
+      }
+      RtnetlinkMessage::Address {
+        index,
+        address,
+        prefix_length,
+        removed,
+      } => {
+        let address = Address {
+          index,
+          address,
+          prefix_length,
+        };
+        if !removed && self.addresses.insert(address.clone()) {
+          changes.push(self.address_change(NetworkEvent::AddressAdd, &address));
+        }
+        if removed && self.addresses.remove(&address) {
+          changes
+            .push(self.address_change(NetworkEvent::AddressRemove, &address));
+        }
+      }
+      RtnetlinkMessage::DefaultRoute {
+        ipv6,
+        index,
+        gateway,
+        removed,
+      } => {
+        let route = DefaultRoute {
+          ipv6,
+          index,
+          gateway,
+        };
+        if !removed && self.default_routes.insert(route.clone()) {
+          changes.push(
+            self.default_route_change(NetworkEvent::DefaultRouteAdd, &route),
+          );
+        }
+        if removed && self.default_routes.remove(&route) {
+          changes.push(
+            self.default_route_change(NetworkEvent::DefaultRouteRemove, &route),
+          );
+        }
+      }
+      RtnetlinkMessage::Done | RtnetlinkMessage::Error(_) => (),
+    }
+    changes
+  }
+}
+
+/// Listen for changes to the network until the Sytter shuts down.  Only
+/// changes after this starts are given, not what was already there.
+pub fn network_change_listen(
+  mut callback: impl FnMut(NetworkChange),
+  receive_from_sytter: &Receiver<TriggerControl>,
+) -> Result<(), AppError> {
+  // Listen before looking, so nothing can change in between unnoticed.
+  let socket = RtnetlinkSocket::new()?;
+  let mut state = NetworkState::default();
+  for message in rtnetlink_current()? {
+    state.apply(message);
+  }
+  debug!(
+    "Network has {} links, {} addresses, and {} default routes.",
+    state.links.len(),
+    state.addresses.len(),
+    state.default_routes.len(),
+  );
+  loop {
+    for message in socket.messages_await(NETWORK_POLL_INTERVAL)? {
+      trace!("Got rtnetlink message: {:?}", message);
+      state.apply(message).into_iter().for_each(&mut callback);
+    }
+    match receive_from_sytter.try_recv() {
+      Err(TryRecvError::Empty) => (),
+      Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+        debug!("Sytter is closing. Closing the rtnetlink socket...");
+        return Ok(());
+      }
+    }
+  }
+}
@@ -0,0 +1,166 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  contrib::power::PowerSupply,
+  error::AppError,
+  linux::{sysfs_path, uevent::UeventSocket},
+  trigger::TriggerControl,
+};
+use std::{
+  path::{Path, PathBuf},
+  sync::mpsc::{Receiver, TryRecvError},
+  time::{Duration, Instant},
+};
+use tracing::*;
+
+/**
+ * Every AC adapter, USB-C port, and battery the kernel knows about is a
+ * directory in /sys/class/power_supply.  See
+ * https://www.kernel.org/doc/html/latest/power/power_supply_class.html for the
+ * attributes.  Energy is in µWh and power in µW, or for batteries that only
+ * count charge, µAh and µA.
+ */
+// The kernel is waited on in slices this long, so a shutdown doesn't have to
+// wait for the power supply to change.
+const POWER_SUPPLY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn power_supply_path() -> PathBuf {
+  sysfs_path().join("class/power_supply")
+}
+
+fn power_supply_attribute(dir: &Path, attribute: &str) -> Option<String> {
+  std::fs::read_to_string(dir.join(attribute))
+    .ok()
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+}
+
+fn power_supply_number(dir: &Path, attribute: &str) -> Option<f64> {
+  power_supply_attribute(dir, attribute).and_then(|s| s.parse::<f64>().ok())
+}
+
+/// What is left and how fast it's going, in either energy or charge, for
+/// working out how long the batteries have.
+fn battery_remaining(dir: &Path) -> Option<(f64, f64)> {
+  power_supply_number(dir, "energy_now")
+    .zip(power_supply_number(dir, "power_now"))
+    .or(
+      power_supply_number(dir, "charge_now")
+        .zip(power_supply_number(dir, "current_now")),
+    )
+    .map(|(left, rate)| (left, rate.abs()))
+}
+
+/// Read what the AC adapter and batteries are doing now.  Batteries in
+/// peripherals, such as a wireless mouse, are left out.
+pub fn power_supply_read() -> PowerSupply {
+  let mut dirs: Vec<PathBuf> = std::fs::read_dir(power_supply_path())
+    .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+    .unwrap_or_default();
+  dirs.sort();
+  let (batteries, adapters): (Vec<PathBuf>, Vec<PathBuf>) = dirs
+    .into_iter()
+    .filter(|d| power_supply_attribute(d, "scope").as_deref() != Some("Device"))
+    .partition(|d| {
+      power_supply_attribute(d, "type").as_deref() == Some("Battery")
+    });
+  let batteries: Vec<PathBuf> = batteries
+    .into_iter()
+    .filter(|d| power_supply_attribute(d, "present").as_deref() != Some("0"))
+    .collect();
+  let onlines: Vec<bool> = adapters
+    .iter()
+    .filter_map(|d| power_supply_attribute(d, "online"))
+    .map(|online| online == "1")
+    .collect();
+  // Weighed by how much each battery holds, when they all say.
+  let energies: Option<Vec<(f64, f64)>> = batteries
+    .iter()
+    .map(|d| {
+      power_supply_number(d, "energy_now")
+        .zip(power_supply_number(d, "energy_full"))
+    })
+    .collect();
+  let capacities: Vec<f64> = batteries
+    .iter()
+    .filter_map(|d| power_supply_number(d, "capacity"))
+    .collect();
+  let battery_percent = match energies {
+    Some(energies) if energies.iter().any(|(_, full)| *full > 0.0) => Some(
+      100.0 * energies.iter().map(|(now, _)| now).sum::<f64>()
+        / energies.iter().map(|(_, full)| full).sum::<f64>(),
+    ),
+    _ if !capacities.is_empty() => {
+      Some(capacities.iter().sum::<f64>() / capacities.len() as f64)
+    }
+    _ => None,
+  }
+  .map(|p| p.round().clamp(0.0, 100.0) as u8);
+  let statuses: Vec<String> = batteries
+    .iter()
+    .filter_map(|d| power_supply_attribute(d, "status"))
+    .collect();
+  // One battery charging or draining is what the machine as a whole is doing.
+  let battery_status = ["Charging", "Discharging"]
+    .into_iter()
+    .find(|s| statuses.iter().any(|status| status == s))
+    .map(|s| s.to_string())
+    .or(statuses.first().cloned());
+  let battery_time_to_empty = match battery_status.as_deref() {
+    Some("Discharging") => batteries
+      .iter()
+      .map(|d| battery_remaining(d))
+      .collect::<Option<Vec<(f64, f64)>>>()
+      .map(|r| {
+        (
+          r.iter().map(|(left, _)| left).sum::<f64>(),
+          r.iter().map(|(_, rate)| rate).sum::<f64>(),
+        )
+      })
+      .filter(|(_, rate)| *rate > 0.0)
+      .map(|(left, rate)| Duration::from_secs_f64(left / rate * 3600.0))
+      .or(match batteries.as_slice() {
+        [battery] => power_supply_number(battery, "time_to_empty_now")
+          .map(Duration::from_secs_f64),
+        _ => None,
+      }),
+    _ => None,
+  };
+  PowerSupply {
+    ac_online: (!onlines.is_empty()).then(|| onlines.contains(&true)),
+    battery_percent,
+    battery_status,
+    battery_time_to_empty,
+  }
+}
+
+/// Read the power supply whenever the kernel says it changed, and every
+/// `interval` besides, until the Sytter shuts down.  The callback gets every
+/// reading, starting with one right away.
+pub fn power_supply_listen(
+  interval: Duration,
+  mut callback: impl FnMut(PowerSupply),
+  receive_from_sytter: &Receiver<TriggerControl>,
+) -> Result<(), AppError> {
+  let socket = UeventSocket::new()?;
+  let mut read_at = Instant::now();
+  loop {
+    let announced = socket
+      .uevent_await(POWER_SUPPLY_POLL_INTERVAL)?
+      .inspect(|uevent| trace!("Got uevent: {:?}", uevent))
+      .is_some_and(|uevent| {
+        uevent.property("SUBSYSTEM") == Some("power_supply")
+      });
+    if announced || Instant::now() >= read_at {
+      read_at = Instant::now() + interval;
+      callback(power_supply_read());
+    }
+    match receive_from_sytter.try_recv() {
+      Err(TryRecvError::Empty) => (),
+      Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+        debug!("Sytter is closing. No longer reading the power supply.");
+        return Ok(());
+      }
+    }
+  }
+}
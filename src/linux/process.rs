@@ -0,0 +1,271 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  contrib::process::{
+    process_event_send, process_name_full, ProcessEvent, ProcessInfo,
+    ProcessMatcher, ProcessTrigger,
+  },
+  error::AppError,
+  linux::{netlink_receive, netlink_send, netlink_socket},
+  trigger::{TriggerControl, TriggerEvent},
+};
+use std::{
+  collections::BTreeMap,
+  ffi::CStr,
+  io,
+  os::fd::OwnedFd,
+  path::Path,
+  sync::mpsc::{Receiver, SyncSender, TryRecvError},
+  time::Duration,
+};
+use tracing::*;
+
+// The kernel cuts comm off at this many characters.
+const COMM_MAX_LEN: usize = 15;
+
+fn user_name(uid: u32) -> Option<String> {
+  let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+  let mut buffer = vec![0 as libc::c_char; 4096];
+  let mut result: *mut libc::passwd = std::ptr::null_mut();
+  // Safe because every pointer outlives the call, and the buffer's length is
+  // passed along with it.
+  let status = unsafe {
+    libc::getpwuid_r(
+      uid,
+      &mut passwd,
+      buffer.as_mut_ptr(),
+      buffer.len(),
+      &mut result,
+    )
+  };
+  match status == 0 && !result.is_null() {
+    true => Some(
+      unsafe { CStr::from_ptr(passwd.pw_name) }
+        .to_string_lossy()
+        .to_string(),
+    ),
+    false => None,
+  }
+}
+
+/// What /proc knows about a process.  None if it's gone, or is a kernel
+/// thread (which has no command line).
+pub fn process_info(pid: u32) -> Option<ProcessInfo> {
+  let dir = Path::new("/proc").join(pid.to_string());
+  let cmdline: Vec<String> = std::fs::read(dir.join("cmdline"))
+    .ok()?
+    .split(|b| *b == 0)
+    .filter(|arg| !arg.is_empty())
+    .map(|arg| String::from_utf8_lossy(arg).to_string())
+    .collect();
+  if cmdline.is_empty() {
+    return None;
+  }
+  let comm = std::fs::read_to_string(dir.join("comm")).ok()?;
+  let comm = comm.trim_end_matches('\n');
+  let args: Vec<&str> = cmdline.iter().map(|arg| arg.as_str()).collect();
+  let name = process_name_full(comm, COMM_MAX_LEN, &args);
+  let uid = std::fs::read_to_string(dir.join("status"))
+    .ok()?
+    .lines()
+    .find_map(|l| l.strip_prefix("Uid:"))
+    .and_then(|ids| ids.split_whitespace().next())
+    .and_then(|uid| uid.parse::<u32>().ok())?;
+  Some(ProcessInfo {
+    pid,
+    name,
+    cmdline: cmdline.join(" "),
+    uid,
+    user: user_name(uid).unwrap_or(uid.to_string()),
+  })
+}
+
+pub fn process_list() -> Result<BTreeMap<u32, ProcessInfo>, AppError> {
+  Ok(
+    std::fs::read_dir("/proc")
+      .map_err(|e| {
+        AppError::TriggerRuntimeError(format!(
+          "Could not list processes: {:?}",
+          e,
+        ))
+      })?
+      .flatten()
+      .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+      .filter_map(|pid| process_info(pid).map(|info| (pid, info)))
+      .collect(),
+  )
+}
+
+/**
+ * The proc connector has the kernel tell us about every fork, exec, and exit
+ * as it happens, including exit codes, which polling /proc can't see.  It
+ * needs CAP_NET_ADMIN.  See include/uapi/linux/cn_proc.h and connector.h in
+ * the kernel for the message layouts used here.
+ */
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_EXEC: u32 = 0x00000002;
+const PROC_EVENT_EXIT: u32 = 0x80000000;
+const NLMSGHDR_LEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+// what, cpu, and timestamp_ns come before the event's own data.
+const PROC_EVENT_HEADER_LEN: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcConnectorEvent {
+  // A process started running a program (an exec).  Forks are left out, since
+  // a fork is still the same program until it execs, which would make every
+  // command a matching shell runs look like the shell starting and exiting.
+  Started(u32),
+  // The raw wait status, as waitpid would give it.
+  Exited(u32, i32),
+}
+
+pub struct ProcConnector {
+  fd: OwnedFd,
+}
+
+fn u32_at(message: &[u8], offset: usize) -> Option<u32> {
+  message
+    .get(offset..offset + 4)
+    .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+impl ProcConnector {
+  pub fn new() -> Result<Self, AppError> {
+    let init_error = |e: io::Error| {
+      AppError::TriggerInitializeError(format!(
+        "Could not listen to the proc connector: {:?}",
+        e,
+      ))
+    };
+    let fd = netlink_socket(libc::NETLINK_CONNECTOR, CN_IDX_PROC)
+      .map_err(init_error)?;
+    // Ask for events with an nlmsghdr, then a cn_msg, then the operation.
+    let operation = PROC_CN_MCAST_LISTEN.to_ne_bytes();
+    let length = NLMSGHDR_LEN + CN_MSG_LEN + operation.len();
+    let message: Vec<u8> = [
+      &(length as u32).to_ne_bytes()[..],
+      &(libc::NLMSG_DONE as u16).to_ne_bytes(),
+      &0_u16.to_ne_bytes(),
+      &0_u32.to_ne_bytes(),
+      &std::process::id().to_ne_bytes(),
+      &CN_IDX_PROC.to_ne_bytes(),
+      &CN_VAL_PROC.to_ne_bytes(),
+      &0_u32.to_ne_bytes(),
+      &0_u32.to_ne_bytes(),
+      &(operation.len() as u16).to_ne_bytes(),
+      &0_u16.to_ne_bytes(),
+      &operation,
+    ]
+    .concat();
+    netlink_send(&fd, &message).map_err(init_error)?;
+    Ok(ProcConnector { fd })
+  }
+
+  /// Wait up to `timeout` for the next process to start or exit.
+  pub fn event_await(
+    &self,
+    timeout: Duration,
+  ) -> Result<Option<ProcConnectorEvent>, AppError> {
+    let mut buffer = [0_u8; 1024];
+    let Some(read) =
+      netlink_receive(&self.fd, &mut buffer, timeout, "process events")?
+    else {
+      return Ok(None);
+    };
+    let message = &buffer[..read];
+    let event_start = NLMSGHDR_LEN + CN_MSG_LEN;
+    let data = event_start + PROC_EVENT_HEADER_LEN;
+    Ok(match u32_at(message, event_start) {
+      Some(PROC_EVENT_EXEC) => {
+        u32_at(message, data).map(ProcConnectorEvent::Started)
+      }
+      // Every thread reports its exit, but only the last one is the process.
+      Some(PROC_EVENT_EXIT) => {
+        match (
+          u32_at(message, data),
+          u32_at(message, data + 4),
+          u32_at(message, data + 8),
+        ) {
+          (Some(pid), Some(tgid), Some(status)) if pid == tgid => {
+            Some(ProcConnectorEvent::Exited(pid, status as i32))
+          }
+          _ => None,
+        }
+      }
+      _ => None,
+    })
+  }
+}
+
+// The kernel is waited on in slices this long, so a shutdown doesn't have to
+// wait for a process.
+const PROCESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Watch processes start and exit through the proc connector until the Sytter
+/// shuts down.  Fails with a TriggerInitializeError when the connector isn't
+/// available to us, so the caller can fall back to polling.
+pub fn process_connector_watch(
+  trigger: &ProcessTrigger,
+  matcher: &ProcessMatcher,
+  send_to_sytter: &SyncSender<TriggerEvent>,
+  receive_from_sytter: &Receiver<TriggerControl>,
+) -> Result<(), AppError> {
+  let connector = ProcConnector::new()?;
+  // Subscribe before listing, so nothing started in between is missed.
+  let mut known = matcher.matching(process_list()?);
+  debug!("Listening to the proc connector, matching {:?}.", known);
+  loop {
+    match connector.event_await(PROCESS_POLL_INTERVAL)? {
+      Some(ProcConnectorEvent::Started(pid)) => {
+        match process_info(pid).filter(|info| matcher.matches(info)) {
+          Some(info) => {
+            if known.insert(pid, info.clone()).is_none() {
+              process_event_send(
+                send_to_sytter,
+                &trigger.events,
+                ProcessEvent::Start,
+                &info,
+                None,
+              );
+            }
+          }
+          // A process we knew about has exec'd into something else, so the one
+          // we were watching is gone even though its pid lives on.
+          None => {
+            if let Some(info) = known.remove(&pid) {
+              process_event_send(
+                send_to_sytter,
+                &trigger.events,
+                ProcessEvent::Exit,
+                &info,
+                None,
+              );
+            }
+          }
+        }
+      }
+      Some(ProcConnectorEvent::Exited(pid, status)) => {
+        if let Some(info) = known.remove(&pid) {
+          process_event_send(
+            send_to_sytter,
+            &trigger.events,
+            ProcessEvent::Exit,
+            &info,
+            Some(status),
+          );
+        }
+      }
+      None => (),
+    }
+    match receive_from_sytter.try_recv() {
+      Err(TryRecvError::Empty) => (),
+      Ok(TriggerControl::Shutdown) | Err(TryRecvError::Disconnected) => {
+        debug!("Sytter is closing. Closing the proc connector...");
+        return Ok(());
+      }
+    }
+  }
+}
@@ -0,0 +1,183 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  contrib::resource::{ResourceMeasures, ResourceMetric},
+  error::AppError,
+};
+use std::{collections::BTreeMap, path::Path, time::Instant};
+
+/**
+ * Everything here comes from /proc.  See proc(5) for the formats.  CPU time
+ * and I/O are running totals, so they are measured as the difference from the
+ * last sample, which means the first sample of anything doesn't have them.
+ */
+struct ProcessCounters {
+  at: Instant,
+  cpu_ticks: u64,
+  io_bytes: Option<u64>,
+}
+
+pub struct ResourceSampler {
+  clock_ticks_per_second: f64,
+  processes: BTreeMap<u32, ProcessCounters>,
+  // Busy and total jiffies across every CPU.
+  system_cpu: Option<(u64, u64)>,
+}
+
+fn proc_read(path: impl AsRef<Path>) -> Option<String> {
+  std::fs::read_to_string(path).ok()
+}
+
+/// Find "<key>: <number> ..." in files like /proc/meminfo.
+fn proc_field(contents: &str, key: &str) -> Option<u64> {
+  contents
+    .lines()
+    .find_map(|l| l.strip_prefix(key)?.strip_prefix(':'))
+    .and_then(|v| v.split_whitespace().next())
+    .and_then(|v| v.parse::<u64>().ok())
+}
+
+/// User and system time, in clock ticks.
+fn process_cpu_ticks(dir: &Path) -> Option<u64> {
+  let stat = proc_read(dir.join("stat"))?;
+  // The name is in parentheses and can have anything in it, so fields are
+  // counted from after it.  utime and stime are the 14th and 15th fields.
+  let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+  let utime = fields.get(11)?.parse::<u64>().ok()?;
+  let stime = fields.get(12)?.parse::<u64>().ok()?;
+  Some(utime + stime)
+}
+
+impl Default for ResourceSampler {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl ResourceSampler {
+  pub fn new() -> Self {
+    // Safe because sysconf has no side effects.
+    let clock_ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    ResourceSampler {
+      clock_ticks_per_second: match clock_ticks > 0 {
+        true => clock_ticks as f64,
+        false => 100.0,
+      },
+      processes: BTreeMap::new(),
+      system_cpu: None,
+    }
+  }
+
+  /// Everything can be measured on Linux.
+  pub fn supports(_metric: &ResourceMetric, _process: bool) -> bool {
+    true
+  }
+
+  /// Measure each process that is still around.  File descriptors and I/O
+  /// are left out for processes we aren't allowed to look into.
+  pub fn process_measures(
+    &mut self,
+    pids: &[u32],
+  ) -> BTreeMap<u32, ResourceMeasures> {
+    let now = Instant::now();
+    let mut counters = BTreeMap::new();
+    let mut measured = BTreeMap::new();
+    for pid in pids {
+      let dir = Path::new("/proc").join(pid.to_string());
+      let Some(cpu_ticks) = process_cpu_ticks(&dir) else {
+        continue;
+      };
+      let io_bytes = proc_read(dir.join("io")).and_then(|io| {
+        Some(proc_field(&io, "read_bytes")? + proc_field(&io, "write_bytes")?)
+      });
+      let mut measures = ResourceMeasures::new();
+      if let Some(rss_kb) =
+        proc_read(dir.join("status")).and_then(|s| proc_field(&s, "VmRSS"))
+      {
+        measures.insert(ResourceMetric::Rss, (rss_kb * 1024) as f64);
+      }
+      if let Ok(fds) = std::fs::read_dir(dir.join("fd")) {
+        measures.insert(ResourceMetric::Fds, fds.count() as f64);
+      }
+      if let Some(previous) = self.processes.get(pid) {
+        let seconds = now.duration_since(previous.at).as_secs_f64();
+        if seconds > 0.0 {
+          let ticks = cpu_ticks.saturating_sub(previous.cpu_ticks) as f64;
+          measures.insert(
+            ResourceMetric::Cpu,
+            ticks / self.clock_ticks_per_second / seconds * 100.0,
+          );
+          if let (Some(io), Some(previous_io)) = (io_bytes, previous.io_bytes) {
+            measures.insert(
+              ResourceMetric::Io,
+              io.saturating_sub(previous_io) as f64 / seconds,
+            );
+          }
+        }
+      }
+      counters.insert(
+        *pid,
+        ProcessCounters {
+          at: now,
+          cpu_ticks,
+          io_bytes,
+        },
+      );
+      measured.insert(*pid, measures);
+    }
+    // Anything not asked about this time is gone, or no longer matches.
+    self.processes = counters;
+    measured
+  }
+
+  pub fn system_measures(&mut self) -> Result<ResourceMeasures, AppError> {
+    let read_error = |file: &str| {
+      AppError::TriggerRuntimeError(format!("Could not read {}.", file))
+    };
+    let mut measures = ResourceMeasures::new();
+    // The first line adds up every CPU: user, nice, system, idle, iowait, irq,
+    // softirq, and steal.  Guest time is already counted in user time.
+    let stat = proc_read("/proc/stat").ok_or(read_error("/proc/stat"))?;
+    let jiffies: Vec<u64> = stat
+      .lines()
+      .next()
+      .and_then(|l| l.strip_prefix("cpu "))
+      .ok_or(read_error("/proc/stat"))?
+      .split_whitespace()
+      .take(8)
+      .filter_map(|j| j.parse::<u64>().ok())
+      .collect();
+    let total: u64 = jiffies.iter().sum();
+    let idle = jiffies.get(3).unwrap_or(&0) + jiffies.get(4).unwrap_or(&0);
+    let busy = total.saturating_sub(idle);
+    if let Some((previous_busy, previous_total)) = self.system_cpu {
+      let elapsed = total.saturating_sub(previous_total);
+      if elapsed > 0 {
+        measures.insert(
+          ResourceMetric::Cpu,
+          busy.saturating_sub(previous_busy) as f64 / elapsed as f64 * 100.0,
+        );
+      }
+    }
+    self.system_cpu = Some((busy, total));
+    let meminfo =
+      proc_read("/proc/meminfo").ok_or(read_error("/proc/meminfo"))?;
+    if let (Some(total), Some(available)) = (
+      proc_field(&meminfo, "MemTotal"),
+      proc_field(&meminfo, "MemAvailable"),
+    ) {
+      if total > 0 {
+        measures.insert(
+          ResourceMetric::Memory,
+          total.saturating_sub(available) as f64 / total as f64 * 100.0,
+        );
+      }
+    }
+    if let Some(load) = proc_read("/proc/loadavg")
+      .and_then(|l| l.split_whitespace().next()?.parse::<f64>().ok())
+    {
+      measures.insert(ResourceMetric::Load, load);
+    }
+    Ok(measures)
+  }
+}
@@ -0,0 +1,297 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  error::AppError,
+  linux::{netlink_receive, netlink_send, netlink_socket},
+};
+use std::{
+  net::{IpAddr, Ipv4Addr, Ipv6Addr},
+  os::fd::OwnedFd,
+  time::Duration,
+};
+
+/**
+ * The kernel announces changes to links, addresses, and routes on a
+ * NETLINK_ROUTE socket, which is what `ip monitor` listens to.  The same
+ * socket answers requests for everything there is now.  See rtnetlink(7) and
+ * netlink(7) for the message layouts used here.
+ */
+const NLMSGHDR_LEN: usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+const IFADDRMSG_LEN: usize = 8;
+const RTMSG_LEN: usize = 12;
+// Flags that can be set on an attribute's type.
+const NLA_TYPE_MASK: u16 =
+  !(libc::NLA_F_NESTED as u16 | libc::NLA_F_NET_BYTEORDER as u16);
+// Dumps pack as many messages into a datagram as fit in a page or two.
+const RTNETLINK_BUFFER_SIZE: usize = 32768;
+// How long the kernel gets to answer a dump.
+const RTNETLINK_DUMP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RtnetlinkMessage {
+  Link {
+    index: u32,
+    name: Option<String>,
+    // Up, and able to pass traffic (such as having a cable plugged in).
+    up: bool,
+    removed: bool,
+  },
+  Address {
+    index: u32,
+    address: IpAddr,
+    prefix_length: u8,
+    removed: bool,
+  },
+  // Only default routes in the main table are of interest.
+  DefaultRoute {
+    ipv6: bool,
+    index: Option<u32>,
+    gateway: Option<IpAddr>,
+    removed: bool,
+  },
+  // The end of a dump.
+  Done,
+  // The kernel refused a request, with this errno.
+  Error(i32),
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+  data
+    .get(offset..offset + 2)
+    .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+  data
+    .get(offset..offset + 4)
+    .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// Messages and attributes are padded out to four bytes.
+fn align(length: usize) -> usize {
+  (length + 3) & !3
+}
+
+/// The attributes after a message's fixed header, as (type, data).
+fn attributes(data: &[u8]) -> Vec<(u16, &[u8])> {
+  let mut found = vec![];
+  let mut offset = 0;
+  while let (Some(length), Some(kind)) =
+    (u16_at(data, offset), u16_at(data, offset + 2))
+  {
+    let length = length as usize;
+    match data.get(offset + 4..offset + length) {
+      Some(value) if length >= 4 => found.push((kind & NLA_TYPE_MASK, value)),
+      _ => break,
+    }
+    offset += align(length);
+  }
+  found
+}
+
+fn attribute(attributes: &[(u16, &[u8])], kind: u16) -> Option<Vec<u8>> {
+  attributes
+    .iter()
+    .find(|(k, _)| *k == kind)
+    .map(|(_, v)| v.to_vec())
+}
+
+fn ip_address(value: &[u8]) -> Option<IpAddr> {
+  match value.len() {
+    4 => Some(IpAddr::V4(Ipv4Addr::new(
+      value[0], value[1], value[2], value[3],
+    ))),
+    16 => {
+      let mut octets = [0_u8; 16];
+      octets.copy_from_slice(value);
+      Some(IpAddr::V6(Ipv6Addr::from(octets)))
+    }
+    _ => None,
+  }
+}
+
+fn link_parse(data: &[u8], removed: bool) -> Option<RtnetlinkMessage> {
+  let index = u32_at(data, 4)?;
+  let flags = u32_at(data, 8)?;
+  let attributes = attributes(data.get(IFINFOMSG_LEN..)?);
+  let running = (libc::IFF_UP | libc::IFF_RUNNING) as u32;
+  Some(RtnetlinkMessage::Link {
+    index,
+    name: attribute(&attributes, libc::IFLA_IFNAME).map(|name| {
+      String::from_utf8_lossy(&name)
+        .trim_end_matches('\0')
+        .to_string()
+    }),
+    up: flags & running == running,
+    removed,
+  })
+}
+
+fn address_parse(data: &[u8], removed: bool) -> Option<RtnetlinkMessage> {
+  let prefix_length = *data.get(1)?;
+  let index = u32_at(data, 4)?;
+  let attributes = attributes(data.get(IFADDRMSG_LEN..)?);
+  // For point to point links, IFA_ADDRESS is the other end, and IFA_LOCAL is
+  // ours.  Otherwise they're the same, or only IFA_ADDRESS is there.
+  let address = attribute(&attributes, libc::IFA_LOCAL)
+    .or(attribute(&attributes, libc::IFA_ADDRESS))
+    .and_then(|a| ip_address(&a))?;
+  Some(RtnetlinkMessage::Address {
+    index,
+    address,
+    prefix_length,
+    removed,
+  })
+}
+
+fn route_parse(data: &[u8], removed: bool) -> Option<RtnetlinkMessage> {
+  let destination_length = *data.get(1)?;
+  let attributes = attributes(data.get(RTMSG_LEN..)?);
+  // Tables past 255 only fit in the attribute.
+  let table = attribute(&attributes, libc::RTA_TABLE)
+    .and_then(|t| u32_at(&t, 0))
+    .unwrap_or(*data.get(4)? as u32);
+  let kind = *data.get(7)?;
+  if destination_length != 0
+    || table != libc::RT_TABLE_MAIN as u32
+    || kind != libc::RTN_UNICAST
+  {
+    return None;
+  }
+  Some(RtnetlinkMessage::DefaultRoute {
+    ipv6: *data.first()? == libc::AF_INET6 as u8,
+    index: attribute(&attributes, libc::RTA_OIF).and_then(|i| u32_at(&i, 0)),
+    gateway: attribute(&attributes, libc::RTA_GATEWAY)
+      .and_then(|g| ip_address(&g)),
+    removed,
+  })
+}
+
+/// Everything we know how to read in a datagram from the kernel.
+pub fn rtnetlink_parse(datagram: &[u8]) -> Vec<RtnetlinkMessage> {
+  let mut messages = vec![];
+  let mut offset = 0;
+  while let (Some(length), Some(kind)) =
+    (u32_at(datagram, offset), u16_at(datagram, offset + 4))
+  {
+    let length = length as usize;
+    let Some(data) = datagram.get(offset + NLMSGHDR_LEN..offset + length)
+    else {
+      break;
+    };
+    let message = match kind {
+      libc::RTM_NEWLINK => link_parse(data, false),
+      libc::RTM_DELLINK => link_parse(data, true),
+      libc::RTM_NEWADDR => address_parse(data, false),
+      libc::RTM_DELADDR => address_parse(data, true),
+      libc::RTM_NEWROUTE => route_parse(data, false),
+      libc::RTM_DELROUTE => route_parse(data, true),
+      k if k == libc::NLMSG_DONE as u16 => Some(RtnetlinkMessage::Done),
+      // Errors are negative errnos, and zero is an acknowledgement.
+      k if k == libc::NLMSG_ERROR as u16 => u32_at(data, 0)
+        .map(|e| -(e as i32))
+        .filter(|e| *e != 0)
+        .map(RtnetlinkMessage::Error),
+      _ => None,
+    };
+    messages.extend(message);
+    offset += align(length.max(NLMSGHDR_LEN));
+  }
+  messages
+}
+
+pub struct RtnetlinkSocket {
+  fd: OwnedFd,
+}
+
+impl RtnetlinkSocket {
+  /// Listen for changes to links, addresses, and routes for IPv4 and IPv6.
+  pub fn new() -> Result<Self, AppError> {
+    let groups = libc::RTMGRP_LINK
+      | libc::RTMGRP_IPV4_IFADDR
+      | libc::RTMGRP_IPV6_IFADDR
+      | libc::RTMGRP_IPV4_ROUTE
+      | libc::RTMGRP_IPV6_ROUTE;
+    let fd =
+      netlink_socket(libc::NETLINK_ROUTE, groups as u32).map_err(|e| {
+        AppError::TriggerInitializeError(format!(
+          "Could not listen for network changes: {:?}",
+          e,
+        ))
+      })?;
+    Ok(RtnetlinkSocket { fd })
+  }
+
+  /// Wait up to `timeout` for the next changes.
+  pub fn messages_await(
+    &self,
+    timeout: Duration,
+  ) -> Result<Vec<RtnetlinkMessage>, AppError> {
+    let mut buffer = vec![0_u8; RTNETLINK_BUFFER_SIZE];
+    Ok(
+      netlink_receive(&self.fd, &mut buffer, timeout, "network changes")?
+        .map(|n| rtnetlink_parse(&buffer[..n]))
+        .unwrap_or_default(),
+    )
+  }
+}
+
+/// Ask the kernel for every link, address, and default route there is now.
+/// This uses its own socket, so it doesn't get mixed up with changes.
+pub fn rtnetlink_current() -> Result<Vec<RtnetlinkMessage>, AppError> {
+  let dump_error = |e: String| {
+    AppError::TriggerRuntimeError(format!(
+      "Could not list the network as it is: {}",
+      e,
+    ))
+  };
+  let fd = netlink_socket(libc::NETLINK_ROUTE, 0)
+    .map_err(|e| dump_error(format!("{:?}", e)))?;
+  let mut current = vec![];
+  // Only one dump can be running on a socket at a time.
+  for (kind, header_length) in [
+    (libc::RTM_GETLINK, IFINFOMSG_LEN),
+    (libc::RTM_GETADDR, IFADDRMSG_LEN),
+    (libc::RTM_GETROUTE, RTMSG_LEN),
+  ] {
+    // A zeroed header asks for every family.
+    let length = NLMSGHDR_LEN + header_length;
+    let request: Vec<u8> = [
+      &(length as u32).to_ne_bytes()[..],
+      &kind.to_ne_bytes(),
+      &((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes(),
+      &1_u32.to_ne_bytes(),
+      &0_u32.to_ne_bytes(),
+      &vec![0_u8; header_length],
+    ]
+    .concat();
+    netlink_send(&fd, &request).map_err(|e| dump_error(format!("{:?}", e)))?;
+    let mut buffer = vec![0_u8; RTNETLINK_BUFFER_SIZE];
+    loop {
+      let read = netlink_receive(
+        &fd,
+        &mut buffer,
+        RTNETLINK_DUMP_TIMEOUT,
+        "the network as it is",
+      )?
+      .ok_or(dump_error("The kernel didn't answer.".to_string()))?;
+      let messages = rtnetlink_parse(&buffer[..read]);
+      if let Some(RtnetlinkMessage::Error(e)) = messages
+        .iter()
+        .find(|m| matches!(m, RtnetlinkMessage::Error(_)))
+      {
+        return Err(dump_error(format!(
+          "{:?}",
+          std::io::Error::from_raw_os_error(*e)
+        )));
+      }
+      let done = messages.contains(&RtnetlinkMessage::Done);
+      current.extend(messages);
+      if done {
+        break;
+      }
+    }
+  }
+  Ok(current)
+}
@@ -0,0 +1,83 @@
+#![cfg(target_os = "linux")]
+
+use crate::{
+  error::AppError,
+  linux::{netlink_receive, netlink_socket},
+};
+use std::{collections::BTreeMap, os::fd::OwnedFd, time::Duration};
+
+/**
+ * The kernel announces devices coming and going on a netlink socket, which is
+ * what udevd itself listens to.  Each message is an "<action>@<devpath>"
+ * header followed by "KEY=value" properties, all separated by NULs.
+ */
+// The multicast group the kernel sends uevents to.  udevd re-broadcasts its own
+// version to group 2, which we don't need.
+const UEVENT_KERNEL_GROUP: u32 = 1;
+// Uevents are capped at a few kilobytes by the kernel.
+const UEVENT_BUFFER_SIZE: usize = 8192;
+
+/// One kernel uevent, such as "add" for a USB device being plugged in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Uevent {
+  pub action: String,
+  // Keys are as the kernel sends them, such as "SUBSYSTEM" and "DEVNAME".
+  pub properties: BTreeMap<String, String>,
+}
+
+impl Uevent {
+  pub fn property(&self, key: &str) -> Option<&str> {
+    self.properties.get(key).map(|v| v.as_str())
+  }
+}
+
+/// None for anything that isn't a kernel uevent.
+pub fn uevent_parse(message: &[u8]) -> Option<Uevent> {
+  let mut parts = message
+    .split(|b| *b == 0)
+    .filter(|p| !p.is_empty())
+    .map(String::from_utf8_lossy);
+  let header = parts.next()?;
+  if !header.contains('@') {
+    return None;
+  }
+  let properties: BTreeMap<String, String> = parts
+    .filter_map(|p| {
+      p.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+    })
+    .collect();
+  Some(Uevent {
+    action: properties.get("ACTION")?.clone(),
+    properties,
+  })
+}
+
+pub struct UeventSocket {
+  fd: OwnedFd,
+}
+
+impl UeventSocket {
+  pub fn new() -> Result<Self, AppError> {
+    let fd = netlink_socket(libc::NETLINK_KOBJECT_UEVENT, UEVENT_KERNEL_GROUP)
+      .map_err(|e| {
+        AppError::TriggerInitializeError(format!(
+          "Could not listen for kernel uevents: {:?}",
+          e,
+        ))
+      })?;
+    Ok(UeventSocket { fd })
+  }
+
+  /// Wait up to `timeout` for the next uevent.
+  pub fn uevent_await(
+    &self,
+    timeout: Duration,
+  ) -> Result<Option<Uevent>, AppError> {
+    let mut buffer = [0_u8; UEVENT_BUFFER_SIZE];
+    Ok(
+      netlink_receive(&self.fd, &mut buffer, timeout, "device events")?
+        .and_then(|n| uevent_parse(&buffer[..n])),
+    )
+  }
+}
@@ -5,12 +5,12 @@
  * https://github.com/opensource-apple/IOUSBFamily/blob/master/Examples/Another%20USB%20Notification%20Example/USBNotificationExample.c
  */
 use crate::{
-  contrib::device::DeviceConnectionEvent,
+  contrib::device::{DeviceConnection, DeviceConnectionEvent},
   error::AppError,
   macos::event::{listen_start, refcon_callback, CallbackData, ListenResult},
-  state::{State, SytterVariable},
 };
 use std::{
+  collections::BTreeMap,
   ffi::{c_void, CString},
   sync::{Arc, Mutex},
 };
@@ -24,7 +24,7 @@ use sytter_macos_bindings::{
 };
 use tracing::*;
 
-impl CallbackData for DeviceConnectionEvent {}
+impl CallbackData for DeviceConnection {}
 
 /// Helper function to convert CFStringRef to Rust String
 unsafe fn cfstring_to_string(cf_string: CFStringRef) -> Option<String> {
@@ -137,53 +137,42 @@ unsafe fn get_device_int_property(
   result
 }
 
-/// Extract device properties and set them as state variables
-unsafe fn extract_and_set_device_properties(device: io_object_t) {
-  // Extract common device properties
-  if let Some(name) = get_device_string_property(device, "USB Product Name")
-    .or_else(|| get_device_string_property(device, "IOClass"))
-  {
-    State::set_variable(SytterVariable {
-      key: "sytter_device_name".into(),
-      value: name,
-    });
-  }
-
-  if let Some(vendor_id) = get_device_int_property(device, "idVendor") {
-    State::set_variable(SytterVariable {
-      key: "sytter_device_vendor_id".into(),
-      value: format!("0x{:04x}", vendor_id),
-    });
-  }
-
-  if let Some(product_id) = get_device_int_property(device, "idProduct") {
-    State::set_variable(SytterVariable {
-      key: "sytter_device_product_id".into(),
-      value: format!("0x{:04x}", product_id),
-    });
-  }
-
-  if let Some(serial) = get_device_string_property(device, "USB Serial Number")
-  {
-    State::set_variable(SytterVariable {
-      key: "sytter_device_serial_number".into(),
-      value: serial,
-    });
-  }
-
-  if let Some(location_id) = get_device_int_property(device, "locationID") {
-    State::set_variable(SytterVariable {
-      key: "sytter_device_location_id".into(),
-      value: format!("0x{:08x}", location_id),
-    });
-  }
-
-  if let Some(bsd_name) = get_device_string_property(device, "BSD Name") {
-    State::set_variable(SytterVariable {
-      key: "sytter_device_bsd_name".into(),
-      value: bsd_name,
-    });
-  }
+/// Extract the device properties we know how to read, keyed by their trigger
+/// event field names.
+unsafe fn device_properties(device: io_object_t) -> BTreeMap<String, String> {
+  [
+    (
+      "device_name",
+      get_device_string_property(device, "USB Product Name")
+        .or_else(|| get_device_string_property(device, "IOClass")),
+    ),
+    (
+      "device_vendor_id",
+      get_device_int_property(device, "idVendor")
+        .map(|x| format!("0x{:04x}", x)),
+    ),
+    (
+      "device_product_id",
+      get_device_int_property(device, "idProduct")
+        .map(|x| format!("0x{:04x}", x)),
+    ),
+    (
+      "device_serial_number",
+      get_device_string_property(device, "USB Serial Number"),
+    ),
+    (
+      "device_location_id",
+      get_device_int_property(device, "locationID")
+        .map(|x| format!("0x{:08x}", x)),
+    ),
+    (
+      "device_bsd_name",
+      get_device_string_property(device, "BSD Name"),
+    ),
+  ]
+  .into_iter()
+  .filter_map(|(k, v)| v.map(|v| (k.to_string(), v)))
+  .collect()
 }
 
 // Callback invoked when a device matching our filter is added.
@@ -234,13 +223,11 @@ extern "C" fn device_connection_callback(
     count += 1;
     info!("Device #{} connected: {:?}", count, device);
 
-    // Extract device properties and set them as state variables
-    unsafe {
-      extract_and_set_device_properties(device);
-    }
-
     // Emit the Add event to the Rust callback.
-    let event = Box::new(DeviceConnectionEvent::Add);
+    let event = Box::new(DeviceConnection {
+      event: DeviceConnectionEvent::Add,
+      properties: unsafe { device_properties(device) },
+    });
     match refcon_callback(refcon_value, event) {
       Ok(_kernel_port) => {
         debug!("Successfully invoked device callback for device #{}", count);
@@ -317,7 +304,7 @@ fn drain_iterator(iterator: io_iterator_t) {
 pub fn device_connection_listen_start(
   device_class: &str,
   notification_type: &str,
-  callback: impl FnMut(DeviceConnectionEvent) -> () + Send + Sync + 'static,
+  callback: impl FnMut(DeviceConnection) -> () + Send + Sync + 'static,
 ) -> Result<Box<dyn FnOnce() -> Result<(), AppError>>, AppError> {
   // Common device classes:
   // - IOUSBHostDevice: Modern USB devices (macOS 10.11+)
@@ -7,11 +7,14 @@ use config::{cli_parse, config_cli_merge, env_config_load};
 use error::AppError;
 use http_server::http_server;
 use logging::logger_init;
-use sytter::sytter_load;
+use shutdown::{shutdown_signal_await, sytters_stop};
+use status::sytter_status_update;
+use sytter::{sytter_load, SytterHandle};
 use tracing::*;
 
 use crate::state::State;
 
+mod concurrency;
 mod condition;
 mod config;
 mod contrib;
@@ -20,13 +23,20 @@ mod error;
 mod executor;
 mod failure;
 mod http_server;
+#[cfg(target_os = "linux")]
+mod linux;
 mod logging;
 #[cfg(target_os = "macos")]
 mod macos;
 // #[cfg(target_os = "macos")]
 // mod macos_bindings;
+mod rate_limit;
+mod run;
 mod shell;
+mod shutdown;
 mod state;
+mod status;
+mod supervisor;
 mod sytter;
 mod trigger;
 
@@ -55,17 +65,46 @@ async fn main() -> Result<(), AppError> {
   let config = config_cli_merge(env_config, cli_config)?;
   logger_init(config.log_level)?;
   debug!("Using config: {:?}", config);
+  let sytters: Arc<Mutex<Vec<SytterHandle>>> = Arc::new(Mutex::new(vec![]));
   for file in sytter_paths(&config.sytters_path)? {
     info!("Starting sytter '{}'...", file.display());
     let config_copy = config.clone();
+    let sytters_copy = sytters.clone();
     std::thread::spawn(move || {
-      // TODO: Handle errors from results (await and load).
-      let sytter = sytter_load(file.as_path())
+      // A broken Sytter shouldn't take the others down with it.  Its status
+      // (keyed by file, since it may not have a name) makes /health report it.
+      match sytter_load(file.as_path())
         .inspect(|s| debug!("Loaded Sytter: {:?}", s))
-        .expect(&format!("Failed to load sytter '{}'.", file.display()));
-      sytter.start(&config_copy);
+        .and_then(|sytter| sytter.start(&config_copy))
+      {
+        Ok(handle) => sytters_copy.lock().unwrap().push(handle),
+        Err(e) => {
+          error!("Failed to start sytter '{}': {:?}", file.display(), e);
+          sytter_status_update(&file.display().to_string(), |s| {
+            s.error = Some(format!("Failed to start: {:?}", e))
+          });
+        }
+      }
     });
   }
-  http_server(config.http_port).await?;
+  let server = http_server(config.http_port)?;
+  let server_handle = server.handle();
+  let server_task = tokio::spawn(server);
+  tokio::select! {
+    result = shutdown_signal_await() => result?,
+    result = server_task => {
+      return result
+        .map_err(|e| AppError::HttpStartError(std::io::Error::other(e)))?
+        .map_err(AppError::HttpStartError);
+    }
+  };
+  info!("Shutting down...");
+  let handles = std::mem::take(&mut *sytters.lock().unwrap());
+  let grace = config.shutdown_grace_period;
+  let _ = tokio::task::spawn_blocking(move || sytters_stop(handles, grace))
+    .await
+    .inspect_err(|e| error!("Sytters failed to stop: {:?}", e));
+  server_handle.stop(true).await;
+  info!("Shut down.");
   Ok(())
 }
@@ -0,0 +1,179 @@
+use crate::{
+  deserialize::duration_des,
+  error::AppError,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+  sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender},
+  time::{Duration, Instant},
+};
+use toml::Table;
+use tracing::*;
+
+/// Which event of a burst is passed on to the Sytter.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitKeep {
+  First,
+  #[default]
+  Last,
+}
+
+/// Collapses bursts of events into one.  A burst ends once `debounce` passes
+/// without another event, or once `throttle` has passed since the burst's
+/// first event, whichever comes first.  Setting both gives a debounce that
+/// can't be held off forever by a trigger that never goes quiet.  With only
+/// `throttle`, the first event of a burst is passed on right away.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TriggerRateLimit {
+  pub debounce: Option<Duration>,
+  pub throttle: Option<Duration>,
+  pub keep: RateLimitKeep,
+}
+
+fn rate_limit_duration_deserialize(
+  section_data: &Table,
+  field: &str,
+) -> Result<Option<Duration>, AppError> {
+  section_data
+    .get(field)
+    .map(|x| {
+      duration_des(x).map_err(|e| {
+        AppError::SytterDeserializeRawError(format!(
+          "Field '{}' in Trigger is invalid: {}",
+          field, e,
+        ))
+      })
+    })
+    .transpose()
+}
+
+/// Read the rate limiting options that any trigger can have.  None when the
+/// trigger has neither `debounce` nor `throttle`.
+pub fn trigger_rate_limit_toml_deserialize(
+  section_data: &Table,
+) -> Result<Option<TriggerRateLimit>, AppError> {
+  let debounce = rate_limit_duration_deserialize(section_data, "debounce")?;
+  let throttle = rate_limit_duration_deserialize(section_data, "throttle")?;
+  let keep = match section_data.get("keep").map(|x| x.as_str()) {
+    None => Ok(RateLimitKeep::default()),
+    Some(Some("first")) => Ok(RateLimitKeep::First),
+    Some(Some("last")) => Ok(RateLimitKeep::Last),
+    Some(_) => Err(AppError::SytterDeserializeRawError(
+      "Field 'keep' in Trigger must be 'first' or 'last'.".to_string(),
+    )),
+  }?;
+  Ok(match (debounce, throttle) {
+    (None, None) => None,
+    (debounce, throttle) => Some(TriggerRateLimit {
+      debounce,
+      throttle,
+      keep,
+    }),
+  })
+}
+
+impl TriggerRateLimit {
+  fn deadline(&self, burst_start: Instant, last_event: Instant) -> Instant {
+    [
+      self.debounce.map(|d| last_event + d),
+      self.throttle.map(|t| burst_start + t),
+    ]
+    .into_iter()
+    .flatten()
+    .min()
+    .unwrap_or(last_event)
+  }
+
+  /// Pass events from `receive` on to `send`, one per burst.  Returns once the
+  /// trigger stops sending, after passing on any burst still in progress.
+  ///
+  /// A throttle on its own lets the event that starts a burst straight
+  /// through.  Whatever arrives in the window after it is collapsed into one
+  /// event at the window's end, which opens the next window, so a trigger
+  /// that never goes quiet still runs the Sytter once per window.
+  pub fn relay(
+    &self,
+    receive: Receiver<TriggerEvent>,
+    send: SyncSender<TriggerEvent>,
+  ) {
+    let leading = self.debounce.is_none();
+    while let Ok(first) = receive.recv() {
+      let mut burst = if leading {
+        if send.send(first.field("coalesced_events", 1)).is_err() {
+          break;
+        }
+        None
+      } else {
+        Some((first, 1))
+      };
+      let mut burst_start = Instant::now();
+      let mut last_event = burst_start;
+      loop {
+        let timeout = self
+          .deadline(burst_start, last_event)
+          .saturating_duration_since(Instant::now());
+        let disconnected = match receive.recv_timeout(timeout) {
+          Ok(event) => {
+            last_event = Instant::now();
+            burst = Some(match burst {
+              Some((kept, count)) if self.keep == RateLimitKeep::First => {
+                (kept, count + 1)
+              }
+              Some((_, count)) => (event, count + 1),
+              None => (event, 1),
+            });
+            continue;
+          }
+          Err(RecvTimeoutError::Timeout) => false,
+          Err(RecvTimeoutError::Disconnected) => true,
+        };
+        match burst.take() {
+          Some((kept, count)) => {
+            debug!("Collapsed {} {} events into one.", count, kept.kind);
+            if send.send(kept.field("coalesced_events", count)).is_err()
+              || disconnected
+            {
+              return;
+            }
+          }
+          None if disconnected => return,
+          // The window after the leading event stayed quiet.
+          None => break,
+        }
+        if !leading {
+          break;
+        }
+        burst_start = Instant::now();
+      }
+    }
+  }
+}
+
+/// Wraps any trigger to apply its rate limit.  This keeps rate limiting out of
+/// the individual trigger kinds, which only need to report what they see.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RateLimitedTrigger {
+  pub trigger: Box<dyn Trigger>,
+  pub rate_limit: TriggerRateLimit,
+}
+
+#[typetag::serde]
+impl Trigger for RateLimitedTrigger {
+  fn trigger_await(
+    &mut self,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
+  ) -> Result<(), AppError> {
+    let (send_to_relay, receive_from_trigger) = sync_channel(0);
+    let rate_limit = self.rate_limit.clone();
+    std::thread::scope(|scope| {
+      scope
+        .spawn(move || rate_limit.relay(receive_from_trigger, send_to_sytter));
+      self
+        .trigger
+        .trigger_await(send_to_relay, receive_from_sytter)
+    })
+  }
+}
@@ -0,0 +1,149 @@
+use crate::{error::AppError, trigger::TriggerEvent};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  mpsc::{channel, Receiver, Sender},
+  Arc, Mutex,
+};
+use strum_macros::{Display, EnumString};
+use uuid::Uuid;
+
+/// Everything a component might want to know about the run it is part of.  A
+/// run starts when a trigger fires and ends once the failure handlers (if any)
+/// have finished.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RunContext {
+  pub sytter_name: String,
+  // Unique per run, so log lines and side effects from the same run can be
+  // tied together.
+  pub run_id: String,
+  pub event: TriggerEvent,
+  // Starts at 1.  Nothing retries a run yet, but components shouldn't have to
+  // change when something does.
+  pub attempt: u32,
+  // Results of the executors that have run so far, in pipeline order.
+  pub step_results: Vec<StepResult>,
+  #[serde(skip)]
+  pub cancel: CancelToken,
+}
+
+impl RunContext {
+  pub fn new(sytter_name: &str, event: TriggerEvent) -> Self {
+    RunContext {
+      sytter_name: sytter_name.to_string(),
+      run_id: Uuid::new_v4().to_string(),
+      // The trigger may have kept a way to cancel the run.
+      cancel: event.cancel.clone().unwrap_or_default(),
+      event,
+      attempt: 1,
+      step_results: vec![],
+    }
+  }
+}
+
+/// Asks a run to stop early.  Clones share the same flag, so whoever started
+/// the run can keep one and cancel it from another thread.  Components that
+/// block for a long time (such as scripts) are expected to check it.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::SeqCst);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::SeqCst)
+  }
+}
+
+impl PartialEq for CancelToken {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, Display, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum StepStatus {
+  Succeeded,
+  Failed,
+}
+
+/// How an executor fared.  The exit code is only known for failures that came
+/// from a script.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StepResult {
+  pub index: usize,
+  pub status: StepStatus,
+  pub exit_code: Option<i32>,
+}
+
+impl StepResult {
+  pub fn new(index: usize, result: &Result<(), AppError>) -> Self {
+    match result {
+      Ok(()) => StepResult {
+        index,
+        status: StepStatus::Succeeded,
+        exit_code: None,
+      },
+      Err(e) => StepResult {
+        index,
+        status: StepStatus::Failed,
+        exit_code: match e {
+          AppError::ShellExecError((_, _, code)) => Some(*code),
+          _ => None,
+        },
+      },
+    }
+  }
+}
+
+/// How a run ended.  Cancelled runs were stopped on purpose, so they don't
+/// have an outcome.
+#[derive(
+  Clone, Debug, Deserialize, Display, EnumString, PartialEq, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum RunOutcome {
+  Succeeded,
+  Failed,
+  ConditionFalse,
+}
+
+/// A finished run, for whoever wants to follow a Sytter.
+#[derive(Clone, Debug)]
+pub struct RunReport {
+  pub context: RunContext,
+  pub outcome: RunOutcome,
+  // What went wrong first, for failed runs.
+  pub error: Option<String>,
+}
+
+lazy_static! {
+  // Everything following runs.  Listeners that have gone away are dropped the
+  // next time a run finishes.
+  static ref RUN_SUBSCRIBERS: Arc<Mutex<Vec<Sender<RunReport>>>> =
+    Arc::new(Mutex::new(vec![]));
+}
+
+pub fn run_report_publish(report: RunReport) {
+  RUN_SUBSCRIBERS
+    .lock()
+    .unwrap() // If this got poisoned, there's no limping by, just panic.
+    .retain(|subscriber| subscriber.send(report.clone()).is_ok());
+}
+
+/// Hear about every run of every Sytter from now on.  Dropping the receiver
+/// stops it.
+pub fn run_reports_subscribe() -> Receiver<RunReport> {
+  let (send, receive) = channel();
+  RUN_SUBSCRIBERS
+    .lock()
+    .unwrap() // If this got poisoned, there's no limping by, just panic.
+    .push(send);
+  receive
+}
@@ -5,6 +5,8 @@ function sytter-vars() {
     curl \
       --request GET \
       --header "Authorization: Bearer $sytter_token" \
+      --header "X-Sytter-Name: $sytter_name" \
+      --header "X-Sytter-Run-Id: $sytter_run_id" \
       --header 'Accept: text/plain' \
       --silent \
       "http://localhost:$sytter_port/state"
@@ -17,15 +19,35 @@ function sytter-vars() {
   done
 }
 
+# The optional second argument is the namespace to write to: "sytter" (the
+# default), "run", or "global".
 function sytter-var-write() {
   name="$1"
+  scope="${2:-sytter}"
   value=$(eval "printf '%s' "'$'"$name")
-  echo "Writing $name=$value ..."
+  echo "Writing $name=$value to $scope ..."
   curl \
     --request POST \
     --silent \
     --header "Authorization: Bearer $sytter_token" \
+    --header "X-Sytter-Name: $sytter_name" \
+    --header "X-Sytter-Run-Id: $sytter_run_id" \
     --header 'Content-Type: application/json;charset=utf8' \
-    --data "{ \"key\": \"$name\", \"value\": \"$value\" }" \
+    --data "{ \"key\": \"$name\", \"value\": \"$value\", \"scope\": \"$scope\" }" \
     "http://localhost:$sytter_port/state"
 }
+
+# Like sytter-var-write, the optional second argument is the namespace to
+# delete from.
+function sytter-var-delete() {
+  name="$1"
+  scope="${2:-sytter}"
+  echo "Deleting $name from $scope ..."
+  curl \
+    --request DELETE \
+    --silent \
+    --header "Authorization: Bearer $sytter_token" \
+    --header "X-Sytter-Name: $sytter_name" \
+    --header "X-Sytter-Run-Id: $sytter_run_id" \
+    "http://localhost:$sytter_port/state?key=$name&scope=$scope"
+}
@@ -1,5 +1,15 @@
-use crate::error::AppError;
-use std::process::Command;
+use crate::{
+  error::AppError,
+  run::{CancelToken, RunContext},
+  trigger::TriggerEvent,
+};
+use std::{
+  io::Read,
+  os::unix::process::CommandExt,
+  process::{Child, Command, Output, Stdio},
+  thread::{sleep, spawn, JoinHandle},
+  time::{Duration, Instant},
+};
 use tracing::*;
 
 // We could lazy_static this but eventually it'll become configurable.
@@ -13,8 +23,11 @@ pub fn shell_exec_check(
   expected_exit_codes: &Vec<i32>,
   script: &String,
   id: &String,
+  envs: &[(String, String)],
+  cancel: &CancelToken,
 ) -> Result<bool, AppError> {
-  let output = Command::new(shell)
+  let mut command = Command::new(shell);
+  command
     .args(["-c", script])
     // This requires curl, which isn't the most portable.  This is a rich location
     // for a better contribution.  Some possibilities:
@@ -31,8 +44,8 @@ pub fn shell_exec_check(
       ("sytter_token", shell_sytter_token()),
       ("sytter_port", http_port.to_string()),
     ])
-    .output()
-    .map_err(AppError::ShellSpawnError)?;
+    .envs(envs.iter().cloned());
+  let output = command_output(&mut command, cancel)?;
   let (stdout, stderr) =
     (from_utf8(&output.stdout)?, from_utf8(&output.stderr)?);
   debug!("{}", stdout);
@@ -59,30 +72,154 @@ pub fn shell_exec_outputs(
   shell: &String,
   script: &String,
   id: &String,
+  envs: &[(String, String)],
+  cancel: &CancelToken,
 ) -> Result<(String, String), AppError> {
-  Command::new(shell)
+  let mut command = Command::new(shell);
+  command
     .args(["-c", script])
     .envs([
       ("sytter_token", shell_sytter_token()),
       ("sytter_port", http_port.to_string()),
     ])
-    .output()
-    .map_err(AppError::ShellSpawnError)
-    .and_then(|output| {
-      let outputs = (from_utf8(&output.stdout)?, from_utf8(&output.stderr)?);
-      debug!("{}", outputs.0);
-      debug!("{}", outputs.1);
-      match output.status.code() {
-        Some(status) => {
-          if status == 0 {
-            Ok(outputs)
-          } else {
-            Err(AppError::ShellExecError(outputs))
-          }
+    .envs(envs.iter().cloned());
+  command_output(&mut command, cancel).and_then(|output| {
+    let outputs = (from_utf8(&output.stdout)?, from_utf8(&output.stderr)?);
+    debug!("{}", outputs.0);
+    debug!("{}", outputs.1);
+    match output.status.code() {
+      Some(status) => {
+        if status == 0 {
+          Ok(outputs)
+        } else {
+          Err(AppError::ShellExecError((outputs.0, outputs.1, status)))
         }
-        None => Err(AppError::ShellChildTerminatedError),
       }
-    })
+      None => Err(AppError::ShellChildTerminatedError),
+    }
+  })
+}
+
+// How long a cancelled script gets to exit after SIGTERM before it is killed.
+const SHELL_CANCEL_GRACE: Duration = Duration::from_secs(2);
+const SHELL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn pipe_read(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+  spawn(move || {
+    let mut buffer = vec![];
+    if let Some(mut p) = pipe {
+      let _ = p.read_to_end(&mut buffer);
+    }
+    buffer
+  })
+}
+
+fn process_group_signal(child: &Child, signal: libc::c_int) {
+  // A negative PID addresses the whole process group.
+  unsafe {
+    libc::kill(-(child.id() as libc::pid_t), signal);
+  }
+}
+
+/// Ask the script (and anything it started) to stop, and kill it if it won't.
+pub fn process_group_terminate(child: &mut Child) {
+  process_group_signal(child, libc::SIGTERM);
+  let start = Instant::now();
+  while start.elapsed() < SHELL_CANCEL_GRACE {
+    if let Ok(Some(_)) = child.try_wait() {
+      return;
+    }
+    sleep(SHELL_POLL_INTERVAL);
+  }
+  process_group_signal(child, libc::SIGKILL);
+  let _ = child.wait();
+}
+
+/// Like Command::output, but gives up on the script if the run is cancelled.
+/// Scripts get their own process group so cancelling one doesn't leave its
+/// children behind.
+pub fn command_output(
+  command: &mut Command,
+  cancel: &CancelToken,
+) -> Result<Output, AppError> {
+  let mut child = command
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .process_group(0)
+    .spawn()
+    .map_err(AppError::ShellSpawnError)?;
+  let stdout = pipe_read(child.stdout.take());
+  let stderr = pipe_read(child.stderr.take());
+  loop {
+    if let Some(status) = child.try_wait().map_err(AppError::ShellSpawnError)? {
+      return Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+      });
+    }
+    if cancel.is_cancelled() {
+      warn!("Run cancelled, stopping script (PID {})...", child.id());
+      process_group_terminate(&mut child);
+      return Err(AppError::RunCancelledError);
+    }
+    sleep(SHELL_POLL_INTERVAL);
+  }
+}
+
+/// The trigger's payload as environment variables, so scripts can read what
+/// fired them without a round trip through the state API.
+pub fn trigger_event_envs(event: &TriggerEvent) -> Vec<(String, String)> {
+  vec![
+    ("sytter_trigger_kind".to_string(), event.kind.clone()),
+    (
+      "sytter_trigger_index".to_string(),
+      event.trigger_index.to_string(),
+    ),
+    (
+      "sytter_trigger_timestamp".to_string(),
+      event.timestamp_seconds().to_string(),
+    ),
+  ]
+  .into_iter()
+  .chain(
+    event
+      .fields
+      .iter()
+      .map(|(k, v)| (format!("sytter_{}", k), v.clone())),
+  )
+  .collect()
+}
+
+/// The run context as environment variables.  Earlier executors are listed as
+/// sytter_step_<index>_status and sytter_step_<index>_exit_code, so a later
+/// step can react to how they went.
+pub fn run_context_envs(context: &RunContext) -> Vec<(String, String)> {
+  vec![
+    ("sytter_name".to_string(), context.sytter_name.clone()),
+    ("sytter_run_id".to_string(), context.run_id.clone()),
+    ("sytter_attempt".to_string(), context.attempt.to_string()),
+    (
+      "sytter_steps_run".to_string(),
+      context.step_results.len().to_string(),
+    ),
+  ]
+  .into_iter()
+  .chain(context.step_results.iter().flat_map(|r| {
+    [
+      (
+        format!("sytter_step_{}_status", r.index),
+        r.status.to_string(),
+      ),
+      (
+        format!("sytter_step_{}_exit_code", r.index),
+        r.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+      ),
+    ]
+  }))
+  .chain(trigger_event_envs(&context.event))
+  .collect()
 }
 
 // This should be a cryptographically generated token.
@@ -0,0 +1,32 @@
+use crate::{error::AppError, sytter::SytterHandle};
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::*;
+
+/// Wait for SIGTERM (what launchd and systemd send) or SIGINT (Ctrl-C).
+pub async fn shutdown_signal_await() -> Result<(), AppError> {
+  let mut terminate =
+    signal(SignalKind::terminate()).map_err(AppError::SignalHandlerError)?;
+  let mut interrupt =
+    signal(SignalKind::interrupt()).map_err(AppError::SignalHandlerError)?;
+  tokio::select! {
+    _ = terminate.recv() => info!("Got SIGTERM."),
+    _ = interrupt.recv() => info!("Got SIGINT."),
+  };
+  Ok(())
+}
+
+/// Stop every Sytter at once, so the grace period is shared rather than paid
+/// once per Sytter.
+pub fn sytters_stop(sytters: Vec<SytterHandle>, grace: Duration) {
+  sytters
+    .into_iter()
+    .map(|sytter| std::thread::spawn(move || sytter.stop(grace)))
+    .collect::<Vec<_>>()
+    .into_iter()
+    .for_each(|handle| {
+      let _ = handle
+        .join()
+        .inspect_err(|e| error!("Sytter failed to stop: {:?}", e));
+    });
+}
@@ -1,55 +1,231 @@
-use std::sync::{Arc, Mutex};
+use std::{
+  collections::BTreeMap,
+  sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+  },
+};
 
 use lazy_static::lazy_static;
 
+use crate::error::AppError;
+
 #[derive(Clone, std::fmt::Debug, serde::Deserialize, serde::Serialize)]
 pub struct SytterVariable {
   pub key: String,
   pub value: String,
 }
 
+/// Which namespace a variable lives in.  Sytters can't see each other's
+/// variables, so they can't clobber each other by accident.  Sharing has to be
+/// done on purpose through the global namespace.
+#[derive(
+  Clone,
+  std::fmt::Debug,
+  Eq,
+  Ord,
+  PartialEq,
+  PartialOrd,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+pub enum StateScope {
+  Global,
+  // Keyed by the Sytter's name.
+  Sytter(String),
+  // Keyed by the run ID.  Dropped when the run ends.
+  Run(String),
+}
+
+impl StateScope {
+  /// Resolve a scope by name for the calling script.  The name and run ID come
+  /// from the script's own run context.
+  pub fn from_name(
+    name: &str,
+    sytter_name: Option<&str>,
+    run_id: Option<&str>,
+  ) -> Result<StateScope, AppError> {
+    match (name, sytter_name, run_id) {
+      ("global", _, _) => Ok(StateScope::Global),
+      ("sytter", Some(s), _) => Ok(StateScope::Sytter(s.to_string())),
+      ("run", _, Some(r)) => Ok(StateScope::Run(r.to_string())),
+      ("sytter", None, _) | ("run", _, None) => {
+        Err(AppError::StateScopeUnresolvedError(name.to_string()))
+      }
+      _ => Err(AppError::StateScopeInvalidError(name.to_string())),
+    }
+  }
+
+  /// The scopes a script can see, from least to most specific.  A variable in
+  /// a more specific scope hides one of the same name in a less specific one.
+  pub fn visible(
+    sytter_name: Option<&str>,
+    run_id: Option<&str>,
+  ) -> Vec<StateScope> {
+    vec![Some(StateScope::Global)]
+      .into_iter()
+      .chain([
+        sytter_name.map(|s| StateScope::Sytter(s.to_string())),
+        run_id.map(|r| StateScope::Run(r.to_string())),
+      ])
+      .flatten()
+      .collect()
+  }
+}
+
+/// A variable that was created, changed, or deleted.  There's no old value for
+/// a created variable, and no new value for a deleted one.
+#[derive(Clone, std::fmt::Debug, PartialEq)]
+pub struct StateChange {
+  pub scope: StateScope,
+  pub key: String,
+  pub old_value: Option<String>,
+  pub new_value: Option<String>,
+}
+
 #[derive(Clone, std::fmt::Debug, serde::Deserialize, serde::Serialize)]
 pub struct State {
-  pub variables: Vec<SytterVariable>,
+  pub namespaces: BTreeMap<StateScope, Vec<SytterVariable>>,
 }
 
 lazy_static! {
   pub static ref STATE: Arc<Mutex<State>> = Arc::new(Mutex::new(State::new()));
+  // Everything listening for changes.  Listeners that have gone away are
+  // dropped the next time something changes.
+  static ref STATE_SUBSCRIBERS: Arc<Mutex<Vec<Sender<StateChange>>>> =
+    Arc::new(Mutex::new(vec![]));
+}
+
+/// Tell every listener about changes.  This is called with the state locked,
+/// so listeners hear about changes in the order they happened.
+fn state_changes_publish(changes: Vec<StateChange>) {
+  if changes.is_empty() {
+    return;
+  }
+  let mut subscribers = STATE_SUBSCRIBERS
+    .lock()
+    .unwrap() // If this got poisoned, there's no limping by, just panic.
+    ;
+  subscribers.retain(|subscriber| {
+    changes
+      .iter()
+      .all(|change| subscriber.send(change.clone()).is_ok())
+  });
 }
 
 impl State {
   pub fn new() -> Self {
-    State { variables: vec![] }
+    State {
+      namespaces: BTreeMap::new(),
+    }
   }
 
-  pub fn get_variables() -> Vec<SytterVariable> {
+  /// All variables visible from the given scopes, merged in order.
+  pub fn get_variables(scopes: &[StateScope]) -> Vec<SytterVariable> {
     let state = STATE
       .lock()
       .unwrap() // If this got poisoned, there's no limping by, just panic.
       ;
-    state.variables.clone()
+    scopes
+      .iter()
+      .filter_map(|scope| state.namespaces.get(scope))
+      .flatten()
+      .fold(BTreeMap::new(), |mut merged, v| {
+        merged.insert(v.key.clone(), v.value.clone());
+        merged
+      })
+      .into_iter()
+      .map(|(key, value)| SytterVariable { key, value })
+      .collect()
   }
 
-  pub fn get_variable(key: &String) -> Option<String> {
+  #[allow(unused)]
+  pub fn get_variable(scope: &StateScope, key: &String) -> Option<String> {
     let state = STATE
       .lock()
       .unwrap() // If this got poisoned, there's no limping by, just panic.
       ;
     state
-      .variables
-      .iter()
-      .find(|v| *key == v.key)
+      .namespaces
+      .get(scope)
+      .and_then(|vars| vars.iter().find(|v| *key == v.key))
       .map(|v| v.value.clone())
   }
 
-  pub fn set_variable(variable: SytterVariable) {
+  pub fn set_variable(scope: StateScope, variable: SytterVariable) {
     let mut state = STATE
       .lock()
       .unwrap() // If this got poisoned, there's no limping by, just panic.
       ;
-    match state.variables.iter_mut().find(|v| variable.key == v.key) {
-      Some(v) => v.value = variable.value.clone(),
-      None => state.variables.push(variable.clone()),
+    let variables = state.namespaces.entry(scope.clone()).or_default();
+    let old_value = match variables.iter_mut().find(|v| variable.key == v.key) {
+      Some(v) => Some(std::mem::replace(&mut v.value, variable.value.clone())),
+      None => {
+        variables.push(variable.clone());
+        None
+      }
     };
+    // Writing the same value again isn't a change.
+    if old_value.as_ref() != Some(&variable.value) {
+      state_changes_publish(vec![StateChange {
+        scope,
+        key: variable.key,
+        old_value,
+        new_value: Some(variable.value),
+      }]);
+    }
+  }
+
+  /// Remove a variable, giving whether it was there.
+  pub fn delete_variable(scope: &StateScope, key: &str) -> bool {
+    let mut state = STATE
+      .lock()
+      .unwrap() // If this got poisoned, there's no limping by, just panic.
+      ;
+    let Some(variables) = state.namespaces.get_mut(scope) else {
+      return false;
+    };
+    let Some(index) = variables.iter().position(|v| v.key == key) else {
+      return false;
+    };
+    let removed = variables.remove(index);
+    state_changes_publish(vec![StateChange {
+      scope: scope.clone(),
+      key: removed.key,
+      old_value: Some(removed.value),
+      new_value: None,
+    }]);
+    true
+  }
+
+  /// Listen for every change from now on, in every scope.  Dropping the
+  /// receiver stops it.
+  pub fn subscribe() -> Receiver<StateChange> {
+    let (send, receive) = channel();
+    STATE_SUBSCRIBERS
+      .lock()
+      .unwrap() // If this got poisoned, there's no limping by, just panic.
+      .push(send);
+    receive
+  }
+
+  pub fn clear_scope(scope: &StateScope) {
+    let mut state = STATE
+      .lock()
+      .unwrap() // If this got poisoned, there's no limping by, just panic.
+      ;
+    let changes = state
+      .namespaces
+      .remove(scope)
+      .unwrap_or_default()
+      .into_iter()
+      .map(|v| StateChange {
+        scope: scope.clone(),
+        key: v.key,
+        old_value: Some(v.value),
+        new_value: None,
+      })
+      .collect();
+    state_changes_publish(changes);
   }
 }
@@ -0,0 +1,106 @@
+use std::{
+  collections::BTreeMap,
+  sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+/// Running totals for a Sytter, so events that never became a run can still be
+/// accounted for.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SytterStatus {
+  pub events_received: u64,
+  pub runs_started: u64,
+  pub runs_replaced: u64,
+  pub runs_panicked: u64,
+  pub events_queued: u64,
+  pub events_skipped: u64,
+  pub events_dropped: u64,
+  // Why the Sytter couldn't be loaded or started, if it couldn't.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+  // Keyed by the trigger's index.
+  pub triggers: BTreeMap<usize, TriggerStatus>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerState {
+  #[default]
+  Running,
+  // Crashed, and waiting to be started again.
+  Restarting,
+  // Crashed too many times in a row.  It is still restarted, but something
+  // needs looking at.
+  Unhealthy,
+  // Returned on its own or was shut down.  Not restarted.
+  Stopped,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TriggerStatus {
+  pub state: TriggerState,
+  pub restarts: u64,
+  pub consecutive_failures: u32,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub last_error: Option<String>,
+}
+
+impl SytterStatus {
+  /// Reasons this Sytter isn't doing its job, if any.
+  pub fn problems(&self) -> Vec<String> {
+    self
+      .error
+      .iter()
+      .cloned()
+      .chain(
+        self
+          .triggers
+          .iter()
+          .filter(|(_, t)| t.state == TriggerState::Unhealthy)
+          .map(|(index, t)| {
+            format!(
+              "Trigger {} is unhealthy after {} failures: {}",
+              index,
+              t.consecutive_failures,
+              t.last_error.clone().unwrap_or_default(),
+            )
+          }),
+      )
+      .collect()
+  }
+}
+
+lazy_static! {
+  pub static ref SYTTER_STATUSES: Arc<Mutex<BTreeMap<String, SytterStatus>>> =
+    Arc::new(Mutex::new(BTreeMap::new()));
+}
+
+pub fn sytter_status_update(
+  name: &str,
+  update: impl FnOnce(&mut SytterStatus),
+) {
+  let mut statuses = SYTTER_STATUSES
+    .lock()
+    .unwrap() // If this got poisoned, there's no limping by, just panic.
+    ;
+  update(statuses.entry(name.to_string()).or_default());
+}
+
+pub fn trigger_status_update(
+  name: &str,
+  trigger_index: usize,
+  update: impl FnOnce(&mut TriggerStatus),
+) {
+  sytter_status_update(name, |s| {
+    update(s.triggers.entry(trigger_index).or_default())
+  });
+}
+
+pub fn sytter_statuses() -> BTreeMap<String, SytterStatus> {
+  SYTTER_STATUSES
+    .lock()
+    .unwrap() // If this got poisoned, there's no limping by, just panic.
+    .clone()
+}
@@ -0,0 +1,162 @@
+use crate::{
+  error::AppError,
+  status::{trigger_status_update, TriggerState},
+  trigger::{Trigger, TriggerControl, TriggerEvent},
+};
+use std::{
+  any::Any,
+  sync::{
+    mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender},
+    Arc, Mutex,
+  },
+  thread::JoinHandle,
+  time::{Duration, Instant},
+};
+use tracing::*;
+
+const RESTART_BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(5 * 60);
+// A trigger that has run this long without crashing is considered to have
+// recovered, and its backoff starts over.
+const RESTART_STABLE_AFTER: Duration = Duration::from_secs(60);
+const UNHEALTHY_AFTER_FAILURES: u32 = 3;
+const SUPERVISOR_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn restart_backoff(consecutive_failures: u32) -> Duration {
+  RESTART_BACKOFF_INITIAL
+    .saturating_mul(
+      2_u32.saturating_pow(consecutive_failures.saturating_sub(1)),
+    )
+    .min(RESTART_BACKOFF_MAX)
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+  panic
+    .downcast_ref::<&str>()
+    .map(|s| s.to_string())
+    .or_else(|| panic.downcast_ref::<String>().cloned())
+    .unwrap_or("Unknown panic.".to_string())
+}
+
+/// Either the trigger's own result, or what it panicked with.
+type TriggerOutcome = Result<Result<(), AppError>, Box<dyn Any + Send>>;
+
+/// Wait for the trigger's thread to finish, passing on a shutdown if one comes
+/// first.  Returns None when shut down.  `on_stable` is called once the
+/// trigger has been running long enough to count as recovered.
+fn trigger_attempt_await(
+  attempt: JoinHandle<Result<(), AppError>>,
+  send_to_trigger: &SyncSender<TriggerControl>,
+  receive_from_sytter: &Receiver<TriggerControl>,
+  mut on_stable: impl FnMut(),
+) -> Option<TriggerOutcome> {
+  let started = Instant::now();
+  let mut stable = false;
+  loop {
+    if !stable && started.elapsed() >= RESTART_STABLE_AFTER {
+      stable = true;
+      on_stable();
+    }
+    match receive_from_sytter.recv_timeout(SUPERVISOR_POLL_INTERVAL) {
+      Ok(TriggerControl::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+        let _ = send_to_trigger.try_send(TriggerControl::Shutdown);
+        let _ = attempt.join();
+        return None;
+      }
+      Err(RecvTimeoutError::Timeout) if attempt.is_finished() => {
+        return Some(attempt.join());
+      }
+      Err(RecvTimeoutError::Timeout) => (),
+    }
+  }
+}
+
+/// Run a trigger until it is shut down, starting it again whenever it fails or
+/// panics.  Restarts back off exponentially, and a trigger that keeps failing
+/// is marked unhealthy (which /health reports).
+pub fn trigger_supervise(
+  sytter_name: &str,
+  trigger_index: usize,
+  trigger: Arc<Mutex<Box<dyn Trigger>>>,
+  send_to_sytter: SyncSender<TriggerEvent>,
+  receive_from_sytter: Receiver<TriggerControl>,
+) {
+  let mut consecutive_failures = 0;
+  loop {
+    trigger_status_update(sytter_name, trigger_index, |t| {
+      if t.state != TriggerState::Unhealthy {
+        t.state = TriggerState::Running;
+      }
+    });
+    // Each attempt gets its own control channel, since a trigger consumes its
+    // receiver.
+    let (send_to_trigger, receive_control) = sync_channel::<TriggerControl>(1);
+    let trigger_copy = trigger.clone();
+    let send_to_sytter_copy = send_to_sytter.clone();
+    let started = Instant::now();
+    let attempt = std::thread::spawn(move || {
+      trigger_copy
+        .lock()
+        // A panic in an earlier attempt poisons the lock.  The trigger is
+        // being started over anyway, so that's fine.
+        .unwrap_or_else(|e| e.into_inner())
+        .trigger_await(send_to_sytter_copy, receive_control)
+    });
+    let error = match trigger_attempt_await(
+      attempt,
+      &send_to_trigger,
+      &receive_from_sytter,
+      || {
+        trigger_status_update(sytter_name, trigger_index, |t| {
+          t.state = TriggerState::Running;
+          t.consecutive_failures = 0;
+        })
+      },
+    ) {
+      None => {
+        debug!("{}: Trigger {} shut down.", sytter_name, trigger_index);
+        trigger_status_update(sytter_name, trigger_index, |t| {
+          t.state = TriggerState::Stopped
+        });
+        return;
+      }
+      Some(Ok(Ok(()))) => {
+        debug!("{}: Trigger {} finished.", sytter_name, trigger_index);
+        trigger_status_update(sytter_name, trigger_index, |t| {
+          t.state = TriggerState::Stopped
+        });
+        return;
+      }
+      Some(Ok(Err(e))) => format!("{:?}", e),
+      Some(Err(panic)) => format!("Panicked: {}", panic_message(panic)),
+    };
+    if started.elapsed() >= RESTART_STABLE_AFTER {
+      consecutive_failures = 0;
+    }
+    consecutive_failures += 1;
+    let backoff = restart_backoff(consecutive_failures);
+    error!(
+      "{}: Trigger {} failed ({} in a row), restarting in {:?}: {}",
+      sytter_name, trigger_index, consecutive_failures, backoff, error,
+    );
+    trigger_status_update(sytter_name, trigger_index, |t| {
+      t.consecutive_failures = consecutive_failures;
+      t.last_error = Some(error);
+      t.state = if consecutive_failures >= UNHEALTHY_AFTER_FAILURES {
+        TriggerState::Unhealthy
+      } else {
+        TriggerState::Restarting
+      };
+    });
+    match receive_from_sytter.recv_timeout(backoff) {
+      Err(RecvTimeoutError::Timeout) => (),
+      Ok(TriggerControl::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+        trigger_status_update(sytter_name, trigger_index, |t| {
+          t.state = TriggerState::Stopped
+        });
+        return;
+      }
+    }
+    trigger_status_update(sytter_name, trigger_index, |t| t.restarts += 1);
+  }
+}
@@ -1,28 +1,59 @@
 use crate::{
-  condition::Condition,
+  concurrency::{
+    concurrency_default_queue_size, ConcurrencyPolicy, DispatchMessage,
+    Dispatcher,
+  },
+  condition::{conditions_check, Condition, ConditionMode},
   config::Config,
   contrib::{
+    chain::chain_trigger_toml_deserialize,
     cron::cron_trigger_toml_deserialize,
     device::device_connection_toml_deserialize,
+    file_watch::file_watch_trigger_toml_deserialize,
+    lifecycle::{lifecycle_shutdown_run, lifecycle_trigger_toml_deserialize},
+    log_tail::log_tail_trigger_toml_deserialize,
+    logic::{
+      all_condition_toml_deserialize, any_condition_toml_deserialize,
+      not_condition_toml_deserialize,
+    },
+    network::network_trigger_toml_deserialize,
     power::power_trigger_toml_deserialize,
+    probe::probe_trigger_toml_deserialize,
+    process::process_trigger_toml_deserialize,
+    resource::resource_trigger_toml_deserialize,
     shell::{
       shell_condition_toml_deserialize, shell_executor_toml_deserialize,
       shell_failure_toml_deserialize,
     },
+    signal::signal_trigger_toml_deserialize,
+    state::state_trigger_toml_deserialize,
+    stdout::stdout_trigger_toml_deserialize,
+    webhook::webhook_trigger_toml_deserialize,
   },
   error::AppError,
-  executor::Executor,
+  executor::{
+    executor_error_policy_deserialize, Executor, ExecutorErrorPolicy,
+    ExecutorStep,
+  },
   failure::Failure,
-  trigger::Trigger,
+  rate_limit::{trigger_rate_limit_toml_deserialize, RateLimitedTrigger},
+  run::{run_report_publish, RunContext, RunOutcome, RunReport, StepResult},
+  shell::trigger_event_envs,
+  state::{State, StateScope, SytterVariable},
+  supervisor::trigger_supervise,
+  trigger::{Trigger, TriggerControl, TriggerEvent},
 };
 use serde::Deserialize;
 use std::{
   fs::read_to_string,
+  ops::ControlFlow,
   path::Path,
   sync::{
-    mpsc::{sync_channel, Receiver, SyncSender},
+    mpsc::{channel, sync_channel, Sender, SyncSender},
     Arc, Mutex,
   },
+  thread::JoinHandle,
+  time::{Duration, Instant},
 };
 use toml::Table;
 use tracing::*;
@@ -34,9 +65,12 @@ pub struct Sytter {
   #[allow(unused)]
   pub description: String,
   pub triggers: Vec<Arc<Mutex<Box<dyn Trigger>>>>,
+  pub condition_mode: ConditionMode,
   pub conditions: Vec<Box<dyn Condition>>,
-  pub executors: Vec<Box<dyn Executor>>,
+  pub executors: Vec<ExecutorStep>,
   pub failures: Vec<Box<dyn Failure>>,
+  pub concurrency: ConcurrencyPolicy,
+  pub queue_size: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,12 +78,23 @@ pub struct SytterDeserializedRaw {
   pub name: String,
   pub description: String,
   pub triggers: Vec<Table>,
+  // How the top level conditions are combined.  Nested groups use the "all",
+  // "any", and "not" condition kinds instead.
+  #[serde(default)]
+  pub mode: ConditionMode,
+  #[serde(default)]
   pub conditions: Vec<Table>,
+  // What to do with events that arrive while a run is in progress.
+  #[serde(default)]
+  pub concurrency: ConcurrencyPolicy,
+  #[serde(default = "concurrency_default_queue_size")]
+  pub queue_size: usize,
   pub executors: Vec<Table>,
   pub failures: Vec<Table>,
 }
 
 pub fn sytter_trigger_table_deserialize(
+  sytter_name: &str,
   section_data: &Table,
 ) -> Result<Arc<Mutex<Box<dyn Trigger>>>, AppError> {
   let kind = section_data.get("kind").and_then(|x| x.as_str()).ok_or(
@@ -57,15 +102,43 @@ pub fn sytter_trigger_table_deserialize(
       "Field 'kind' missing from Trigger.".to_string(),
     ),
   )?;
-  Ok(Arc::new(Mutex::new(match kind {
+  let trigger = match kind {
     "cron" => cron_trigger_toml_deserialize(section_data),
     "device-connection" => device_connection_toml_deserialize(section_data),
+    "file-watch" => file_watch_trigger_toml_deserialize(section_data),
+    // Shutdowns find it by Sytter name.
+    "lifecycle" => {
+      lifecycle_trigger_toml_deserialize(sytter_name, section_data)
+    }
+    // Remembers how far it got under the Sytter's name.
+    "log-tail" => log_tail_trigger_toml_deserialize(sytter_name, section_data),
+    "network" => network_trigger_toml_deserialize(section_data),
     "power" => power_trigger_toml_deserialize(section_data),
+    "probe" => probe_trigger_toml_deserialize(section_data),
+    "process" => process_trigger_toml_deserialize(section_data),
+    "resource" => resource_trigger_toml_deserialize(section_data),
+    "signal" => signal_trigger_toml_deserialize(section_data),
+    // Can watch its own Sytter's namespace, which it needs the name for.
+    "state" => state_trigger_toml_deserialize(sytter_name, section_data),
+    "stdout" => stdout_trigger_toml_deserialize(section_data),
+    // Follows another Sytter, which can't be its own.
+    "sytter" => chain_trigger_toml_deserialize(sytter_name, section_data),
+    // Webhooks are routed by Sytter name.
+    "webhook" => webhook_trigger_toml_deserialize(sytter_name, section_data),
     _ => Err(AppError::SytterDeserializeRawError(format!(
       "Kind '{}' not supported",
       kind,
     ))),
-  }?)))
+  }?;
+  Ok(Arc::new(Mutex::new(
+    match trigger_rate_limit_toml_deserialize(section_data)? {
+      Some(rate_limit) => Box::new(RateLimitedTrigger {
+        trigger,
+        rate_limit,
+      }),
+      None => trigger,
+    },
+  )))
 }
 
 pub fn sytter_condition_table_deserialize(
@@ -76,13 +149,15 @@ pub fn sytter_condition_table_deserialize(
       "Field 'kind' missing from Condition.".to_string(),
     ),
   )?;
-  if kind == "shell" {
-    shell_condition_toml_deserialize(section_data)
-  } else {
-    Err(AppError::SytterDeserializeRawError(format!(
+  match kind {
+    "all" => all_condition_toml_deserialize(section_data),
+    "any" => any_condition_toml_deserialize(section_data),
+    "not" => not_condition_toml_deserialize(section_data),
+    "shell" => shell_condition_toml_deserialize(section_data),
+    _ => Err(AppError::SytterDeserializeRawError(format!(
       "Kind '{}' not supported",
       kind,
-    )))
+    ))),
   }
 }
 
@@ -104,6 +179,25 @@ pub fn sytter_executor_table_deserialize(
   }
 }
 
+pub fn sytter_executor_step_table_deserialize(
+  section_data: &Table,
+) -> Result<ExecutorStep, AppError> {
+  Ok(ExecutorStep {
+    executor: sytter_executor_table_deserialize(section_data)?,
+    on_error: section_data
+      .get("on_error")
+      .map(|x| {
+        x.as_str()
+          .ok_or(AppError::SytterDeserializeRawError(
+            "Field 'on_error' in Executor must be a string.".to_string(),
+          ))
+          .and_then(executor_error_policy_deserialize)
+      })
+      .transpose()?
+      .unwrap_or_default(),
+  })
+}
+
 pub fn sytter_failure_table_deserialize(
   section_data: &Table,
 ) -> Result<Box<dyn Failure>, AppError> {
@@ -131,29 +225,32 @@ pub fn sytter_deserialize(
   let triggers: Vec<Arc<Mutex<Box<dyn Trigger>>>> = sd
     .triggers
     .iter()
-    .map(sytter_trigger_table_deserialize)
+    .map(|t| sytter_trigger_table_deserialize(&sd.name, t))
     .collect::<Result<Vec<Arc<Mutex<Box<dyn Trigger>>>>, AppError>>()?;
   let conditions: Vec<Box<dyn Condition>> = sd
     .conditions
     .iter()
     .map(sytter_condition_table_deserialize)
     .collect::<Result<Vec<Box<dyn Condition>>, AppError>>()?;
-  let executors: Vec<Box<dyn Executor>> = sd
+  let executors: Vec<ExecutorStep> = sd
     .executors
     .iter()
-    .map(sytter_executor_table_deserialize)
-    .collect::<Result<Vec<Box<dyn Executor>>, AppError>>()?;
+    .map(sytter_executor_step_table_deserialize)
+    .collect::<Result<Vec<ExecutorStep>, AppError>>()?;
   let failures: Vec<Box<dyn Failure>> = sd
     .failures
     .iter()
     .map(sytter_failure_table_deserialize)
     .collect::<Result<Vec<Box<dyn Failure>>, AppError>>()?;
   Ok(Sytter {
+    concurrency: sd.concurrency,
+    condition_mode: sd.mode,
     conditions,
     description: sd.description,
     executors,
     failures,
     name: sd.name,
+    queue_size: sd.queue_size,
     triggers,
   })
 }
@@ -167,56 +264,142 @@ pub fn sytter_load(path: &Path) -> Result<Sytter, AppError> {
 }
 
 impl Sytter {
-  pub fn start(self, config: &Config) -> Result<(), AppError> {
+  pub fn start(self, config: &Config) -> Result<SytterHandle, AppError> {
     if self.triggers.len() == 0 {
       error!("{}: No triggers found.  Nothing to do here.", self.name);
       Err(AppError::TriggersMissing(self.name.clone()))
     } else {
       let name = self.name.clone();
-      // TODO: Wire up error handling properly.
-      let triggers_copy = self.triggers.clone();
-      let _channels = triggers_copy
+      let threaded_sytter = Arc::new(ThreadedSytter {
+        name: name.clone(),
+        description: self.description.clone(),
+        condition_mode: self.condition_mode.clone(),
+        conditions: self.conditions.clone(),
+        executors: self.executors.clone(),
+        failures: self.failures.clone(),
+      });
+      // Every trigger feeds the same dispatcher, which is what lets the
+      // concurrency policy apply to the Sytter as a whole.  The channel is
+      // unbounded so triggers never wait on a running script.
+      let (send_to_dispatcher, receive_from_triggers) =
+        channel::<DispatchMessage>();
+      let mut dispatcher = Dispatcher::new(
+        threaded_sytter,
+        config.clone(),
+        self.concurrency.clone(),
+        self.queue_size,
+        send_to_dispatcher.clone(),
+      );
+      std::thread::spawn(move || dispatcher.dispatch(receive_from_triggers));
+      let send_to_dispatcher_copy = send_to_dispatcher.clone();
+      let triggers = self
+        .triggers
         .clone()
         .into_iter()
-        .map(move |trigger| {
-          let config_copy = config.clone();
+        .enumerate()
+        .map(move |(trigger_index, trigger)| {
           let name_copy = name.clone();
-          let threaded_sytter = ThreadedSytter {
-            name: name_copy.clone(),
-            description: self.description.clone(),
-            conditions: self.conditions.clone(),
-            executors: self.executors.clone(),
-            failures: self.failures.clone(),
-          };
-          std::thread::spawn(move || {
-            let (send_to_trigger, receive_from_sytter) =
-              sync_channel::<String>(0);
-            let (send_to_sytter, receive_from_trigger) = sync_channel(0);
+          let send_to_dispatcher = send_to_dispatcher_copy.clone();
+          // Room for one message, so asking a trigger to shut down never
+          // blocks on a trigger that isn't listening.
+          let (send_to_trigger, receive_from_sytter) =
+            sync_channel::<TriggerControl>(1);
+          let join_handle = std::thread::spawn(move || {
+            let (send_to_sytter, receive_from_trigger) =
+              sync_channel::<TriggerEvent>(0);
+            let name_for_trigger = name_copy.clone();
             let _join_handle = std::thread::spawn(move || {
-              trace!("Awaiting trigger for {}", name_copy);
-              let binding_trigger = trigger.clone();
-              binding_trigger
-                .lock()
-                .unwrap()
-                .trigger_await(send_to_sytter, receive_from_sytter)
-                .unwrap();
-            });
-            loop {
-              let _ = threaded_sytter.trigger_execute_on_message(
-                &config_copy,
-                &send_to_trigger,
-                &receive_from_trigger,
+              trace!("Awaiting trigger for {}", name_for_trigger);
+              trigger_supervise(
+                &name_for_trigger,
+                trigger_index,
+                trigger,
+                send_to_sytter,
+                receive_from_sytter,
               );
-            }
+            });
+            info!("{}: Waiting for message from trigger...", name_copy);
+            receive_from_trigger.iter().for_each(|event| {
+              let _ = send_to_dispatcher
+                .send(DispatchMessage::Event(TriggerEvent {
+                  trigger_index,
+                  ..event
+                }))
+                .inspect_err(|e| {
+                  error!("{}: Dispatcher has gone away: {:?}", name_copy, e)
+                });
+            });
+            warn!(
+              "{}: Trigger {} has stopped sending events.",
+              name_copy, trigger_index,
+            );
           });
-          Ok(())
+          Ok((send_to_trigger, join_handle))
         })
-        .collect::<Result<Vec<()>, AppError>>()?;
-      Ok(())
+        .collect::<Result<Vec<_>, AppError>>()?;
+      Ok(SytterHandle {
+        name: self.name,
+        send_to_dispatcher,
+        triggers,
+      })
     }
   }
 }
 
+// How long triggers get to clean up after being asked to shut down.
+const TRIGGER_SHUTDOWN_WAIT: Duration = Duration::from_secs(5);
+
+/// What's left of a Sytter once it's started, kept so it can be stopped.
+pub struct SytterHandle {
+  pub name: String,
+  send_to_dispatcher: Sender<DispatchMessage>,
+  triggers: Vec<(SyncSender<TriggerControl>, JoinHandle<()>)>,
+}
+
+impl SytterHandle {
+  /// Run the Sytter's Shutdown triggers, stop taking events, give runs in
+  /// progress up to `grace` to finish, and then have every trigger clean up
+  /// after itself.  Triggers are stopped last so a run never loses something
+  /// (such as a sleep delay) that its trigger was holding for it.
+  pub fn stop(self, grace: Duration) {
+    info!("{}: Stopping...", self.name);
+    // Events are still taken until these are done, since that's how their
+    // runs start.
+    lifecycle_shutdown_run(&self.name, grace);
+    let (send_done, receive_done) = channel();
+    match self
+      .send_to_dispatcher
+      .send(DispatchMessage::Shutdown(grace, send_done))
+    {
+      Ok(()) => {
+        let _ = receive_done.recv();
+      }
+      Err(e) => error!("{}: Dispatcher has gone away: {:?}", self.name, e),
+    }
+    self.triggers.iter().for_each(|(send_to_trigger, _)| {
+      let _ = send_to_trigger.try_send(TriggerControl::Shutdown);
+    });
+    let start = Instant::now();
+    while start.elapsed() < TRIGGER_SHUTDOWN_WAIT
+      && !self.triggers.iter().all(|(_, t)| t.is_finished())
+    {
+      std::thread::sleep(Duration::from_millis(50));
+    }
+    self
+      .triggers
+      .iter()
+      .enumerate()
+      .filter(|(_, (_, t))| !t.is_finished())
+      .for_each(|(index, _)| {
+        warn!(
+          "{}: Trigger {} did not shut down in time.",
+          self.name, index
+        )
+      });
+    info!("{}: Stopped.", self.name);
+  }
+}
+
 // It's kind of hard to pass self around into a thread.  I can't figure it out.
 // So let's just create a new type that doesn't have triggers (because we don't
 // need them), and we'll copy everything there and use that.
@@ -224,59 +407,163 @@ impl Sytter {
 pub struct ThreadedSytter {
   pub name: String,
   pub description: String,
+  pub condition_mode: ConditionMode,
   pub conditions: Vec<Box<dyn Condition>>,
-  pub executors: Vec<Box<dyn Executor>>,
+  pub executors: Vec<ExecutorStep>,
   pub failures: Vec<Box<dyn Failure>>,
 }
 
 impl ThreadedSytter {
-  fn trigger_execute_on_message(
+  pub fn trigger_execute_on_message(
     &self,
     config: &Config,
-    _send_to_trigger: &SyncSender<String>,
-    receive_from_trigger: &Receiver<String>,
+    mut context: RunContext,
   ) -> Result<(), AppError> {
-    info!("{}: Waiting for message from trigger...", self.name);
-    let trigger_message = receive_from_trigger.recv();
-    debug!("{}: Got trigger message: {:?}", self.name, trigger_message);
+    // Everything logged during the run carries its ID.
+    let _span =
+      info_span!("run", sytter = self.name, run_id = context.run_id).entered();
+    debug!("{}: Got trigger message: {:?}", self.name, context.event);
+    // The trigger's data goes in the run's namespace, where it can't leak into
+    // other runs.  It is dropped once the run is over.
+    let run_scope = StateScope::Run(context.run_id.clone());
+    trigger_event_envs(&context.event)
+      .into_iter()
+      .for_each(|(key, value)| {
+        State::set_variable(run_scope.clone(), SytterVariable { key, value })
+      });
+    let (outcome, errors) = match conditions_check(
+      &self.condition_mode,
+      &self.conditions,
+      config,
+      &context,
+    ) {
+      Ok(true) => {
+        debug!("{}: Conditional is true, executing...", self.name);
+        (
+          RunOutcome::Succeeded,
+          self.executors_run(config, &mut context),
+        )
+      }
+      Ok(false) => {
+        debug!("{}: Conditional is false.", self.name);
+        (RunOutcome::ConditionFalse, vec![])
+      }
+      Err(e) => (
+        RunOutcome::Failed,
+        vec![AppError::ConditionCheckError(Box::new(e))],
+      ),
+    };
+    // A cancelled run was stopped on purpose, so its errors aren't failures.
+    let result = if context.cancel.is_cancelled() {
+      info!("{}: Run cancelled.", self.name);
+      Ok(())
+    } else {
+      // Sytters following this one hear about it before the failure handlers
+      // run, so a slow handler doesn't hold them up.
+      // Whatever the event holds is let go once this run is over, not once
+      // they're done with it.
+      run_report_publish(RunReport {
+        context: RunContext {
+          event: TriggerEvent {
+            hold: None,
+            ..context.event.clone()
+          },
+          ..context.clone()
+        },
+        outcome: match errors.is_empty() {
+          true => outcome,
+          false => RunOutcome::Failed,
+        },
+        error: errors.first().map(|e| format!("{:?}", e)),
+      });
+      // A handler failing for one step doesn't keep the later steps from
+      // being reported.
+      errors
+        .into_iter()
+        .map(|e| {
+          error!("{}: Executor failed: {:?}", self.name, e);
+          self.failures_dispatch(config, &context, &e)
+        })
+        .collect::<Vec<Result<(), AppError>>>()
+        .into_iter()
+        .collect::<Result<(), AppError>>()
+        .inspect_err(|e| {
+          error!("{}: Failure handlers failed: {:?}", self.name, e)
+        })
+    };
+    State::clear_scope(&run_scope);
+    result
+  }
+
+  // Every handler gets a chance to run even if an earlier one failed.  A
+  // notification handler that can't reach its mail server shouldn't prevent a
+  // logging handler from recording the failure.
+  fn failures_dispatch(
+    &self,
+    config: &Config,
+    context: &RunContext,
+    error: &AppError,
+  ) -> Result<(), AppError> {
+    if self.failures.is_empty() {
+      return Err(AppError::SytterMissingComponentError(
+        "No failures!".to_string(),
+      ));
+    }
     self
-      .conditions
-      .get(0)
-      .ok_or(AppError::SytterMissingComponentError(
-        "No conditions!".into(),
-      ))?
-      .check_condition(&config)
-      .and_then(|cond| {
-        if cond {
-          debug!("{}: Conditional is true, executing...", self.name);
-          self
-            .executors
-            .get(0)
-            .ok_or(AppError::SytterMissingComponentError(
-              format!("No executors in {}!", self.name).into(),
-            ))?
-            .execute(config)
-            .inspect(|_| debug!("{}: Execution successful.", self.name))
-        } else {
-          debug!("{}: Conditional is false.", self.name);
-          // Log that the condition fell through.
-          Ok(())
-        }
-      })
-      .or_else(|e| {
-        error!("{}: Executor failed: {:?}", self.name, e);
-        self
-          .failures
-          .get(0)
-          .ok_or(AppError::SytterMissingComponentError(
-            "No failures!".to_string(),
-          ))?
-          .execute(&config, e)
+      .failures
+      .iter()
+      .enumerate()
+      .map(|(index, failure)| {
+        failure
+          .execute(config, context, error)
           .inspect(|_| {
-            info!("{}: Failure handler successful!", self.name);
+            info!("{}: Failure handler {} successful!", self.name, index);
+          })
+          .inspect_err(|e| {
+            error!("{}: Failure handler {} failed: {:?}", self.name, index, e);
           })
       })
-      .map(|_| ())
-      .inspect_err(|e| error!("It blowed up bad in condition! {:?}", e))
+      .collect::<Vec<Result<(), AppError>>>()
+      .into_iter()
+      .collect()
+  }
+
+  // Steps marked "continue" still have their errors collected, so the failure
+  // handlers hear about every step that failed and not just the last one.
+  // Each step's result is recorded in the context before the next step runs.
+  fn executors_run(
+    &self,
+    config: &Config,
+    context: &mut RunContext,
+  ) -> Vec<AppError> {
+    match self.executors.iter().enumerate().try_fold(
+      vec![],
+      |mut errors, (index, step)| {
+        if context.cancel.is_cancelled() {
+          return ControlFlow::Break(errors);
+        }
+        let result = step.executor.execute(config, context);
+        context.step_results.push(StepResult::new(index, &result));
+        match result {
+          Ok(()) => {
+            debug!("{}: Executor step {} successful.", self.name, index);
+            ControlFlow::Continue(errors)
+          }
+          Err(e) => {
+            error!(
+              "{}: Executor step {} failed ({:?}): {:?}",
+              self.name, index, step.on_error, e,
+            );
+            errors.push(AppError::ExecutorStepError(index, Box::new(e)));
+            match step.on_error {
+              ExecutorErrorPolicy::Stop => ControlFlow::Break(errors),
+              ExecutorErrorPolicy::Continue => ControlFlow::Continue(errors),
+            }
+          }
+        }
+      },
+    ) {
+      ControlFlow::Continue(errors) | ControlFlow::Break(errors) => errors,
+    }
   }
 }
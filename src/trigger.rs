@@ -1,12 +1,117 @@
-use crate::error::AppError;
+use crate::{error::AppError, run::CancelToken};
 use core::fmt::Debug;
-use std::sync::mpsc::{Receiver, SyncSender};
+use serde::{Deserialize, Serialize};
+use std::{
+  any::Any,
+  collections::BTreeMap,
+  sync::{
+    mpsc::{Receiver, SyncSender},
+    Arc,
+  },
+  time::SystemTime,
+};
 
 #[typetag::serde(tag = "type")]
 pub trait Trigger: Debug + Sync + Send {
   fn trigger_await(
     &mut self,
-    send_to_sytter: SyncSender<String>,
-    receive_from_sytter: Receiver<String>,
+    send_to_sytter: SyncSender<TriggerEvent>,
+    receive_from_sytter: Receiver<TriggerControl>,
   ) -> Result<(), AppError>;
 }
+
+/// What the Sytter can tell a running trigger.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TriggerControl {
+  /// Stop listening, clean up anything registered with the OS, and return.
+  Shutdown,
+}
+
+/// Block until the Sytter asks the trigger to shut down.  A Sytter that has
+/// gone away entirely counts as asking.
+pub fn trigger_shutdown_await(receive_from_sytter: &Receiver<TriggerControl>) {
+  match receive_from_sytter.recv() {
+    Ok(TriggerControl::Shutdown) | Err(_) => (),
+  }
+}
+
+/// Keeps something alive until every copy of its event is gone, which is once
+/// the event's run is over (or the event was skipped or dropped).  Triggers use
+/// this to hold off the OS until the run is done, such as with a lock that
+/// delays sleep until it is closed.
+#[derive(Clone)]
+pub struct TriggerEventHold(Arc<dyn Any + Send + Sync>);
+
+impl Debug for TriggerEventHold {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("TriggerEventHold")
+  }
+}
+
+impl PartialEq for TriggerEventHold {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+/// What a trigger saw when it fired.  Fields are kept as strings because they
+/// end up as environment variables for shell components, which are prefixed
+/// with "sytter_" (so "power_event" becomes "sytter_power_event").
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TriggerEvent {
+  pub kind: String,
+  pub timestamp: SystemTime,
+  // Which of the Sytter's [[triggers]] fired.  Triggers don't know their own
+  // position, so the Sytter fills this in when it receives the event.
+  pub trigger_index: usize,
+  pub fields: BTreeMap<String, String>,
+  #[serde(skip)]
+  pub hold: Option<TriggerEventHold>,
+  // Lets the trigger cancel the run it starts, such as when the run has a
+  // deadline.
+  #[serde(skip)]
+  pub cancel: Option<CancelToken>,
+}
+
+impl TriggerEvent {
+  pub fn new(kind: &str) -> Self {
+    TriggerEvent {
+      kind: kind.to_string(),
+      timestamp: SystemTime::now(),
+      trigger_index: 0,
+      fields: BTreeMap::new(),
+      hold: None,
+      cancel: None,
+    }
+  }
+
+  pub fn field(mut self, key: &str, value: impl ToString) -> Self {
+    self.fields.insert(key.to_string(), value.to_string());
+    self
+  }
+
+  pub fn fields(mut self, fields: BTreeMap<String, String>) -> Self {
+    self.fields.extend(fields);
+    self
+  }
+
+  pub fn hold(mut self, held: impl Any + Send + Sync) -> Self {
+    self.hold = Some(TriggerEventHold(Arc::new(held)));
+    self
+  }
+
+  pub fn cancel(mut self, cancel: CancelToken) -> Self {
+    self.cancel = Some(cancel);
+    self
+  }
+
+  /// Seconds since the Unix epoch.  Clocks set before 1970 get 0 rather than an
+  /// error, since nothing downstream could do anything useful with one.
+  pub fn timestamp_seconds(&self) -> u64 {
+    self
+      .timestamp
+      .duration_since(SystemTime::UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or(0)
+  }
+}
@@ -0,0 +1,178 @@
+// Sytter trigger integration test.
+//
+// An upstream Sytter is run through a webhook, and told whether to succeed,
+// fail, or have its condition come out false.  The fixture's other Sytters
+// follow it, one for any outcome and one for failures only.  Sytters that
+// follow each other in a circle are checked separately.
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Deserialize)]
+struct HealthResponse {
+  #[serde(default)]
+  problems: HashMap<String, Vec<String>>,
+}
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect()
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+#[test]
+fn test_sytter_trigger_outcomes() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_chain_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/chain");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 39080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The triggers subscribe on their own threads.
+  thread::sleep(Duration::from_millis(500));
+
+  let client = reqwest::blocking::Client::new();
+  let run = |code: &str| {
+    let status = client
+      .post(format!(
+        "http://localhost:{}/hooks/test_chain_upstream/run",
+        test_port
+      ))
+      .body(format!("{{\"code\": \"{}\"}}", code))
+      .send()
+      .expect("Failed to send webhook")
+      .status();
+    assert_eq!(status.as_u16(), 202, "Webhook for {} was refused", code);
+  };
+
+  // Each waits for the last, since the followers run independently.
+  run("0");
+  wait_for_lines(&output_file, 1);
+  run("3");
+  wait_for_lines(&output_file, 3);
+  run("skip");
+  let lines = wait_for_lines(&output_file, 4);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines[0], "any succeeded - 0");
+  let mut failed = lines[1..3].to_vec();
+  failed.sort();
+  assert_eq!(
+    failed,
+    ["any failed 3 3", "failed test_chain_upstream failed 3"]
+  );
+  assert_eq!(lines[3], "any condition-false - skip");
+
+  // Nothing else should have fired.
+  thread::sleep(Duration::from_millis(500));
+  assert_eq!(output_lines(&output_file).len(), 4);
+
+  let _ = fs::remove_file(&output_file);
+}
+
+#[test]
+fn test_chain_trigger_circle_rejected() {
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 46080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(manifest_dir.join("tests/fixtures/chain_cycle"))
+    .arg("--log-level")
+    .arg("debug")
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let mut problems = vec![];
+  while start.elapsed() < Duration::from_secs(5) && problems.is_empty() {
+    problems = reqwest::blocking::get(&health_url)
+      .and_then(|r| r.json::<HealthResponse>())
+      .map(|h| h.problems.into_values().flatten().collect::<Vec<String>>())
+      .unwrap_or_default();
+    thread::sleep(Duration::from_millis(100));
+  }
+  // Only one of the three should fail, so give the others time to be reported
+  // too if they were going to be.
+  thread::sleep(Duration::from_millis(500));
+  let problems: Vec<String> = reqwest::blocking::get(&health_url)
+    .and_then(|r| r.json::<HealthResponse>())
+    .map(|h| h.problems.into_values().flatten().collect())
+    .unwrap_or_default();
+  println!("Problems: {:?}", problems);
+  assert_eq!(problems.len(), 1);
+  assert!(problems[0].contains("would make Sytters set each other off forever"));
+}
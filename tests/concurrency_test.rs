@@ -0,0 +1,143 @@
+// Concurrency policy integration test.
+//
+// Three sytters fire every second but take three seconds to run, so events
+// always arrive while a run is in progress.  Each uses a different policy, and
+// the output file and the /status counters show what happened to the extra
+// events.
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct SytterStatus {
+  #[allow(dead_code)]
+  error: Option<String>,
+  #[allow(dead_code)]
+  triggers: IgnoredAny,
+  // Everything else is a counter.
+  #[serde(flatten)]
+  counters: HashMap<String, u64>,
+}
+
+#[test]
+fn test_concurrency_policies() {
+  let temp_dir = std::env::temp_dir();
+  let output_file = temp_dir.join(format!(
+    "sytter_concurrency_test_{}.txt",
+    std::process::id()
+  ));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/concurrency");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 23080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not return success within {} seconds",
+    max_wait.as_secs()
+  );
+
+  thread::sleep(Duration::from_secs(8));
+
+  let statuses: HashMap<String, SytterStatus> =
+    reqwest::blocking::get(format!("http://localhost:{}/status", test_port))
+      .and_then(|r| r.json())
+      .expect("Failed to read status");
+  println!("Statuses: {:?}", statuses);
+  let count = |sytter: &str, counter: &str| -> u64 {
+    statuses
+      .get(&format!("test_concurrency_{}", sytter))
+      .and_then(|s| s.counters.get(counter))
+      .copied()
+      .unwrap_or(0)
+  };
+
+  let contents =
+    fs::read_to_string(&output_file).expect("Failed to read output file");
+  println!("Output contents:\n{}", contents);
+  let lines: Vec<&str> = contents.lines().collect();
+  let occurrences = |line: &str| lines.iter().filter(|l| **l == line).count();
+
+  assert!(
+    !lines.iter().any(|l| l.starts_with("FAILURE")),
+    "No failure handlers should have run, including for cancelled runs"
+  );
+
+  // One run at a time, with one event waiting and the rest dropped.
+  assert!(occurrences("queue-end") >= 1, "Queued runs should complete");
+  assert!(count("queue", "events_queued") >= 1);
+  assert!(count("queue", "events_dropped") >= 1);
+  assert_eq!(count("queue", "events_skipped"), 0);
+
+  // Events during a run are ignored.
+  assert!(
+    occurrences("skip-end") >= 1,
+    "Skipping should not stop runs"
+  );
+  assert!(count("skip", "events_skipped") >= 1);
+  assert_eq!(count("skip", "events_queued"), 0);
+
+  // Each event cancels the run before it, so no run lives to the end.
+  assert!(
+    occurrences("replace-start") >= 2,
+    "Replacing runs should start"
+  );
+  assert_eq!(
+    occurrences("replace-end"),
+    0,
+    "Replaced runs should be cancelled before they finish"
+  );
+  assert!(count("replace", "runs_replaced") >= 1);
+
+  let _ = fs::remove_file(&output_file);
+}
@@ -0,0 +1,94 @@
+// Condition combinator integration test.
+//
+// Two sytters share a cron trigger and an output file.  One of them nests
+// "all", "any", and "not" groups that evaluate to true, and the other nests
+// groups that evaluate to false.  Only the former should ever execute.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[test]
+fn test_condition_combinators() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_conditions_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/conditions");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 19080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not return success within {} seconds",
+    max_wait.as_secs()
+  );
+
+  // The cron fires every 2 seconds, so this gives us a couple of runs.
+  thread::sleep(Duration::from_secs(5));
+
+  let contents =
+    fs::read_to_string(&output_file).expect("Failed to read output file");
+  println!("Output contents:\n{}", contents);
+  let lines: Vec<&str> = contents.lines().collect();
+  assert!(
+    lines.contains(&"matching"),
+    "The sytter with true conditions should have executed"
+  );
+  assert!(
+    !lines.contains(&"non-matching"),
+    "The sytter with false conditions should not have executed"
+  );
+  assert!(
+    !lines.contains(&"FAILURE"),
+    "No failure handlers should have run"
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
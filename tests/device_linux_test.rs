@@ -0,0 +1,140 @@
+// Linux device connection trigger integration test.
+//
+// Real devices can't be plugged in from a test, but root can ask the kernel to
+// announce one again by writing to its uevent file in sysfs.  The loopback
+// interface is used, since every machine has one.  Everything else listening
+// for uevents on the machine hears these too, such as udevd and network
+// managers, so the test only runs when SYTTER_TEST_SYSFS is set and sysfs is
+// writable.
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LOOPBACK_UEVENT: &str = "/sys/class/net/lo/uevent";
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  let mut lines: Vec<String> = fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .filter(|l| l.contains(" lo "))
+    .map(|l| l.to_string())
+    .collect();
+  // The two Sytters run independently, so their order isn't fixed.
+  lines.sort();
+  lines
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+#[test]
+fn test_device_trigger_linux_uevents() {
+  if std::env::var_os("SYTTER_TEST_SYSFS").is_none() {
+    println!("Skipping, set SYTTER_TEST_SYSFS=1 to announce loopback uevents.");
+    return;
+  }
+  if fs::write(LOOPBACK_UEVENT, "change").is_err() {
+    println!("Skipping, {} is not writable.", LOOPBACK_UEVENT);
+    return;
+  }
+  let temp_dir = std::env::temp_dir();
+  let output_file = temp_dir.join(format!(
+    "sytter_device_linux_test_{}.txt",
+    std::process::id()
+  ));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/device_linux");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 29080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The triggers open their sockets on their own threads.
+  thread::sleep(Duration::from_millis(500));
+
+  fs::write(LOOPBACK_UEVENT, "add").expect("Failed to announce an add");
+  let lines = wait_for_lines(&output_file, 2);
+  assert_eq!(
+    lines,
+    vec![
+      "subsystem Add net lo /sys/devices/virtual/net/lo",
+      "types Add net lo /sys/devices/virtual/net/lo",
+    ]
+  );
+
+  // Anything other than adds and removes is ignored.
+  fs::write(LOOPBACK_UEVENT, "change").expect("Failed to announce a change");
+  fs::write(LOOPBACK_UEVENT, "remove").expect("Failed to announce a remove");
+  let lines = wait_for_lines(&output_file, 4);
+  assert_eq!(
+    lines,
+    vec![
+      "subsystem Add net lo /sys/devices/virtual/net/lo",
+      "subsystem Remove net lo /sys/devices/virtual/net/lo",
+      "types Add net lo /sys/devices/virtual/net/lo",
+      "types Remove net lo /sys/devices/virtual/net/lo",
+    ]
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
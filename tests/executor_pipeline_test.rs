@@ -0,0 +1,103 @@
+// Executor pipeline integration test.
+//
+// The fixture has four executors.  The first fails but is marked "continue",
+// the third fails and is marked "stop", so the fourth must never run.  Both
+// failure handlers must run once for each failed step, and must be told which
+// step failed and how.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[test]
+fn test_executor_pipeline() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_pipeline_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path =
+    manifest_dir.join("tests/fixtures/test_executor_pipeline.toml");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 20080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not return success within {} seconds",
+    max_wait.as_secs()
+  );
+
+  // The cron fires every 2 seconds.  Wait for the first run only, so the
+  // output describes exactly one pass through the pipeline.
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) && !output_file.exists() {
+    thread::sleep(Duration::from_millis(100));
+  }
+  thread::sleep(Duration::from_millis(500));
+
+  let contents =
+    fs::read_to_string(&output_file).expect("Failed to read output file");
+  println!("Output contents:\n{}", contents);
+  let lines: Vec<&str> = contents.lines().take(7).collect();
+  assert_eq!(
+    lines,
+    vec![
+      "step-0",
+      "step-1",
+      "step-2",
+      "FAILURE executor 0 1",
+      "REPORT Kind: ShellExecError",
+      "FAILURE executor 2 1",
+      "REPORT Kind: ShellExecError",
+    ],
+    "Steps should run in order, stop at the failed 'stop' step, and report \
+     each failed step to every failure handler"
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
@@ -0,0 +1,125 @@
+// File watch trigger integration test.
+//
+// Sytter is started in a scratch directory so the fixture can watch a relative
+// drop directory.  Files are then created, written, renamed, and deleted in it.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.trim_end().to_string())
+    .collect()
+}
+
+// Gives the settle delay time to pass and the run time to finish.
+fn settle_wait() {
+  thread::sleep(Duration::from_millis(1000));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_file_watch_trigger() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_file_watch_test_{}.txt", std::process::id()));
+  let work_dir =
+    temp_dir.join(format!("sytter_file_watch_test_{}", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+  let _ = fs::remove_dir_all(&work_dir);
+  let drop_dir = work_dir.join("drop");
+  fs::create_dir_all(&drop_dir).expect("Failed to create drop directory");
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/test_file_watch.toml");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 27080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .current_dir(&work_dir)
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The trigger sets up its watch on its own thread.
+  thread::sleep(Duration::from_millis(500));
+
+  // Written in several pieces, which should settle into a single event.
+  let csv = drop_dir.join("a.csv");
+  for i in 0..5 {
+    let mut contents = fs::read_to_string(&csv).unwrap_or_default();
+    contents.push_str(&format!("{}\n", i));
+    fs::write(&csv, contents).expect("Failed to write CSV");
+    thread::sleep(Duration::from_millis(50));
+  }
+  fs::write(drop_dir.join("ignored.txt"), "not a CSV")
+    .expect("Failed to write text file");
+  settle_wait();
+  assert_eq!(output_lines(&output_file), vec!["Create drop/a.csv"]);
+
+  fs::rename(&csv, drop_dir.join("b.csv")).expect("Failed to rename CSV");
+  settle_wait();
+  fs::remove_file(drop_dir.join("b.csv")).expect("Failed to delete CSV");
+  settle_wait();
+
+  let lines = output_lines(&output_file);
+  println!("Output: {:?}", lines);
+  assert_eq!(
+    lines,
+    vec![
+      "Create drop/a.csv",
+      "Move drop/b.csv drop/a.csv",
+      "Delete drop/b.csv",
+    ]
+  );
+
+  let _ = fs::remove_file(&output_file);
+  let _ = fs::remove_dir_all(&work_dir);
+}
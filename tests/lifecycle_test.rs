@@ -0,0 +1,125 @@
+// Lifecycle trigger integration test.
+//
+// Startup fires once its delay is up.  On SIGTERM, one Sytter's Shutdown run
+// finishes, and another's is cancelled once its deadline passes, after which
+// Sytter exits on its own.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect()
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+#[test]
+fn test_lifecycle_startup_and_shutdown() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_lifecycle_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/lifecycle");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 38080 + (std::process::id() % 1000);
+
+  let started = Instant::now();
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .arg("--shutdown-grace-period")
+    .arg("1s")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let mut process = SytterProcess::new(child);
+
+  let lines = wait_for_lines(&output_file, 1);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines, ["0 Startup"]);
+  assert!(
+    started.elapsed() >= Duration::from_secs(1),
+    "Startup should wait for its delay, fired after {:?}",
+    started.elapsed(),
+  );
+
+  Command::new("kill")
+    .arg("-TERM")
+    .arg(process.child.id().to_string())
+    .status()
+    .expect("Failed to send SIGTERM");
+  let terminated = Instant::now();
+  let status = loop {
+    if let Some(status) = process.child.try_wait().unwrap() {
+      break status;
+    }
+    assert!(
+      terminated.elapsed() < Duration::from_secs(15),
+      "Sytter did not exit after SIGTERM"
+    );
+    thread::sleep(Duration::from_millis(100));
+  };
+  let elapsed = terminated.elapsed();
+  assert!(
+    status.success(),
+    "Sytter should exit cleanly, got {}",
+    status
+  );
+  assert!(
+    elapsed < Duration::from_secs(10),
+    "Sytter should not wait past the deadline, took {:?}",
+    elapsed,
+  );
+
+  let lines = output_lines(&output_file);
+  println!("Output: {:?}", lines);
+  let mut shutdown = lines[1..].to_vec();
+  shutdown.sort();
+  assert_eq!(
+    shutdown,
+    ["1 Shutdown", "slow Shutdown"],
+    "Both Shutdown runs should start, and the slow one should be cancelled \
+     without running the failure handlers",
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
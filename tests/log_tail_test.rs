@@ -0,0 +1,200 @@
+// Log tail trigger integration test.
+//
+// Sytter is run from a directory of its own, where the fixture's log and
+// offset files are.  The log is written to, rotated, and truncated the way
+// loggers and logrotate do, and Sytter is restarted to show it picks up where
+// it left off.
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Deserialize)]
+struct HealthResponse {
+  #[serde(default)]
+  problems: HashMap<String, Vec<String>>,
+}
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect()
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+fn append(path: &Path, text: &str) {
+  OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path)
+    .and_then(|mut f| f.write_all(text.as_bytes()))
+    .expect("Failed to write to the log");
+}
+
+fn sytter_start(
+  work_dir: &Path,
+  output_file: &PathBuf,
+  test_port: u32,
+) -> SytterProcess {
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(manifest_dir.join("tests/fixtures/log_tail"))
+    .arg("--log-level")
+    .arg("debug")
+    .current_dir(work_dir)
+    .env("SYTTER_TEST_OUTPUT", output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The trigger opens the log on its own thread.
+  thread::sleep(Duration::from_millis(500));
+  process
+}
+
+#[test]
+fn test_log_tail_trigger_rotation_and_restart() {
+  let temp_dir = std::env::temp_dir();
+  let work_dir =
+    temp_dir.join(format!("sytter_log_tail_test_{}", std::process::id()));
+  let _ = fs::remove_dir_all(&work_dir);
+  fs::create_dir_all(&work_dir).expect("Failed to make a work directory");
+  let output_file = work_dir.join("output.txt");
+  let log = work_dir.join("app.log");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 36080 + (std::process::id() % 1000);
+
+  // What is in the log before Sytter first starts is old news.
+  append(&log, "authentication failed for user=old from 10.0.0.0\n");
+  let process = sytter_start(&work_dir, &output_file, test_port);
+
+  append(&log, "authentication failed for user=alice from 10.0.0.1\n");
+  append(&log, "authentication succeeded for user=alice\n");
+  // A line is only read once it's finished.
+  append(&log, "authentication failed for user=bob");
+  thread::sleep(Duration::from_millis(300));
+  append(&log, " from 10.0.0.2\n");
+  let lines = wait_for_lines(&output_file, 2);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines, ["alice 10.0.0.1", "bob 10.0.0.2"]);
+
+  // Rotated by renaming, with a last line going to the old file.
+  fs::rename(&log, work_dir.join("app.log.1")).expect("Failed to rotate");
+  append(
+    &work_dir.join("app.log.1"),
+    "authentication failed for user=carol from 10.0.0.3\n",
+  );
+  thread::sleep(Duration::from_millis(300));
+  append(&log, "authentication failed for user=dave from 10.0.0.4\n");
+  let lines = wait_for_lines(&output_file, 4);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines[2..], ["carol 10.0.0.3", "dave 10.0.0.4"]);
+
+  // Truncated in place, and written to again.
+  fs::write(&log, "authentication failed for user=e from 10.0.0.5\n")
+    .expect("Failed to truncate");
+  let lines = wait_for_lines(&output_file, 5);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines[4..], ["e 10.0.0.5"]);
+
+  // What is written while Sytter is down is picked up when it's back, and
+  // nothing is fired twice.
+  drop(process);
+  append(&log, "authentication failed for user=frank from 10.0.0.6\n");
+  let _process = sytter_start(&work_dir, &output_file, test_port);
+  let lines = wait_for_lines(&output_file, 6);
+  thread::sleep(Duration::from_millis(300));
+  println!("Output: {:?}", lines);
+  assert_eq!(lines[5..], ["frank 10.0.0.6"]);
+  assert_eq!(output_lines(&output_file).len(), 6);
+
+  let _ = fs::remove_dir_all(&work_dir);
+}
+
+#[test]
+fn test_log_tail_trigger_zero_interval_rejected() {
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 45080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(manifest_dir.join("tests/fixtures/log_tail_zero_interval"))
+    .arg("--log-level")
+    .arg("debug")
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  // The Sytter fails to load, so the server never reports healthy.
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let mut problems = vec![];
+  while start.elapsed() < Duration::from_secs(5) && problems.is_empty() {
+    problems = reqwest::blocking::get(&health_url)
+      .and_then(|r| r.json::<HealthResponse>())
+      .map(|h| h.problems.into_values().flatten().collect())
+      .unwrap_or_default();
+    thread::sleep(Duration::from_millis(100));
+  }
+  println!("Problems: {:?}", problems);
+  assert!(problems.iter().any(|p| p
+    .contains("Field 'interval' in log-tail Trigger must be more than zero.")));
+}
@@ -0,0 +1,180 @@
+// Network trigger integration test.
+//
+// A veth pair is made for the test, so its changes don't disturb anything
+// else.  Only one end matches the fixture's interfaces, and the other end is
+// there so it can come up.  The test is skipped without permission to make
+// links.
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+/// Helper to delete the veth pair on drop, which takes both ends with it.
+struct VethPair {
+  name: String,
+}
+
+impl Drop for VethPair {
+  fn drop(&mut self) {
+    let _ = Command::new("ip")
+      .args(["link", "del", &self.name])
+      .stderr(Stdio::null())
+      .status();
+  }
+}
+
+fn ip(args: &[&str]) -> bool {
+  Command::new("ip")
+    .args(args)
+    .status()
+    .is_ok_and(|s| s.success())
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    // Link local IPv6 addresses come and go on their own.
+    .filter(|l| !l.contains(" fe80:"))
+    .map(|l| l.trim_end().to_string())
+    .collect()
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+#[test]
+fn test_network_trigger_veth_changes() {
+  let interface = format!("sytnet{}", std::process::id() % 10000);
+  let peer = format!("sytpeer{}", std::process::id() % 10000);
+  if !ip(&[
+    "link", "add", &interface, "type", "veth", "peer", "name", &peer,
+  ]) {
+    println!("Skipping, could not make a veth pair.");
+    return;
+  }
+  let _veth = VethPair {
+    name: interface.clone(),
+  };
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_network_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/test_network.toml");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 33080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The trigger opens its socket on its own thread.
+  thread::sleep(Duration::from_millis(500));
+
+  // The peer coming up isn't reported, but is what lets the interface pass
+  // traffic.
+  assert!(ip(&["link", "set", &peer, "up"]));
+  assert!(ip(&["link", "set", &interface, "up"]));
+  let lines = wait_for_lines(&output_file, 1);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines, vec![format!("LinkUp {}", interface)]);
+
+  assert!(ip(&["addr", "add", "10.231.0.1/24", "dev", &interface]));
+  let route = [
+    "default",
+    "via",
+    "10.231.0.2",
+    "dev",
+    &interface,
+    "metric",
+    "4242",
+  ];
+  assert!(ip(&[&["route", "add"][..], &route].concat()));
+  assert!(ip(&[&["route", "del"][..], &route].concat()));
+  let lines = wait_for_lines(&output_file, 4);
+  println!("Output: {:?}", lines);
+  assert_eq!(
+    lines[1..],
+    [
+      format!("AddressAdd {} 10.231.0.1", interface),
+      format!("DefaultRouteAdd {}  10.231.0.2", interface),
+      format!("DefaultRouteRemove {}  10.231.0.2", interface),
+    ]
+  );
+
+  // The kernel drops the route quietly when the link goes down, and the
+  // address goes with the link.
+  assert!(ip(&[&["route", "add"][..], &route].concat()));
+  assert!(ip(&["link", "set", &interface, "down"]));
+  assert!(ip(&["link", "del", &interface]));
+  let lines = wait_for_lines(&output_file, 8);
+  println!("Output: {:?}", lines);
+  assert_eq!(
+    lines[4..],
+    [
+      format!("DefaultRouteAdd {}  10.231.0.2", interface),
+      format!("LinkDown {}", interface),
+      format!("DefaultRouteRemove {}  10.231.0.2", interface),
+      format!("AddressRemove {} 10.231.0.1", interface),
+    ]
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
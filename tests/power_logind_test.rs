@@ -0,0 +1,187 @@
+// Linux power trigger integration test.
+//
+// A private dbus-daemon stands in for the system bus, and a mock logind on it
+// hands out delay locks and announces sleep and wake.  The lock taken for sleep
+// must be held until the Sleep run is over.
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::io::Read;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+
+/// Hands out delay locks as one end of a socket pair, keeping the other end to
+/// see when the lock is let go.
+struct MockLogind {
+  locks: Arc<Mutex<Vec<UnixStream>>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Manager")]
+impl MockLogind {
+  fn inhibit(
+    &self,
+    what: &str,
+    _who: &str,
+    _why: &str,
+    mode: &str,
+  ) -> zbus::fdo::Result<zbus::zvariant::OwnedFd> {
+    assert_eq!((what, mode), ("sleep", "delay"));
+    let (ours, theirs) = UnixStream::pair()
+      .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+    self.locks.lock().unwrap().push(ours);
+    Ok(std::os::fd::OwnedFd::from(theirs).into())
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect()
+}
+
+fn wait_for(what: &str, max_wait: Duration, check: impl Fn() -> bool) {
+  let start = Instant::now();
+  while !check() {
+    assert!(
+      start.elapsed() < max_wait,
+      "Timed out after {:?} waiting for {}",
+      max_wait,
+      what,
+    );
+    thread::sleep(Duration::from_millis(50));
+  }
+}
+
+fn prepare_for_sleep_emit(
+  connection: &zbus::blocking::Connection,
+  start: bool,
+) {
+  connection
+    .emit_signal(
+      None::<&str>,
+      LOGIND_PATH,
+      "org.freedesktop.login1.Manager",
+      "PrepareForSleep",
+      &(start,),
+    )
+    .expect("Failed to emit PrepareForSleep");
+}
+
+#[test]
+fn test_power_trigger_logind() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_power_test_{}.txt", std::process::id()));
+  let bus_socket =
+    temp_dir.join(format!("sytter_power_test_{}.sock", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+  let _ = fs::remove_file(&bus_socket);
+  let bus_address = format!("unix:path={}", bus_socket.display());
+
+  let bus = Command::new("dbus-daemon")
+    .arg("--session")
+    .arg("--nofork")
+    .arg(format!("--address={}", bus_address))
+    .stderr(Stdio::null())
+    .spawn()
+    .expect("Failed to start dbus-daemon");
+  let _bus = SytterProcess::new(bus);
+  wait_for("the bus", Duration::from_secs(5), || {
+    Path::new(&bus_socket).exists()
+  });
+
+  let locks = Arc::new(Mutex::new(vec![]));
+  let logind =
+    zbus::blocking::connection::Builder::address(bus_address.as_str())
+      .and_then(|b| b.name("org.freedesktop.login1"))
+      .and_then(|b| {
+        b.serve_at(
+          LOGIND_PATH,
+          MockLogind {
+            locks: locks.clone(),
+          },
+        )
+      })
+      .and_then(|b| b.build())
+      .expect("Failed to start mock logind");
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/test_power_logind.toml");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 28080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .env("DBUS_SYSTEM_BUS_ADDRESS", &bus_address)
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  wait_for("a sleep delay lock", Duration::from_secs(10), || {
+    locks.lock().unwrap().len() == 1
+  });
+
+  prepare_for_sleep_emit(&logind, true);
+  let mut first_lock = locks.lock().unwrap()[0]
+    .try_clone()
+    .expect("Failed to clone lock");
+  first_lock
+    .set_read_timeout(Some(Duration::from_secs(10)))
+    .unwrap();
+  let read = first_lock.read(&mut [0; 1]);
+  assert!(
+    matches!(read, Ok(0)),
+    "The sleep delay lock should be closed, got {:?}",
+    read,
+  );
+  assert_eq!(
+    output_lines(&output_file),
+    vec!["Sleep started", "Sleep done"],
+    "The lock should only be let go once the Sleep run is over",
+  );
+
+  prepare_for_sleep_emit(&logind, false);
+  wait_for("the Wake run", Duration::from_secs(5), || {
+    output_lines(&output_file).contains(&"Wake done".to_string())
+  });
+  assert_eq!(
+    locks.lock().unwrap().len(),
+    2,
+    "A new lock should be taken for the next sleep",
+  );
+
+  let _ = fs::remove_file(&output_file);
+  let _ = fs::remove_file(&bus_socket);
+}
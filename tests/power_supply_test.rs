@@ -0,0 +1,195 @@
+// Power trigger AC and battery integration test.
+//
+// There is no battery to drain in a test, so SYSFS_PATH points Sytter at a
+// made up sysfs with an AC adapter, a laptop battery, and a wireless mouse's
+// battery, which are changed the way the kernel would change them.
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+// Events read at the same time run at the same time, so their order isn't
+// known.
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  let mut lines: Vec<String> = fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect();
+  lines.sort();
+  lines
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+fn supply_write(sysfs: &Path, supply: &str, attributes: &[(&str, &str)]) {
+  let dir = sysfs.join("class/power_supply").join(supply);
+  fs::create_dir_all(&dir).expect("Failed to make a power supply");
+  for (attribute, value) in attributes {
+    fs::write(dir.join(attribute), format!("{}\n", value))
+      .expect("Failed to write a power supply attribute");
+  }
+}
+
+#[test]
+fn test_power_trigger_ac_and_battery() {
+  let temp_dir = std::env::temp_dir();
+  let work_dir =
+    temp_dir.join(format!("sytter_power_supply_test_{}", std::process::id()));
+  let _ = fs::remove_dir_all(&work_dir);
+  let sysfs = work_dir.join("sys");
+  let output_file = work_dir.join("output.txt");
+
+  // Energy is in µWh and power in µW.  The battery holds 50 Wh.
+  supply_write(&sysfs, "AC", &[("type", "Mains"), ("online", "1")]);
+  supply_write(
+    &sysfs,
+    "BAT0",
+    &[
+      ("type", "Battery"),
+      ("status", "Charging"),
+      ("energy_now", "25000000"),
+      ("energy_full", "50000000"),
+      ("power_now", "10000000"),
+    ],
+  );
+  // Running low, but it isn't what powers the machine.
+  supply_write(
+    &sysfs,
+    "hidpp_battery_0",
+    &[
+      ("type", "Battery"),
+      ("scope", "Device"),
+      ("status", "Discharging"),
+      ("capacity", "5"),
+    ],
+  );
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/power_supply");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 43080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("SYSFS_PATH", &sysfs)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The first reading is only something to compare against, so nothing fires
+  // for it.
+  thread::sleep(Duration::from_millis(500));
+  assert_eq!(output_lines(&output_file), Vec::<String>::new());
+
+  // Unplugged, with 2.5 hours left.
+  supply_write(&sysfs, "AC", &[("online", "0")]);
+  supply_write(&sysfs, "BAT0", &[("status", "Discharging")]);
+  let lines = wait_for_lines(&output_file, 2);
+  println!("Output: {:?}", lines);
+  assert_eq!(
+    lines,
+    vec![
+      "AcUnplugged ac=false 50 Discharging tte=9000 threshold= was=",
+      "BatteryStatus ac=false 50 Discharging tte=9000 threshold= \
+       was=Charging",
+    ]
+  );
+
+  // Down past 20%, and then past 10%.
+  supply_write(&sysfs, "BAT0", &[("energy_now", "7500000")]);
+  let lines = wait_for_lines(&output_file, 3);
+  supply_write(&sysfs, "BAT0", &[("energy_now", "2500000")]);
+  let lines = [lines, wait_for_lines(&output_file, 4)].concat();
+  println!("Output: {:?}", lines);
+  assert!(lines.contains(
+    &"BatteryThreshold ac=false 15 Discharging tte=2700 threshold=20falling \
+      was="
+      .to_string()
+  ));
+  assert!(lines.contains(
+    &"BatteryThreshold ac=false 5 Discharging tte=900 threshold=10falling \
+      was="
+      .to_string()
+  ));
+
+  // Plugged back in, and charged past both thresholds between readings, so
+  // only the last one crossed fires.
+  supply_write(&sysfs, "AC", &[("online", "1")]);
+  supply_write(
+    &sysfs,
+    "BAT0",
+    &[("status", "Charging"), ("energy_now", "15000000")],
+  );
+  let lines = wait_for_lines(&output_file, 7);
+  thread::sleep(Duration::from_millis(500));
+  println!("Output: {:?}", lines);
+  assert_eq!(output_lines(&output_file).len(), 7);
+  assert!(lines.contains(
+    &"AcPlugged ac=true 30 Charging tte= threshold= was=".to_string()
+  ));
+  assert!(lines.contains(
+    &"BatteryStatus ac=true 30 Charging tte= threshold= was=Discharging"
+      .to_string()
+  ));
+  assert!(lines.contains(
+    &"BatteryThreshold ac=true 30 Charging tte= threshold=20rising was="
+      .to_string()
+  ));
+
+  let _ = fs::remove_dir_all(&work_dir);
+}
@@ -0,0 +1,174 @@
+// Probe trigger integration test.
+//
+// A TCP port, an HTTP server, and a Unix socket are run by the test, and taken
+// down and brought back up.  The fixture is filled in with their addresses,
+// and should only report changes once two checks in a row agree.
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect()
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+/// Answers every request with whatever status and body are set.
+fn http_serve(listener: TcpListener, response: Arc<Mutex<(u16, String)>>) {
+  thread::spawn(move || {
+    for mut stream in listener.incoming().flatten() {
+      let mut request = [0_u8; 1024];
+      let _ = stream.read(&mut request);
+      let (status, body) = response.lock().unwrap().clone();
+      let _ = write!(
+        stream,
+        "HTTP/1.1 {} Whatever\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body,
+      );
+    }
+  });
+}
+
+#[test]
+fn test_probe_trigger_transitions() {
+  let temp_dir = std::env::temp_dir();
+  let work_dir =
+    temp_dir.join(format!("sytter_probe_test_{}", std::process::id()));
+  let _ = fs::remove_dir_all(&work_dir);
+  fs::create_dir_all(work_dir.join("sytters"))
+    .expect("Failed to make a work directory");
+  let output_file = work_dir.join("output.txt");
+  let socket = work_dir.join("probe.sock");
+
+  // Use random ports to avoid conflicts with other tests or running instances.
+  let test_port = 40080 + (std::process::id() % 1000);
+  let tcp_address =
+    format!("127.0.0.1:{}", 41080 + (std::process::id() % 1000));
+  let http_address =
+    format!("127.0.0.1:{}", 42080 + (std::process::id() % 1000));
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let fixture = fs::read_to_string(
+    manifest_dir.join("tests/fixtures/probe/test_probe.toml"),
+  )
+  .expect("Failed to read the fixture");
+  fs::write(
+    work_dir.join("sytters/test_probe.toml"),
+    fixture
+      .replace("TCP_ADDRESS", &tcp_address)
+      .replace("HTTP_ADDRESS", &http_address),
+  )
+  .expect("Failed to write the fixture");
+
+  let tcp = TcpListener::bind(&tcp_address).expect("Failed to listen on TCP");
+  let response = Arc::new(Mutex::new((200, "ok".to_string())));
+  http_serve(
+    TcpListener::bind(&http_address).expect("Failed to listen on HTTP"),
+    response.clone(),
+  );
+  let unix = UnixListener::bind(&socket).expect("Failed to listen on socket");
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(work_dir.join("sytters"))
+    .arg("--log-level")
+    .arg("debug")
+    .current_dir(&work_dir)
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // Everything is up to begin with, which isn't a change.
+  thread::sleep(Duration::from_millis(500));
+  assert!(output_lines(&output_file).is_empty());
+
+  // Each step waits for the last, since the probes run independently.
+  drop(tcp);
+  wait_for_lines(&output_file, 1);
+  let _tcp = TcpListener::bind(&tcp_address).expect("Failed to listen again");
+  let lines = wait_for_lines(&output_file, 2);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines, ["0 tcp Up Down -", "0 tcp Down Up -"]);
+
+  // A captive portal: the right status, but not the right page.  Going from
+  // one kind of down to another isn't a change.
+  *response.lock().unwrap() = (200, "Please log in".to_string());
+  wait_for_lines(&output_file, 3);
+  *response.lock().unwrap() = (302, String::new());
+  thread::sleep(Duration::from_millis(500));
+  *response.lock().unwrap() = (200, "ok".to_string());
+  let lines = wait_for_lines(&output_file, 4);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines[2..], ["1 http Up Down 200", "1 http Down Up 200"]);
+
+  drop(unix);
+  fs::remove_file(&socket).expect("Failed to remove the socket");
+  let lines = wait_for_lines(&output_file, 5);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines[4..], ["2 unix Up Down -"]);
+
+  // Nothing else should have fired, such as for staying down.
+  thread::sleep(Duration::from_millis(500));
+  assert_eq!(output_lines(&output_file).len(), 5);
+
+  let _ = fs::remove_dir_all(&work_dir);
+}
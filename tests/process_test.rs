@@ -0,0 +1,133 @@
+// Process trigger integration test.
+//
+// A script with a name nothing else will have is run to completion, and the
+// Sytter should see it start and then exit with the script's exit code.  Exit
+// codes need the proc connector on Linux, which needs root, so other users only
+// check that the exit was seen.
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.trim_end().to_string())
+    .collect()
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+#[test]
+fn test_process_trigger_start_and_exit() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_process_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+  let script =
+    temp_dir.join(format!("sytter-process-test-{}.sh", std::process::id()));
+  fs::write(&script, "#!/bin/sh\nsleep 1\nexit 3\n")
+    .expect("Failed to write the test script");
+  fs::set_permissions(&script, fs::Permissions::from_mode(0o755))
+    .expect("Failed to make the test script executable");
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/test_process.toml");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 30080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The trigger lists what is already running on its own thread.
+  thread::sleep(Duration::from_millis(500));
+
+  let status = Command::new(&script)
+    .status()
+    .expect("Failed to run the test script");
+  assert_eq!(status.code(), Some(3));
+
+  let user = String::from_utf8(
+    Command::new("id")
+      .arg("-un")
+      .output()
+      .expect("Failed to look up the user")
+      .stdout,
+  )
+  .unwrap()
+  .trim()
+  .to_string();
+  let exit_status = match cfg!(target_os = "linux") && user == "root" {
+    true => "3",
+    false => "none",
+  };
+  let lines = wait_for_lines(&output_file, 2);
+  println!("Output: {:?}", lines);
+  assert_eq!(
+    lines,
+    vec![
+      format!("Start none {}", user),
+      format!("Exit {} {}", exit_status, user),
+    ]
+  );
+
+  let _ = fs::remove_file(&output_file);
+  let _ = fs::remove_file(&script);
+}
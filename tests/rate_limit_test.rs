@@ -0,0 +1,131 @@
+// Trigger rate limiting integration test.
+//
+// Cron triggers that fire every second are throttled to one run per three
+// seconds, passing the leading event straight through and keeping either the
+// first or the last event of each window after it, and debounced by a window
+// they never go quiet long enough to close.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[test]
+fn test_trigger_rate_limits() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_rate_limit_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/rate_limit");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 24080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not return success within {} seconds",
+    max_wait.as_secs()
+  );
+
+  thread::sleep(Duration::from_secs(8));
+
+  let contents =
+    fs::read_to_string(&output_file).expect("Failed to read output file");
+  println!("Output contents:\n{}", contents);
+  // Each line is the kept event, how many events were collapsed into it, and
+  // how many seconds old the kept event was when the run started.
+  let runs = |keep: &str| -> Vec<(u64, u64)> {
+    contents
+      .lines()
+      .filter_map(|l| l.strip_prefix(&format!("{} ", keep)))
+      .map(|l| {
+        let (count, age) = l.split_once(' ').expect("Malformed line");
+        (count.parse().unwrap(), age.parse().unwrap())
+      })
+      .collect()
+  };
+
+  for keep in ["first", "last"] {
+    let runs = runs(keep);
+    assert!(
+      (3..=4).contains(&runs.len()),
+      "Throttling ({}) should allow one run per 3 seconds, got {:?}",
+      keep,
+      runs,
+    );
+    assert!(
+      runs[0].0 == 1 && runs[0].1 <= 1,
+      "Throttling ({}) should run the leading event right away, got {:?}",
+      keep,
+      runs,
+    );
+    assert!(
+      runs[1..].iter().all(|(count, _)| *count >= 2),
+      "Throttling ({}) should collapse the rest of each window, got {:?}",
+      keep,
+      runs,
+    );
+  }
+  assert!(
+    runs("first")[1..].iter().all(|(_, age)| *age >= 2),
+    "Keeping the first event should run with the oldest event of the window"
+  );
+  assert!(
+    runs("last")[1..].iter().all(|(_, age)| *age <= 1),
+    "Keeping the last event should run with the newest event of the window"
+  );
+  assert!(
+    !contents.lines().any(|l| l == "debounce"),
+    "A debounced trigger that never goes quiet should never run"
+  );
+  assert!(
+    !contents.lines().any(|l| l == "FAILURE"),
+    "No failure handlers should have run"
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
@@ -0,0 +1,112 @@
+// Resource threshold trigger integration test.
+//
+// One Sytter watches a shell spinning in a loop, which should go over its CPU
+// threshold and fire once it has stayed there long enough.  Another watches
+// the system load average against a threshold it can't miss.  Both should fire
+// once and then stay quiet while their thresholds keep holding.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  let mut lines: Vec<String> = fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.trim_end().to_string())
+    .collect();
+  // The two Sytters run independently, so their order isn't fixed.
+  lines.sort();
+  lines
+}
+
+#[test]
+fn test_resource_trigger_thresholds() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_resource_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/resource");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 31080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+
+  // The marker is only there so the fixture can find this shell.
+  let busy = Command::new("sh")
+    .arg("-c")
+    .arg(format!(
+      "while :; do :; done # sytter-resource-test-{}",
+      std::process::id()
+    ))
+    .spawn()
+    .expect("Failed to start the busy process");
+  let mut busy = SytterProcess::new(busy);
+  let busy_pid = busy.child.id();
+
+  // A second for the threshold to hold, and then some more where it keeps
+  // holding and nothing else should fire.
+  thread::sleep(Duration::from_secs(4));
+  let _ = busy.child.kill();
+
+  let lines = output_lines(&output_file);
+  println!("Output: {:?}", lines);
+  assert_eq!(lines.len(), 2, "Each Sytter should have fired once");
+  let busy_line: Vec<&str> = lines[0].split(' ').collect();
+  assert_eq!(busy_line[..3], ["busy", "cpu", &busy_pid.to_string()]);
+  let cpu: f64 = busy_line[3].parse().expect("CPU should be a number");
+  assert!(cpu > 50.0, "The measured CPU should be over the threshold");
+  assert_eq!(lines[1], "load load load >= 0");
+
+  let _ = fs::remove_file(&output_file);
+}
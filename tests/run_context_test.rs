@@ -0,0 +1,122 @@
+// Run context integration test.
+//
+// A cron-triggered sytter whose condition, executors, and failure handler each
+// write the run context they see to an output file.  Every component of a run
+// should agree on the sytter name and run ID, and later components should see
+// the results of earlier executors.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[test]
+fn test_run_context() {
+  let temp_dir = std::env::temp_dir();
+  let output_file = temp_dir.join(format!(
+    "sytter_run_context_test_{}.txt",
+    std::process::id()
+  ));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/test_run_context.toml");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 21080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not return success within {} seconds",
+    max_wait.as_secs()
+  );
+
+  // Give the cron (every 2 seconds) time for at least two runs.
+  thread::sleep(Duration::from_secs(5));
+
+  let contents =
+    fs::read_to_string(&output_file).expect("Failed to read output file");
+  println!("Output contents:\n{}", contents);
+  let runs: Vec<Vec<&str>> = contents
+    .lines()
+    .map(|l| l.split(' ').collect::<Vec<&str>>())
+    .collect::<Vec<_>>()
+    .chunks_exact(3)
+    .map(|run| run.concat())
+    .collect();
+  assert!(runs.len() >= 2, "Expected at least two complete runs");
+  for run in &runs {
+    let run_id = run[2];
+    assert!(!run_id.is_empty(), "Every run should have an ID");
+    assert_eq!(
+      run,
+      &vec![
+        "CONDITION",
+        "test_run_context",
+        run_id,
+        "1",
+        "0",
+        "EXECUTOR",
+        "test_run_context",
+        run_id,
+        "1",
+        "1",
+        "failed",
+        "3",
+        "FAILURE",
+        "test_run_context",
+        run_id,
+        "1",
+        "2",
+        "succeeded",
+      ],
+      "Every component in a run should see the same context, with the \
+       results of the steps before it"
+    );
+  }
+  assert_ne!(runs[0][2], runs[1][2], "Each run should get its own ID");
+
+  let _ = fs::remove_file(&output_file);
+}
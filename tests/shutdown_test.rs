@@ -0,0 +1,156 @@
+// Graceful shutdown integration test.
+//
+// A cron-triggered sytter starts a long running script, and then gets SIGTERM.
+// With a long enough grace period the script finishes, and with a short one it
+// is cancelled along with the process it started.  Either way sytter exits on
+// its own.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+/// Start sytter, wait for the first run to start, send SIGTERM, and wait for
+/// sytter to exit.  Returns the exit status, how long the exit took, the output
+/// file's contents, and the PID of the script's child process.
+fn sytter_terminate(
+  name: &str,
+  port_base: u32,
+  sleep_seconds: u32,
+  grace_period: &str,
+) -> (ExitStatus, Duration, String, String) {
+  let temp_dir = std::env::temp_dir();
+  let output_file = temp_dir.join(format!(
+    "sytter_shutdown_test_{}_{}.txt",
+    name,
+    std::process::id()
+  ));
+  let pid_file = PathBuf::from(format!("{}.pid", output_file.display()));
+  let _ = fs::remove_file(&output_file);
+  let _ = fs::remove_file(&pid_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/test_shutdown.toml");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = port_base + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .arg("--shutdown-grace-period")
+    .arg(grace_period)
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("SYTTER_TEST_SLEEP", sleep_seconds.to_string())
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let mut process = SytterProcess::new(child);
+
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) && !pid_file.exists() {
+    thread::sleep(Duration::from_millis(100));
+  }
+  thread::sleep(Duration::from_millis(200));
+  let script_child_pid = fs::read_to_string(&pid_file)
+    .expect("The first run should have started")
+    .trim()
+    .to_string();
+
+  Command::new("kill")
+    .arg("-TERM")
+    .arg(process.child.id().to_string())
+    .status()
+    .expect("Failed to send SIGTERM");
+  let terminated = Instant::now();
+  let status = loop {
+    if let Some(status) = process.child.try_wait().unwrap() {
+      break status;
+    }
+    assert!(
+      terminated.elapsed() < Duration::from_secs(15),
+      "Sytter did not exit after SIGTERM"
+    );
+    thread::sleep(Duration::from_millis(100));
+  };
+  let elapsed = terminated.elapsed();
+
+  let contents =
+    fs::read_to_string(&output_file).expect("Failed to read output file");
+  println!("Output contents ({}):\n{}", name, contents);
+  let _ = fs::remove_file(&output_file);
+  let _ = fs::remove_file(&pid_file);
+  (status, elapsed, contents, script_child_pid)
+}
+
+fn process_alive(pid: &str) -> bool {
+  Command::new("kill")
+    .arg("-0")
+    .arg(pid)
+    .stderr(Stdio::null())
+    .status()
+    .map(|s| s.success())
+    .unwrap_or(false)
+}
+
+#[test]
+fn test_shutdown_waits_for_runs() {
+  let (status, _, contents, _) = sytter_terminate("waits", 25080, 2, "10s");
+  assert!(
+    status.success(),
+    "Sytter should exit cleanly, got {}",
+    status
+  );
+  let lines: Vec<&str> = contents.lines().collect();
+  assert_eq!(
+    lines,
+    vec!["start", "end"],
+    "The run in progress should finish, and no new run should start"
+  );
+}
+
+#[test]
+fn test_shutdown_cancels_runs_after_grace_period() {
+  let (status, elapsed, contents, script_child_pid) =
+    sytter_terminate("cancels", 26080, 30, "1s");
+  assert!(
+    status.success(),
+    "Sytter should exit cleanly, got {}",
+    status
+  );
+  assert!(
+    elapsed < Duration::from_secs(10),
+    "Sytter should not wait for the script, took {:?}",
+    elapsed
+  );
+  let lines: Vec<&str> = contents.lines().collect();
+  assert_eq!(
+    lines,
+    vec!["start"],
+    "The run should be cancelled without running the failure handlers"
+  );
+  assert!(
+    !process_alive(&script_child_pid),
+    "The script's child process should not be left behind"
+  );
+}
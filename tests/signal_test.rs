@@ -0,0 +1,128 @@
+// Signal trigger integration test.
+//
+// Sytter is sent signals the way `pkill -USR1 sytter` would, and the fixture's
+// triggers report which ones they heard.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect()
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+fn signal_send(pid: u32, signal: &str) {
+  let status = Command::new("kill")
+    .arg(format!("-{}", signal))
+    .arg(pid.to_string())
+    .status()
+    .expect("Failed to run kill");
+  assert!(status.success(), "Sending {} failed", signal);
+}
+
+#[test]
+fn test_signal_trigger() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_signal_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/signal");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 37080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let pid = child.id();
+  let mut process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The triggers start listening on their own threads.
+  thread::sleep(Duration::from_millis(500));
+
+  // Each waits for the last, since the triggers run independently.
+  signal_send(pid, "USR1");
+  wait_for_lines(&output_file, 1);
+  signal_send(pid, "USR2");
+  let lines = wait_for_lines(&output_file, 2);
+  println!("Output: {:?}", lines);
+  // Signal numbers differ between systems.
+  let names = lines
+    .iter()
+    .map(|l| l.rsplit_once(' ').expect("No signal number").0)
+    .collect::<Vec<&str>>();
+  assert_eq!(names, ["0 SIGUSR1", "1 SIGUSR2"]);
+
+  // Catching a signal mustn't have stopped Sytter.
+  thread::sleep(Duration::from_millis(300));
+  assert!(
+    process
+      .child
+      .try_wait()
+      .expect("Failed to check sytter")
+      .is_none(),
+    "Sytter exited after being sent a signal",
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
@@ -0,0 +1,145 @@
+// State namespace integration test.
+//
+// Two sytters write a variable of the same name, and one of them also writes a
+// global variable.  Neither should see the other's variable, both should see
+// their trigger data in the run namespace, and callers without a run context
+// should only see the global namespace.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn state_get(url: &str, sytter_name: Option<&str>) -> Vec<String> {
+  let client = reqwest::blocking::Client::new();
+  let request = client.get(url);
+  let request = match sytter_name {
+    Some(name) => request.header("X-Sytter-Name", name),
+    None => request,
+  };
+  let mut lines: Vec<String> = request
+    .send()
+    .and_then(|r| r.text())
+    .expect("Failed to read state")
+    .lines()
+    .map(|l| l.to_string())
+    .collect();
+  lines.sort();
+  lines
+}
+
+#[test]
+fn test_state_namespaces() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_state_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/state");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 22080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not return success within {} seconds",
+    max_wait.as_secs()
+  );
+
+  // The cron fires every 2 seconds, so this gives us a couple of runs.
+  thread::sleep(Duration::from_secs(5));
+
+  let contents =
+    fs::read_to_string(&output_file).expect("Failed to read output file");
+  println!("Output contents:\n{}", contents);
+  let lines: Vec<&str> = contents.lines().collect();
+  assert!(
+    lines.contains(&"A a cron") && lines.contains(&"B b cron"),
+    "Each sytter should read back its own variable and its run's trigger data"
+  );
+  assert!(
+    lines.iter().all(|l| *l == "A a cron" || *l == "B b cron"),
+    "No sytter should see another's variable or fail"
+  );
+
+  let state_url = format!("http://localhost:{}/state", test_port);
+  assert_eq!(
+    state_get(&state_url, None),
+    vec!["sytter_shared=from-a"],
+    "Callers without a run context should only see the global namespace"
+  );
+  assert_eq!(
+    state_get(&state_url, Some("test_state_b")),
+    vec!["sytter_secret=b", "sytter_shared=from-a"],
+    "A sytter should see its own namespace over the global one"
+  );
+
+  // Scopes that are unknown, or that need a run context the caller doesn't
+  // have, are the caller's mistake.
+  let client = reqwest::blocking::Client::new();
+  let status = |request: reqwest::blocking::RequestBuilder| {
+    request
+      .send()
+      .expect("Failed to reach state")
+      .status()
+      .as_u16()
+  };
+  assert_eq!(
+    status(client.get(format!("{}?scope=nowhere", state_url))),
+    400
+  );
+  assert_eq!(status(client.get(format!("{}?scope=run", state_url))), 400);
+  assert_eq!(
+    status(
+      client.post(&state_url).json(
+        &serde_json::json!({"key": "k", "value": "v", "scope": "sytter"})
+      )
+    ),
+    400
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
@@ -0,0 +1,156 @@
+// State trigger integration test.
+//
+// Variables are written and deleted through the HTTP API, as scripts do, and
+// the fixture's triggers report the ones they care about.  Writing the same
+// value again isn't a change, so it shouldn't fire anything.
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect()
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+#[test]
+fn test_state_trigger_changes() {
+  let temp_dir = std::env::temp_dir();
+  let output_file = temp_dir.join(format!(
+    "sytter_state_trigger_test_{}.txt",
+    std::process::id()
+  ));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/state_trigger");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 35080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The triggers subscribe on their own threads.
+  thread::sleep(Duration::from_millis(500));
+
+  let client = reqwest::blocking::Client::new();
+  let state_url = format!("http://localhost:{}/state", test_port);
+  // Without a Sytter name, these go to the global namespace.
+  let write = |key: &str, value: &str, sytter: Option<&str>| {
+    let mut request = client.post(&state_url).json(
+      &[("key", key), ("value", value)]
+        .into_iter()
+        .collect::<std::collections::HashMap<&str, &str>>(),
+    );
+    if let Some(sytter) = sytter {
+      request = request.header("X-Sytter-Name", sytter);
+    }
+    let status = request.send().expect("Failed to write state").status();
+    assert!(status.is_success(), "Writing {} failed: {}", key, status);
+  };
+  let delete = |key: &str| {
+    client
+      .delete(format!("{}?key={}", state_url, key))
+      .send()
+      .expect("Failed to delete state")
+      .status()
+      .as_u16()
+  };
+
+  // Each step waits for the last, since the triggers run independently.
+  write("sytter_vpn_up", "true", None);
+  wait_for_lines(&output_file, 2);
+  write("sytter_vpn_up", "true", None);
+  write("sytter_other", "true", None);
+  write("sytter_vpn_up", "false", None);
+  wait_for_lines(&output_file, 3);
+  assert_eq!(delete("sytter_vpn_up"), 200);
+  assert_eq!(delete("sytter_vpn_up"), 404);
+  wait_for_lines(&output_file, 4);
+  write("sytter_anything", "1", Some("test_state_writer"));
+  let lines = wait_for_lines(&output_file, 5);
+  println!("Output: {:?}", lines);
+  let mut first_two = lines[..2].to_vec();
+  first_two.sort();
+  assert_eq!(
+    first_two,
+    vec![
+      "0 Create sytter_vpn_up - true",
+      "1 Create sytter_vpn_up - true",
+    ]
+  );
+  assert_eq!(
+    lines[2..],
+    [
+      "0 Change sytter_vpn_up true false",
+      "0 Delete sytter_vpn_up false -",
+      "2 Create sytter_anything - 1",
+    ]
+  );
+
+  // Nothing else should have fired, such as for the repeated write.
+  thread::sleep(Duration::from_millis(500));
+  assert_eq!(output_lines(&output_file).len(), 5);
+
+  let _ = fs::remove_file(&output_file);
+}
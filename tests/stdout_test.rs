@@ -0,0 +1,106 @@
+// Stdout trigger integration test.
+//
+// The fixture's command prints a few lines, only some of which match, and then
+// exits.  Each matching line should fire with its captures, and the command
+// should be started again after it exits.
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct TriggerStatus {
+  restarts: u64,
+  last_error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SytterStatus {
+  triggers: HashMap<String, TriggerStatus>,
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect()
+}
+
+#[test]
+fn test_stdout_trigger_lines_and_restarts() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_stdout_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/test_stdout.toml");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 32080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  // The command exits after half a second, and is started again a second
+  // after that, so a second round of lines shows it was restarted.
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5)
+    && output_lines(&output_file).len() < 4
+  {
+    thread::sleep(Duration::from_millis(100));
+  }
+  let lines = output_lines(&output_file);
+  println!("Output: {:?}", lines);
+  assert_eq!(
+    lines[..4],
+    ["hello 1|1|1", "hello 2|2|2", "hello 1|1|1", "hello 2|2|2",]
+  );
+
+  let statuses: HashMap<String, SytterStatus> =
+    reqwest::blocking::get(format!("http://localhost:{}/status", test_port))
+      .and_then(|r| r.json())
+      .expect("Failed to read status");
+  let trigger = &statuses["test_stdout"].triggers["0"];
+  println!("Trigger status: {:?}", trigger);
+  assert!(
+    trigger.restarts >= 1,
+    "The command should have been restarted"
+  );
+  assert!(trigger
+    .last_error
+    .as_ref()
+    .is_some_and(|e| e.contains("exit status: 1")));
+
+  let _ = fs::remove_file(&output_file);
+}
@@ -0,0 +1,147 @@
+// Supervisor integration test.
+//
+// One sytter has a trigger that fails every time it starts, and another can't
+// be loaded at all.  The daemon should keep running the healthy sytter, keep
+// restarting the failing trigger, and report both problems from /health.
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct HealthResponse {
+  status: String,
+  #[serde(default)]
+  problems: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TriggerStatus {
+  state: String,
+  restarts: u64,
+  last_error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SytterStatus {
+  triggers: HashMap<String, TriggerStatus>,
+}
+
+#[test]
+fn test_supervisor_restarts_and_reports() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_supervisor_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/supervisor");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 27080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  // Any response will do here, since the server is expected to be unhealthy.
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut responding = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(_) => {
+        responding = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    responding,
+    "Health check did not respond within {} seconds",
+    max_wait.as_secs()
+  );
+
+  // Failures come after 0, 1, and 3 seconds of backoff, and the third marks
+  // the trigger unhealthy.
+  thread::sleep(Duration::from_secs(5));
+
+  let response =
+    reqwest::blocking::get(&health_url).expect("Failed to get health");
+  assert_eq!(response.status().as_u16(), 503);
+  let health: HealthResponse = response.json().expect("Invalid health JSON");
+  println!("Health: {:?}", health);
+  assert_eq!(health.status, "unhealthy");
+  assert!(
+    health.problems.contains_key("test_supervisor_bad_cron"),
+    "The failing trigger should be reported"
+  );
+  assert!(
+    health.problems.keys().any(|k| k.ends_with("broken.toml")),
+    "The Sytter that failed to load should be reported"
+  );
+  assert!(
+    !health.problems.contains_key("test_supervisor_good"),
+    "The healthy Sytter should not be reported"
+  );
+
+  let statuses: HashMap<String, SytterStatus> =
+    reqwest::blocking::get(format!("http://localhost:{}/status", test_port))
+      .and_then(|r| r.json())
+      .expect("Failed to read status");
+  let trigger = &statuses["test_supervisor_bad_cron"].triggers["0"];
+  println!("Trigger status: {:?}", trigger);
+  assert_eq!(trigger.state, "unhealthy");
+  assert!(
+    trigger.restarts >= 2,
+    "The trigger should have been restarted"
+  );
+  assert!(trigger
+    .last_error
+    .as_ref()
+    .is_some_and(|e| e.contains("could not be parsed")));
+
+  let contents =
+    fs::read_to_string(&output_file).expect("Failed to read output file");
+  let lines: Vec<&str> = contents.lines().collect();
+  assert!(
+    lines.contains(&"good"),
+    "The healthy Sytter should keep running"
+  );
+  assert!(
+    lines.iter().all(|l| *l == "good"),
+    "Nothing else should have run"
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
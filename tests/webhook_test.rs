@@ -0,0 +1,236 @@
+// Webhook trigger integration test.
+//
+// One Sytter's webhook is open to anyone, and the other needs both the shared
+// secret and an HMAC signature of the body.  Webhooks that don't check out
+// should be turned away without running anything.
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to kill the sytter process on drop.
+struct SytterProcess {
+  child: Child,
+}
+
+impl SytterProcess {
+  fn new(child: Child) -> Self {
+    SytterProcess { child }
+  }
+}
+
+impl Drop for SytterProcess {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn output_lines(output_file: &PathBuf) -> Vec<String> {
+  let mut lines: Vec<String> = fs::read_to_string(output_file)
+    .unwrap_or_default()
+    .lines()
+    .map(|l| l.to_string())
+    .collect();
+  // The two Sytters run independently, so their order isn't fixed.
+  lines.sort();
+  lines
+}
+
+fn wait_for_lines(output_file: &PathBuf, count: usize) -> Vec<String> {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5) {
+    if output_lines(output_file).len() >= count {
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+  output_lines(output_file)
+}
+
+fn signature(body: &str, secret: &str) -> String {
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+  mac.update(body.as_bytes());
+  format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[test]
+fn test_webhook_trigger_routes_and_secrets() {
+  let temp_dir = std::env::temp_dir();
+  let output_file =
+    temp_dir.join(format!("sytter_webhook_test_{}.txt", std::process::id()));
+  let _ = fs::remove_file(&output_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/webhook");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 34080 + (std::process::id() % 1000);
+
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("SYTTER_TEST_OUTPUT", &output_file)
+    .env("sytter_http_port", test_port.to_string())
+    .spawn()
+    .expect("Failed to start sytter");
+  let _process = SytterProcess::new(child);
+
+  let health_url = format!("http://localhost:{}/health", test_port);
+  let start = Instant::now();
+  let max_wait = Duration::from_secs(5);
+  let mut healthy = false;
+  while start.elapsed() < max_wait {
+    match reqwest::blocking::get(&health_url) {
+      Ok(response) if response.status().is_success() => {
+        healthy = true;
+        break;
+      }
+      _ => thread::sleep(Duration::from_millis(100)),
+    }
+  }
+  assert!(
+    healthy,
+    "Health check did not pass within {} seconds",
+    max_wait.as_secs()
+  );
+  // The triggers register their routes on their own threads.
+  thread::sleep(Duration::from_millis(500));
+
+  let client = reqwest::blocking::Client::new();
+  let hooks_url = format!("http://localhost:{}/hooks", test_port);
+  let post = |path: &str, body: &str, headers: &[(&str, String)]| {
+    headers
+      .iter()
+      .fold(
+        client
+          .post(format!("{}/{}", hooks_url, path))
+          .body(body.to_string()),
+        |request, (k, v)| request.header(*k, v),
+      )
+      .send()
+      .expect("Failed to send webhook")
+      .status()
+      .as_u16()
+  };
+
+  let open_body = r#"{"ref": "refs/heads/main", "commits": [{"id": "abc"}]}"#;
+  assert_eq!(post("webhook_open/deploy?branch=main", open_body, &[]), 202);
+  assert_eq!(post("webhook_open/nothing", open_body, &[]), 404);
+  assert_eq!(post("webhook_open/deploy", "not json", &[]), 400);
+
+  let signed_body = r#"{"host": "web1"}"#;
+  let bearer = ("Authorization", "Bearer open sesame".to_string());
+  let signed = ("X-Hub-Signature-256", signature(signed_body, "sign here"));
+  assert_eq!(post("webhook_signed/remediate", signed_body, &[]), 401);
+  assert_eq!(
+    post(
+      "webhook_signed/remediate",
+      signed_body,
+      std::slice::from_ref(&bearer)
+    ),
+    401
+  );
+  assert_eq!(
+    post(
+      "webhook_signed/remediate",
+      signed_body,
+      &[
+        ("Authorization", "Bearer guess".to_string()),
+        signed.clone(),
+      ]
+    ),
+    401
+  );
+  assert_eq!(
+    post(
+      "webhook_signed/remediate",
+      r#"{"host": "web2"}"#,
+      &[bearer.clone(), signed.clone()]
+    ),
+    401
+  );
+  assert_eq!(
+    post("webhook_signed/remediate", signed_body, &[bearer, signed]),
+    202
+  );
+
+  let lines = wait_for_lines(&output_file, 2);
+  println!("Output: {:?}", lines);
+  assert_eq!(
+    lines,
+    vec![
+      "open deploy main refs/heads/main abc",
+      r#"signed remediate {"host": "web1"}"#,
+    ]
+  );
+
+  let _ = fs::remove_file(&output_file);
+}
+
+#[test]
+fn test_webhook_trigger_secrets_not_logged() {
+  let temp_dir = std::env::temp_dir();
+  let log_file = temp_dir.join(format!(
+    "sytter_webhook_log_test_{}.txt",
+    std::process::id()
+  ));
+  let _ = fs::remove_file(&log_file);
+
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let config_path = manifest_dir.join("tests/fixtures/webhook");
+
+  // Use a random port to avoid conflicts with other tests or running instances.
+  let test_port = 44080 + (std::process::id() % 1000);
+
+  // Every Sytter is logged whole at debug level as it's loaded.
+  let child = Command::new(env!("CARGO_BIN_EXE_sytter"))
+    .arg("--sytters-path")
+    .arg(&config_path)
+    .arg("--log-level")
+    .arg("debug")
+    .env("sytter_http_port", test_port.to_string())
+    .stderr(Stdio::from(
+      File::create(&log_file).expect("Failed to make the log file"),
+    ))
+    .spawn()
+    .expect("Failed to start sytter");
+  let process = SytterProcess::new(child);
+
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(5)
+    && !fs::read_to_string(&log_file)
+      .unwrap_or_default()
+      .contains("Loaded Sytter: Sytter { name: \"webhook_signed\"")
+  {
+    thread::sleep(Duration::from_millis(100));
+  }
+  drop(process);
+
+  let log = fs::read_to_string(&log_file).unwrap_or_default();
+  assert!(
+    log.contains(
+      r#"secret: Some("<redacted>"), signature_secret: Some("<redacted>")"#
+    ),
+    "The signed webhook wasn't logged: {}",
+    log,
+  );
+  assert!(log.contains(r#"secret: None, signature_secret: None"#));
+  assert!(
+    !log.contains("open sesame"),
+    "The secret was logged: {}",
+    log
+  );
+  assert!(
+    !log.contains("sign here"),
+    "The signing key was logged: {}",
+    log
+  );
+
+  let _ = fs::remove_file(&log_file);
+}